@@ -3,6 +3,7 @@ use serde::de::{DeserializeOwned, DeserializeSeed};
 use serde::Serialize;
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
+use std::path::Path;
 
 fn filename(name: &'static str) -> String {
     format!("world/{}.bc", name)
@@ -49,6 +50,17 @@ pub fn load<T: DeserializeOwned>(name: &'static str) -> Option<T> {
         .ok()
 }
 
+/// Loads a file saved with `save` from anywhere, e.g a map shipped with a scenario
+pub fn load_path<T: DeserializeOwned>(path: impl AsRef<Path>) -> Option<T> {
+    let path = path.as_ref();
+    let file = File::open(path)
+        .map_err(|err| log::error!("could not open {:?}: {}", path, err))
+        .ok()?;
+    bincode::deserialize_from(BufReader::new(file))
+        .map_err(|err| log::error!("failed deserializing {:?}: {}", path, err))
+        .ok()
+}
+
 pub fn load_seed<S: DeserializeSeed<'static>>(name: &'static str, seed: S) -> Option<S::Value> {
     seed.deserialize(&mut bincode::Deserializer::with_reader(
         load_reader(name)?,
@@ -22,6 +22,9 @@ register_resource!(Market, "market");
 #[derive(Serialize, Deserialize)]
 pub struct Market {
     markets: HashMap<CommodityKind, SingleMarket>,
+    /// Money owned by each agent, it is spent but not traded
+    #[serde(default)]
+    money: HashMap<SoulID, u32>,
 }
 
 impl Default for Market {
@@ -31,6 +34,7 @@ impl Default for Market {
                 .iter()
                 .map(|&v| (v, SingleMarket::default()))
                 .collect(),
+            money: HashMap::new(),
         }
     }
 }
@@ -47,7 +51,8 @@ pub struct Trade {
 
 impl Market {
     fn m(&mut self, kind: CommodityKind) -> &mut SingleMarket {
-        self.markets.get_mut(&kind).unwrap()
+        // saves made before a commodity was added don't have its market
+        self.markets.entry(kind).or_default()
     }
 
     /// Called when an agent tells the world it wants to sell something
@@ -84,7 +89,27 @@ impl Market {
 
     /// Get the capital that this agent owns
     pub fn capital(&self, soul: SoulID, kind: CommodityKind) -> i32 {
-        self.markets.get(&kind).unwrap().capital(soul)
+        self.markets.get(&kind).map_or(0, |m| m.capital(soul))
+    }
+
+    /// Get the money that this agent owns
+    pub fn money(&self, soul: SoulID) -> u32 {
+        self.money.get(&soul).copied().unwrap_or(0)
+    }
+
+    pub fn give_money(&mut self, soul: SoulID, amount: u32) {
+        *self.money.entry(soul).or_default() += amount;
+    }
+
+    /// Takes the amount from the money of the agent.
+    /// Returns false and takes nothing if the agent cannot afford it.
+    pub fn pay(&mut self, soul: SoulID, amount: u32) -> bool {
+        let money = self.money.entry(soul).or_default();
+        if *money < amount {
+            return false;
+        }
+        *money -= amount;
+        true
     }
 
     /// Called whenever an agent (like a farm) produces something on it's own
@@ -212,4 +237,30 @@ mod tests {
         assert_eq!(t0.buyer, buyer);
         assert_eq!(t0.qty, 2);
     }
+    #[test]
+    fn test_missing_market() {
+        let soul = SoulID(mk_ent(1));
+
+        // as in a save made before the commodity existed
+        let mut m = Market::default();
+        m.markets.remove(&CommodityKind::Meat);
+
+        assert_eq!(m.capital(soul, CommodityKind::Meat), 0);
+        assert_eq!(m.produce(soul, CommodityKind::Meat, 2), 2);
+        assert_eq!(m.capital(soul, CommodityKind::Meat), 2);
+    }
+
+    #[test]
+    fn test_pay() {
+        let soul = SoulID(mk_ent(1));
+        let mut m = Market::default();
+
+        assert!(!m.pay(soul, 1));
+        assert!(m.pay(soul, 0));
+
+        m.give_money(soul, 5);
+        assert!(m.pay(soul, 3));
+        assert!(!m.pay(soul, 3));
+        assert_eq!(m.money(soul), 2);
+    }
 }
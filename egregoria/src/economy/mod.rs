@@ -35,6 +35,14 @@ macro_rules! commodity {
                 use CommodityKind::*;
                 &[$($member),*]
             }
+
+            /// Parses the name of the variant, as written in the source
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $(stringify!($member) => Some(Self::$member)),*,
+                    _ => None,
+                }
+            }
         }
         impl Display for CommodityKind {
             fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
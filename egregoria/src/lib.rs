@@ -6,11 +6,11 @@ use crate::economy::{Bought, Sold, Workers};
 use crate::engine_interaction::{Movable, RenderStats, Selectable};
 use crate::map_dynamic::{Itinerary, Router};
 use crate::pedestrians::Pedestrian;
-use crate::physics::CollisionWorld;
+use crate::physics::{CollisionUpdates, CollisionWorld};
 use crate::physics::{Collider, Kinematics};
 use crate::rendering::assets::AssetRender;
 use crate::rendering::meshrender_component::MeshRender;
-use crate::souls::desire::{BuyFood, Desire, Home, Work};
+use crate::souls::desire::{BuyFood, Desire, Home, LuaDesire, Work};
 use crate::vehicles::Vehicle;
 use atomic_refcell::{AtomicRef, AtomicRefMut};
 use common::{GameTime, SECONDS_PER_DAY, SECONDS_PER_HOUR};
@@ -152,6 +152,7 @@ impl Egregoria {
         let t = std::time::Instant::now();
         self.schedule.execute(&mut self.world, &mut self.resources);
         ParCommandBuffer::apply(self);
+        scenarios::scenario_runner::run_scenario(self);
         self.write::<RenderStats>()
             .world_update
             .add_value(t.elapsed().as_secs_f32());
@@ -167,6 +168,7 @@ impl Egregoria {
             SECONDS_PER_DAY as f64 + 10.0 * SECONDS_PER_HOUR as f64,
         ));
         goria.insert(CollisionWorld::new(100));
+        goria.insert(CollisionUpdates::default());
         goria.insert(RandProvider::new(RNG_SEED));
         goria.insert(Deleted::<Collider>::default());
         goria.insert(Deleted::<Vehicle>::default());
@@ -229,6 +231,10 @@ impl Egregoria {
     pub fn insert<T: Resource + Send + Sync>(&mut self, res: T) {
         self.resources.insert(res)
     }
+
+    pub fn take<T: Resource>(&mut self) -> Option<T> {
+        self.resources.remove()
+    }
 }
 
 fn my_hash<T>(obj: T) -> u64
@@ -267,6 +273,7 @@ fn registry() -> Registry<u64> {
       Desire<Home>,
       Desire<BuyFood>,
       Desire<Work>,
+      Desire<LuaDesire>,
       Bought,
       Sold,
       Workers,
@@ -312,9 +319,10 @@ pub fn load_from_disk(goria: &mut Egregoria) {
         }
     });
 
-    goria.insert::<Map>(
-        common::saveload::load::<map_model::SerializedMap>("map")
-            .map(|x| x.into())
-            .unwrap_or_default(),
-    );
+    let mut map: Map = common::saveload::load::<map_model::SerializedMap>("map")
+        .map(|x| x.into())
+        .unwrap_or_default();
+    // the building shapes come from the scripts, not the save
+    map.set_building_gen(goria.try_write::<Map>().and_then(|m| m.building_gen()));
+    goria.insert::<Map>(map);
 }
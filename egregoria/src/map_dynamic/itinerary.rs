@@ -1,3 +1,4 @@
+use crate::map_dynamic::SignalActuation;
 use common::GameTime;
 use geom::Transform;
 use geom::Vec2;
@@ -81,13 +82,32 @@ impl Itinerary {
             }
         }
 
+        Self::from_route(start, cur, reversed_route, end, map)
+    }
+
+    /// Goes along the given traversables starting from the projection of `start` on the first
+    /// one, up to the end of the last one
+    pub fn follow(start: Vec2, path: Vec<Traversable>, map: &Map) -> Option<Itinerary> {
+        let end = path.last()?.points(map)?.last();
+        let mut reversed_route: Vec<Traversable> = path.into_iter().rev().collect();
+        let cur = reversed_route.pop()?;
+        Self::from_route(start, cur, reversed_route, end, map)
+    }
+
+    fn from_route(
+        start: Vec2,
+        cur: Traversable,
+        reversed_route: Vec<Traversable>,
+        end: Vec2,
+        map: &Map,
+    ) -> Option<Itinerary> {
+        let points = cur.points(map)?;
         let kind = ItineraryKind::Route(Route {
             reversed_route,
             end_pos: end,
             cur,
         });
 
-        let points = cur.points(map).unwrap();
         let (proj, segid, dir) = points.project_segment_dir(start);
 
         let mut points = points.into_vec();
@@ -126,7 +146,7 @@ impl Itinerary {
         v
     }
 
-    pub fn update(&mut self, position: Vec2, time: u32, map: &Map) {
+    pub fn update(&mut self, position: Vec2, time: u32, map: &Map, signals: &SignalActuation) {
         if let Some(p) = self.get_point() {
             let term = self.is_terminal();
             if position.is_close(p, 2.0) && term {
@@ -143,7 +163,8 @@ impl Itinerary {
                     return;
                 });
 
-                if k.can_pass(time, map.lanes()) {
+                let time = signals.seconds(k.destination_intersection(map.lanes()), time);
+                if k.can_pass(time, self.peek_travers(), map) {
                     self.advance(map);
                 }
             }
@@ -192,6 +213,14 @@ impl Itinerary {
         }
     }
 
+    /// The traversable that comes right after the current one, if any
+    pub fn peek_travers(&self) -> Option<&Traversable> {
+        match &self.kind {
+            ItineraryKind::None | ItineraryKind::WaitUntil(_) | ItineraryKind::Simple => None,
+            ItineraryKind::Route(Route { reversed_route, .. }) => reversed_route.last(),
+        }
+    }
+
     pub fn kind(&self) -> &ItineraryKind {
         &self.kind
     }
@@ -260,8 +289,9 @@ register_system!(itinerary_update);
 pub fn itinerary_update(
     #[resource] time: &GameTime,
     #[resource] map: &Map,
+    #[resource] signals: &SignalActuation,
     trans: &Transform,
     it: &mut Itinerary,
 ) {
-    it.update(trans.position(), time.seconds, map)
+    it.update(trans.position(), time.seconds, map, signals)
 }
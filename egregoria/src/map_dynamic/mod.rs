@@ -2,10 +2,14 @@ mod add_trees;
 mod house_assignment;
 mod itinerary;
 mod parking;
+mod reservations;
 mod router;
+mod signals;
 
 pub use add_trees::*;
 pub use house_assignment::*;
 pub use itinerary::*;
 pub use parking::*;
+pub use reservations::*;
 pub use router::*;
+pub use signals::*;
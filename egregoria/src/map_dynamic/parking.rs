@@ -1,49 +1,360 @@
 use dashmap::DashMap;
 use geom::Vec2;
-use map_model::{LaneKind, Map, ParkingSpotID};
+use imgui_inspect_derive::*;
+use map_model::{
+    BuildingKind, LaneID, LaneKind, Map, ParkingSpotID, ProjectKind, RoadID, Traversable,
+    TraverseDirection, TraverseKind,
+};
+use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// Distance a driver is willing to cruise around the destination looking for a street spot
+const MAX_CRUISE_DISTANCE: f32 = 400.0;
+
+/// Parking lots farther than that from the destination are not considered
+const MAX_LOT_DISTANCE: f32 = 300.0;
+
+/// A cruising driver sees the free spots up to that far ahead of them
+const SPOT_LOOKAHEAD: f32 = 40.0;
+
+/// A cruising driver cannot stop in time for the free spots closer than that
+const SPOT_MIN_AHEAD: f32 = 15.0;
+
+#[derive(Copy, Clone, Serialize, Deserialize, Inspect)]
+pub struct ParkingPricing {
+    pub enabled: bool,
+    /// Price of parking in a street spot
+    pub street: u32,
+    /// Price of parking in a parking lot
+    pub lot: u32,
+}
+
+impl Default for ParkingPricing {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            street: 3,
+            lot: 2,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ParkingOccupancy {
+    pub reserved: u32,
+    pub total: u32,
+}
+
+impl ParkingOccupancy {
+    pub fn ratio(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.reserved as f32 / self.total as f32
+    }
+}
+
+/// Occupancy of each road, rebuilt when the spots of the map change and kept up to date on
+/// reserve and free in between
+#[derive(Default)]
+struct OccupancyCache {
+    generation: Option<u32>,
+    spot_roads: HashMap<ParkingSpotID, RoadID>,
+    roads: HashMap<RoadID, ParkingOccupancy>,
+}
+
+impl OccupancyCache {
+    fn add(&mut self, spot: ParkingSpotID, delta: i32) {
+        let road = unwrap_or!(self.spot_roads.get(&spot), return);
+        if let Some(occ) = self.roads.get_mut(road) {
+            occ.reserved = (occ.reserved as i32 + delta).max(0) as u32;
+        }
+    }
+}
+
+pub struct Occupancy<'a>(MutexGuard<'a, OccupancyCache>);
+
+impl Deref for Occupancy<'_> {
+    type Target = HashMap<RoadID, ParkingOccupancy>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0.roads
+    }
+}
 
 register_resource!(ParkingManagement, "pmanagement");
-#[derive(Clone, Default, Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct ParkingManagement {
     reserved_spots: DashMap<ParkingSpotID, ()>,
+    pub pricing: ParkingPricing,
+    revenue: AtomicU64,
+    #[serde(skip)]
+    occupancy: Mutex<OccupancyCache>,
 }
 
 impl ParkingManagement {
     pub fn free(&self, spot: ParkingSpotID) {
         if self.reserved_spots.remove(&spot).is_none() {
             log::warn!("{:?} wasn't reserved", spot);
+            return;
         }
+        self.occupancy.lock().unwrap().add(spot, -1);
+    }
+
+    pub fn is_reserved(&self, spot: ParkingSpotID) -> bool {
+        self.reserved_spots.contains_key(&spot)
     }
 
+    /// Returns false if another driver took it in the meantime
+    fn reserve(&self, spot: ParkingSpotID) -> bool {
+        if self.reserved_spots.insert(spot, ()).is_some() {
+            return false;
+        }
+        self.occupancy.lock().unwrap().add(spot, 1);
+        true
+    }
+
+    /// Total money collected from paid parking
+    pub fn revenue(&self) -> u64 {
+        self.revenue.load(Ordering::Relaxed)
+    }
+
+    /// Price of the most expensive spot, a driver that can afford it can park anywhere
+    pub fn max_price(&self) -> u32 {
+        if !self.pricing.enabled {
+            return 0;
+        }
+        self.pricing.street.max(self.pricing.lot)
+    }
+
+    pub fn price(&self, spot: ParkingSpotID, map: &Map) -> u32 {
+        if !self.pricing.enabled {
+            return 0;
+        }
+        let in_lot = map
+            .parking
+            .get(spot)
+            .and_then(|spot| map.lanes().get(spot.parent))
+            .map_or(false, |lane| lane.kind == LaneKind::Driving);
+        if in_lot {
+            self.pricing.lot
+        } else {
+            self.pricing.street
+        }
+    }
+
+    /// Called when a driver paid to park
+    pub fn collect(&self, amount: u32) {
+        self.revenue.fetch_add(amount as u64, Ordering::Relaxed);
+    }
+
+    /// Reserves the closest free street spot along the parking lanes around, or else the closest
+    /// free spot of the parking lots around.
+    /// Used when there is no time to cruise, like when spawning a parked vehicle.
     pub fn reserve_near(&self, near: Vec2, map: &Map) -> Option<ParkingSpotID> {
-        let lane = map.nearest_lane(near, LaneKind::Parking)?;
+        let mut street = self.street_spots(near, map);
+        street.sort_by_key(|&(_, dist)| OrderedFloat(dist));
+        street
+            .into_iter()
+            .map(|(spot, _)| spot)
+            .find(|&spot| self.reserve(spot))
+            .or_else(|| self.reserve_lot(near, map))
+    }
+
+    /// Reserves the closest free spot of the parking lots around
+    pub fn reserve_lot(&self, near: Vec2, map: &Map) -> Option<ParkingSpotID> {
+        let mut lots = self.lot_spots(near, map);
+        lots.sort_by_key(|&(_, dist)| OrderedFloat(dist));
+        lots.into_iter()
+            .map(|(spot, _)| spot)
+            .find(|&spot| self.reserve(spot))
+    }
+
+    /// Reserves the first free spot a driver sees on the parking lane next to the driving lane
+    /// they are on, `pos` being the position of the driver.
+    pub fn reserve_ahead(&self, lane: LaneID, pos: Vec2, map: &Map) -> Option<ParkingSpotID> {
         let lane = map.lanes().get(lane)?;
+        let plane = map.roads().get(lane.parent)?.parking_next_to(lane)?;
+        let along = |p: Vec2| lane.points.distance_along(lane.points.project(p));
+        let at = along(pos);
 
-        let depth = 3;
+        let mut ahead: Vec<(ParkingSpotID, f32)> = map
+            .parking
+            .closest_spots(plane, pos)
+            .filter(|&spot| !self.is_reserved(spot))
+            .filter_map(|spot| Some((spot, along(map.parking.get(spot)?.trans.position()) - at)))
+            .filter(|&(_, d)| d > SPOT_MIN_AHEAD && d < SPOT_LOOKAHEAD)
+            .collect();
+        ahead.sort_by_key(|&(_, d)| OrderedFloat(d));
 
-        let mut potential = vec![lane];
-        let mut next = vec![];
+        ahead
+            .into_iter()
+            .map(|(spot, _)| spot)
+            .find(|&spot| self.reserve(spot))
+    }
 
-        for _ in 0..depth {
-            for lane in potential.drain(..) {
-                let parent = unwrap_or!(map.roads().get(lane.parent), continue);
+    /// The way a driver cruises around the destination looking for a street spot: starting from
+    /// the parking lane closest to it, then taking at each intersection the lane with parking
+    /// closest to the destination that wasn't driven yet.
+    pub fn cruise_path(&self, near: Vec2, map: &Map) -> Vec<Traversable> {
+        let lanes = map.lanes();
+        let roads = map.roads();
+        let has_parking = |l: LaneID| {
+            lanes.get(l).map_or(false, |l| {
+                l.kind == LaneKind::Driving
+                    && roads
+                        .get(l.parent)
+                        .map_or(false, |r| r.parking_next_to(l).is_some())
+            })
+        };
 
-                let plane = unwrap_or!(parent.parking_next_to(lane), continue);
+        let start = unwrap_or!(map.nearest_lane(near, LaneKind::Parking), return vec![]);
+        let road = unwrap_or!(roads.get(lanes[start].parent), return vec![]);
+        let mut lane = unwrap_or!(
+            road.lanes_iter()
+                .map(|(id, _)| &lanes[id])
+                .find(|l| l.kind == LaneKind::Driving && road.parking_next_to(l) == Some(start)),
+            return vec![]
+        );
 
-                for spot in map.parking.closest_spots(plane, near) {
-                    if self.reserved_spots.insert(spot, ()).is_none() {
-                        return Some(spot);
-                    }
+        let mut path = vec![Traversable::new(
+            TraverseKind::Lane(lane.id),
+            TraverseDirection::Forward,
+        )];
+        let mut seen = HashSet::new();
+        seen.insert(lane.id);
+        let mut dist = lane.length;
+
+        while dist < MAX_CRUISE_DISTANCE {
+            let inter = unwrap_or!(map.intersections().get(lane.dst), break);
+            let next = inter
+                .turns_from(lane.id)
+                .filter(|&(turn, dir)| {
+                    dir == TraverseDirection::Forward
+                        && !seen.contains(&turn.dst)
+                        && has_parking(turn.dst)
+                })
+                .min_by_key(|&(turn, _)| OrderedFloat(lanes[turn.dst].points.project_dist2(near)));
+            let (turn, _) = unwrap_or!(next, break);
+
+            lane = &lanes[turn.dst];
+            seen.insert(lane.id);
+            dist += lane.length;
+            path.push(Traversable::new(
+                TraverseKind::Turn(turn),
+                TraverseDirection::Forward,
+            ));
+            path.push(Traversable::new(
+                TraverseKind::Lane(lane.id),
+                TraverseDirection::Forward,
+            ));
+        }
+
+        path
+    }
+
+    /// Explores the driving lanes around the destination in order of distance driven, taking the
+    /// closest free spot of each parking lane along the way, with its distance to the destination.
+    fn street_spots(&self, near: Vec2, map: &Map) -> Vec<(ParkingSpotID, f32)> {
+        let mut candidates = vec![];
+        let lanes = map.lanes();
+        let start = unwrap_or!(map.nearest_lane(near, LaneKind::Parking), return candidates);
+        let road = unwrap_or!(map.roads().get(lanes[start].parent), return candidates);
+
+        let mut queue = BinaryHeap::new();
+        let mut seen = HashSet::new();
+        for (id, kind) in road.lanes_iter() {
+            if kind == LaneKind::Driving {
+                queue.push((Reverse(OrderedFloat(0.0)), id));
+            }
+        }
+
+        while let Some((Reverse(OrderedFloat(dist)), id)) = queue.pop() {
+            if dist > MAX_CRUISE_DISTANCE || !seen.insert(id) {
+                continue;
+            }
+            let lane = unwrap_or!(lanes.get(id), continue);
+            let parent = unwrap_or!(map.roads().get(lane.parent), continue);
+
+            if let Some(plane) = parent.parking_next_to(lane) {
+                candidates.extend(
+                    map.parking
+                        .closest_spots(plane, near)
+                        .filter(|&spot| !self.is_reserved(spot))
+                        .find_map(|spot| Some((spot, self.distance(spot, near, map)?))),
+                );
+            }
+
+            let inter = unwrap_or!(map.intersections().get(lane.dst), continue);
+            for (turn, dir) in inter.turns_from(id) {
+                if dir == TraverseDirection::Forward
+                    && lanes
+                        .get(turn.dst)
+                        .map_or(false, |l| l.kind == LaneKind::Driving)
+                {
+                    queue.push((Reverse(OrderedFloat(dist + lane.length)), turn.dst));
                 }
-                next.extend(
-                    map.intersections()[lane.dst]
-                        .turns_from(lane.id)
-                        .map(|(turn, _)| &map.lanes()[turn.dst]),
-                )
             }
-            std::mem::swap(&mut potential, &mut next);
         }
-        None
+        candidates
+    }
+
+    /// The first free spot of each parking lot around, with its distance to the destination
+    fn lot_spots(&self, near: Vec2, map: &Map) -> Vec<(ParkingSpotID, f32)> {
+        let buildings = map.buildings();
+        let mut candidates = vec![];
+        for obj in map.spatial_map().query_around(near, MAX_LOT_DISTANCE) {
+            let b = match obj {
+                ProjectKind::Building(b) => b,
+                _ => continue,
+            };
+            if !matches!(buildings.get(b), Some(x) if x.kind == BuildingKind::ParkingLot) {
+                continue;
+            }
+            candidates.extend(
+                map.parking
+                    .lot_spots(b)
+                    .filter(|&spot| !self.is_reserved(spot))
+                    .find_map(|spot| Some((spot, self.distance(spot, near, map)?))),
+            );
+        }
+        candidates
+    }
+
+    fn distance(&self, spot: ParkingSpotID, near: Vec2, map: &Map) -> Option<f32> {
+        Some(map.parking.get(spot)?.trans.position().distance(near))
+    }
+
+    /// Reserved and total spots of each road, lots being counted with the road they are accessed
+    /// from. Only recomputed from scratch when the spots of the map change.
+    pub fn occupancy(&self, map: &Map) -> Occupancy<'_> {
+        let mut cache = self.occupancy.lock().unwrap();
+        if cache.generation == Some(map.parking.generation()) {
+            return Occupancy(cache);
+        }
+
+        let mut spot_roads = HashMap::new();
+        let mut roads: HashMap<RoadID, ParkingOccupancy> = HashMap::new();
+        for (id, spot) in map.parking.iter() {
+            let lane = unwrap_or!(map.lanes().get(spot.parent), continue);
+            spot_roads.insert(id, lane.parent);
+            let v = roads.entry(lane.parent).or_default();
+            v.total += 1;
+            if self.is_reserved(id) {
+                v.reserved += 1;
+            }
+        }
+
+        *cache = OccupancyCache {
+            generation: Some(map.parking.generation()),
+            spot_roads,
+            roads,
+        };
+        Occupancy(cache)
     }
 }
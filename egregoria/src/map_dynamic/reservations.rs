@@ -0,0 +1,100 @@
+use common::GameTime;
+use dashmap::DashMap;
+use legion::{system, Entity};
+use map_model::{Map, TurnID};
+use std::collections::HashMap;
+
+/// Entries that were not refreshed for this long are considered stale (vehicle despawned or rerouted)
+const RESERVATION_TIMEOUT: f64 = 0.5;
+
+register_resource_noserialize!(TurnReservations);
+/// Keeps track of which vehicles are on or waiting to enter a turn, so that vehicles on
+/// conflicting turns can give way to each other.
+#[derive(Default)]
+pub struct TurnReservations {
+    /// Vehicles currently driving on a turn, with the last time they were seen
+    occupied: DashMap<TurnID, HashMap<Entity, f64>>,
+    /// Vehicles waiting before a turn, with their arrival time and the last time they were seen
+    waiting: DashMap<TurnID, HashMap<Entity, (f64, f64)>>,
+}
+
+impl TurnReservations {
+    /// Marks the vehicle as driving on the turn. Must be called every frame while on it.
+    pub fn occupy(&self, turn: TurnID, ent: Entity, now: f64) {
+        self.occupied.entry(turn).or_default().insert(ent, now);
+        if let Some(mut w) = self.waiting.get_mut(&turn) {
+            w.remove(&ent);
+        }
+    }
+
+    /// Marks the vehicle as waiting to enter the turn. Must be called every frame while waiting.
+    pub fn wait(&self, turn: TurnID, ent: Entity, now: f64) {
+        self.waiting
+            .entry(turn)
+            .or_default()
+            .entry(ent)
+            .or_insert((now, now))
+            .1 = now;
+    }
+
+    /// Whether the vehicle has right of way to enter the turn, given the vehicles on or
+    /// waiting before the conflicting turns.
+    pub fn may_enter(&self, turn: TurnID, ent: Entity, map: &Map) -> bool {
+        let inter = unwrap_or!(map.intersections().get(turn.parent), return true);
+        let turn = unwrap_or!(inter.find_turn(turn), return true);
+
+        let my_arrival = self
+            .waiting
+            .get(&turn.id)
+            .and_then(|w| w.get(&ent).map(|&(arrival, _)| arrival))
+            .unwrap_or(f64::INFINITY);
+
+        for &c in &turn.conflicts {
+            let other = unwrap_or!(inter.find_turn(c), continue);
+
+            // waiting is checked before occupied so that a vehicle moving from one to the other
+            // during this frame is always seen
+            if let Some(w) = self.waiting.get(&c) {
+                let must_yield = if turn.yields_to(other, map.lanes()) {
+                    w.keys().any(|&e| e != ent)
+                } else if other.yields_to(turn, map.lanes()) {
+                    false
+                } else {
+                    w.iter()
+                        .any(|(&e, &(arrival, _))| e != ent && (arrival, e) < (my_arrival, ent))
+                };
+                if must_yield {
+                    return false;
+                }
+            }
+
+            if let Some(occ) = self.occupied.get(&c) {
+                if occ.keys().any(|&e| e != ent) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn maintain(&mut self, now: f64) {
+        let limit = now - RESERVATION_TIMEOUT;
+        self.occupied.retain(|_, occ| {
+            occ.retain(|_, last_seen| *last_seen >= limit);
+            !occ.is_empty()
+        });
+        self.waiting.retain(|_, w| {
+            w.retain(|_, (_, last_seen)| *last_seen >= limit);
+            !w.is_empty()
+        });
+    }
+}
+
+register_system!(turn_reservations_maintain);
+#[system]
+pub fn turn_reservations_maintain(
+    #[resource] res: &mut TurnReservations,
+    #[resource] time: &GameTime,
+) {
+    res.maintain(time.timestamp);
+}
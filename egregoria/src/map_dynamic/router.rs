@@ -1,14 +1,15 @@
+use crate::economy::Market;
 use crate::map_dynamic::{Itinerary, ParkingManagement};
 use crate::pedestrians::{put_pedestrian_in_coworld, Location};
 use crate::physics::{Collider, CollisionWorld, Kinematics};
 use crate::rendering::meshrender_component::MeshRender;
 use crate::vehicles::{put_vehicle_in_coworld, Vehicle, VehicleID, VehicleState};
-use crate::{Egregoria, ParCommandBuffer};
+use crate::{Egregoria, ParCommandBuffer, SoulID};
 use geom::{Spline, Transform, Vec2};
 use imgui_inspect_derive::*;
 use legion::world::SubWorld;
 use legion::{system, Entity, EntityStore};
-use map_model::{BuildingID, CarPath, Map, ParkingSpotID, PedestrianPath};
+use map_model::{BuildingID, CarPath, Map, ParkingSpotID, PedestrianPath, TraverseKind};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Inspect, Serialize, Deserialize)]
@@ -33,6 +34,8 @@ debug_inspect_impl!(Destination);
 pub enum RoutingStep {
     WalkTo(Vec2),
     DriveTo(VehicleID, Vec2),
+    /// Drives around the destination until a street spot is found
+    Cruise(VehicleID, Vec2),
     Park(VehicleID, ParkingSpotID),
     Unpark(VehicleID),
     GetInVehicle(VehicleID),
@@ -52,6 +55,7 @@ pub fn routing_update(
     #[resource] map: &Map,
     #[resource] cbuf: &ParCommandBuffer,
     #[resource] parking: &ParkingManagement,
+    #[resource] market: &Market,
     body: &Entity,
     trans: &Transform,
     itin: &Itinerary,
@@ -63,11 +67,6 @@ pub fn routing_update(
 ) {
     let pos = trans.position();
     if !router.reroute {
-        let next_step = unwrap_or!(router.steps.last(), {
-            router.cur_step = None;
-            return;
-        });
-
         let mut cur_step_over = true;
 
         if let Some(step) = router.cur_step {
@@ -79,6 +78,9 @@ pub fn routing_update(
                     .get_component::<Itinerary>()
                     .unwrap()
                     .has_ended(0.0),
+                RoutingStep::Cruise(vehicle, obj) => {
+                    router.cruise(vehicle, obj, parking, map, subworld)
+                }
                 RoutingStep::Park(vehicle, _) => matches!(
                     subworld
                         .entry_ref(vehicle.0)
@@ -96,9 +98,16 @@ pub fn routing_update(
             };
         }
 
+        // cruising pushes the steps to park, so the next step is only known now
+        let next_step = unwrap_or!(router.steps.last(), {
+            router.cur_step = None;
+            return;
+        });
+
         let next_step_ready = match next_step {
             RoutingStep::WalkTo(_) => true,
             RoutingStep::DriveTo(_, _) => true,
+            RoutingStep::Cruise(_, _) => true,
             RoutingStep::Park(_, _) => true,
             RoutingStep::Unpark(_) => true,
             RoutingStep::GetInVehicle(vehicle) => subworld
@@ -132,13 +141,25 @@ pub fn routing_update(
                     cbuf.add_component(vehicle.0, route);
                 }
             }
+            RoutingStep::Cruise(vehicle, obj) => {
+                let vpos = subworld
+                    .entry_ref(vehicle.0)
+                    .unwrap()
+                    .get_component::<Transform>()
+                    .unwrap()
+                    .position();
+                let path = parking.cruise_path(obj, map);
+                if let Some(route) = Itinerary::follow(vpos, path, map) {
+                    cbuf.add_component(vehicle.0, route);
+                }
+            }
             RoutingStep::Park(vehicle, spot) => {
                 if !map.parking.contains(spot) {
                     router.reroute = true;
                     return;
                 }
 
-                cbuf.exec(park(vehicle, spot));
+                cbuf.exec(park(vehicle, spot, SoulID(*body)));
             }
             RoutingStep::Unpark(vehicle) => {
                 cbuf.exec(unpark(vehicle));
@@ -172,10 +193,11 @@ pub fn routing_update(
 
     // router is dirty
     let dest = router.dest.expect("destination is empty but dirty is true");
+    let money = market.money(SoulID(*body));
     router.clear_steps(parking);
     match dest {
         Destination::Outside(pos) => {
-            router.steps = router.steps_to(pos, parking, map, loc, subworld)
+            router.steps = router.steps_to(pos, money, parking, map, loc, subworld)
         }
         Destination::Building(build) => {
             if let Location::Building(cur_build) = loc {
@@ -185,7 +207,7 @@ pub fn routing_update(
             }
 
             let door_pos = map.buildings()[build].door_pos;
-            router.steps = router.steps_to(door_pos, parking, map, loc, subworld);
+            router.steps = router.steps_to(door_pos, money, parking, map, loc, subworld);
             router.steps.push(RoutingStep::GetInBuilding(build));
         }
     }
@@ -216,7 +238,23 @@ fn walk_outside(
     });
 }
 
-fn park(vehicle: VehicleID, spot_id: ParkingSpotID) -> impl FnOnce(&mut Egregoria) {
+/// Where to stop on the driving lane before getting into the spot
+fn parking_pos(map: &Map, spot: ParkingSpotID) -> Option<Vec2> {
+    let lane = map.parking_to_drive(spot)?;
+    let spot = map.parking.get(spot)?;
+
+    let (pos, _, dir) = map.lanes()[lane]
+        .points
+        .project_segment_dir(spot.trans.position());
+    Some(pos - dir * 4.0)
+}
+
+fn cruise_start(obj: Vec2, parking: &ParkingManagement, map: &Map) -> Option<Vec2> {
+    let first = *parking.cruise_path(obj, map).first()?;
+    Some(first.points(map)?.first())
+}
+
+fn park(vehicle: VehicleID, spot_id: ParkingSpotID, soul: SoulID) -> impl FnOnce(&mut Egregoria) {
     move |goria| {
         let trans = goria.comp::<Transform>(vehicle.0).unwrap();
         let map = goria.read::<Map>();
@@ -228,6 +266,15 @@ fn park(vehicle: VehicleID, spot_id: ParkingSpotID) -> impl FnOnce(&mut Egregori
             }
         };
 
+        let pm = goria.read::<ParkingManagement>();
+        let price = pm.price(spot_id, &map);
+        if goria.write::<Market>().pay(soul, price) {
+            pm.collect(price);
+        } else {
+            log::warn!("{:?} couldn't pay {} to park at {:?}", soul, price, spot_id);
+        }
+        drop(pm);
+
         let s = Spline {
             from: trans.position(),
             to: spot.trans.position(),
@@ -295,9 +342,70 @@ impl Router {
         false
     }
 
+    /// Looks for a free spot ahead of the cruising vehicle, and once one is reserved pushes the
+    /// steps to park in it. Returns whether the cruise is over.
+    fn cruise(
+        &mut self,
+        vehicle: VehicleID,
+        obj: Vec2,
+        parking: &ParkingManagement,
+        map: &Map,
+        subworld: &SubWorld,
+    ) -> bool {
+        let ent = subworld.entry_ref(vehicle.0).unwrap();
+        let itin = ent.get_component::<Itinerary>().unwrap();
+        let vpos = ent.get_component::<Transform>().unwrap().position();
+
+        if let Some(TraverseKind::Lane(lane)) = itin.get_travers().map(|t| t.kind) {
+            if let Some(spot) = parking.reserve_ahead(lane, vpos, map) {
+                if self.park_in(vehicle, spot, parking, map) {
+                    return true;
+                }
+            }
+        }
+
+        if !itin.has_ended(0.0) {
+            return false;
+        }
+
+        // no street spot along the way, fall back to the lots
+        if let Some(spot) = parking
+            .reserve_lot(obj, map)
+            .or_else(|| parking.reserve_near(obj, map))
+        {
+            if self.park_in(vehicle, spot, parking, map) {
+                return true;
+            }
+        }
+
+        // everything is taken, go around again
+        let start = cruise_start(obj, parking, map).unwrap_or(vpos);
+        self.steps.push(RoutingStep::Cruise(vehicle, obj));
+        self.steps.push(RoutingStep::DriveTo(vehicle, start));
+        true
+    }
+
+    /// Pushes the steps to park in the reserved spot, the steps being reversed
+    fn park_in(
+        &mut self,
+        vehicle: VehicleID,
+        spot: ParkingSpotID,
+        parking: &ParkingManagement,
+        map: &Map,
+    ) -> bool {
+        let pos = unwrap_or!(parking_pos(map, spot), {
+            parking.free(spot);
+            return false;
+        });
+        self.steps.push(RoutingStep::Park(vehicle, spot));
+        self.steps.push(RoutingStep::DriveTo(vehicle, pos));
+        true
+    }
+
     fn steps_to(
         &self,
         obj: Vec2,
+        money: u32,
         parking: &ParkingManagement,
         map: &Map,
         loc: &Location,
@@ -308,16 +416,25 @@ impl Router {
             steps.push(RoutingStep::GetOutBuilding(*cur_build));
         }
 
-        if let Some(car) = self.vehicle {
-            if let Some(spot_id) = parking.reserve_near(obj, &map) {
-                let lane = map.parking_to_drive(spot_id).unwrap();
-                let spot = *map.parking.get(spot_id).unwrap();
-
-                let (pos, _, dir) = map.lanes()[lane]
-                    .points
-                    .project_segment_dir(spot.trans.position());
-                let parking_pos = pos - dir * 4.0;
+        // the car is only taken if parking it is affordable, but a driver has to park anyway
+        let can_park = money >= parking.max_price() || matches!(loc, Location::Vehicle(_));
+        if let Some(car) = self.vehicle.filter(|_| can_park) {
+            // cruise along the parking lanes around the destination if there are any,
+            // else go straight to a spot
+            let park_steps = match cruise_start(obj, parking, map) {
+                Some(start) => Some([
+                    RoutingStep::DriveTo(car, start),
+                    RoutingStep::Cruise(car, obj),
+                ]),
+                None => parking.reserve_near(obj, map).and_then(|spot| {
+                    Some([
+                        RoutingStep::DriveTo(car, parking_pos(map, spot)?),
+                        RoutingStep::Park(car, spot),
+                    ])
+                }),
+            };
 
+            if let Some(park_steps) = park_steps {
                 if !matches!(loc, Location::Vehicle(_)) {
                     // safety: only pedestrians have transforms, not cars
                     let carpos = subworld
@@ -332,8 +449,7 @@ impl Router {
                     steps.push(RoutingStep::Unpark(car));
                 }
 
-                steps.push(RoutingStep::DriveTo(car, parking_pos));
-                steps.push(RoutingStep::Park(car, spot_id));
+                steps.extend_from_slice(&park_steps);
                 steps.push(RoutingStep::GetOutVehicle(car));
             }
         }
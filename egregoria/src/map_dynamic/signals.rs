@@ -0,0 +1,135 @@
+use crate::map_dynamic::Itinerary;
+use crate::vehicles::Vehicle;
+use common::GameTime;
+use geom::Transform;
+use legion::world::SubWorld;
+use legion::{system, IntoQuery};
+use map_model::{IntersectionID, LaneID, LightPolicy, Map, RoadID, TrafficBehavior, TraverseKind};
+use std::collections::{HashMap, HashSet};
+
+/// Vehicles closer than this to the end of a lane trigger the lane's detector
+const DETECTOR_LENGTH: f32 = 25.0;
+
+/// How far behind the game time the lights of an actuated intersection are
+#[derive(Default)]
+struct Hold {
+    /// Seconds the lights are behind, given back at the next green of `road`
+    held: u32,
+    /// Seconds the current green was extended by
+    extension: u32,
+    road: Option<RoadID>,
+}
+
+register_resource_noserialize!(SignalActuation);
+/// Actuated intersections extend their green by holding their lights back.
+/// The schedules of the map are never changed, so coordinated intersections come back in the
+/// green wave once the held seconds have been given back.
+#[derive(Default)]
+pub struct SignalActuation {
+    last_seconds: u32,
+    holds: HashMap<IntersectionID, Hold>,
+}
+
+impl SignalActuation {
+    /// The time shown by the lights of that intersection
+    pub fn seconds(&self, inter: IntersectionID, seconds: u32) -> u32 {
+        self.holds
+            .get(&inter)
+            .map_or(seconds, |h| seconds.saturating_sub(h.held))
+    }
+}
+
+/// Whether the lane has a light or follows a signal plan and is green
+fn is_green(map: &Map, lane: LaneID, seconds: u32) -> bool {
+    let lane = unwrap_or!(map.lanes().get(lane), return false);
+    (lane.control.is_light() || lane.control.is_plan())
+        && matches!(
+            map.lane_behavior(lane, None, seconds),
+            TrafficBehavior::GREEN
+        )
+}
+
+register_system!(signals_actuate);
+#[system]
+#[read_component(Transform)]
+#[read_component(Itinerary)]
+#[read_component(Vehicle)]
+pub fn signals_actuate(
+    #[resource] map: &Map,
+    #[resource] time: &GameTime,
+    #[resource] act: &mut SignalActuation,
+    sw: &SubWorld,
+) {
+    let prev = act.last_seconds;
+    if time.seconds <= prev {
+        return;
+    }
+    act.last_seconds = time.seconds;
+    let elapsed = time.seconds - prev;
+
+    let mut detected: HashSet<LaneID> = HashSet::new();
+    for (trans, it, _) in <(&Transform, &Itinerary, &Vehicle)>::query().iter(sw) {
+        if let Some(TraverseKind::Lane(l)) = it.get_travers().map(|t| t.kind) {
+            let lane = unwrap_or!(map.lanes().get(l), continue);
+            if (lane.control.is_light() || lane.control.is_plan())
+                && lane
+                    .control_point()
+                    .is_close(trans.position(), DETECTOR_LENGTH)
+            {
+                detected.insert(l);
+            }
+        }
+    }
+
+    act.holds
+        .retain(|&id, _| map.intersections().contains_key(id));
+
+    for (id, inter) in map.intersections() {
+        let timing = &inter.light_timing;
+        if !timing.actuated || matches!(inter.light_policy, LightPolicy::NoLights) {
+            act.holds.remove(&id);
+            continue;
+        }
+
+        let hold = act.holds.entry(id).or_default();
+        let shown_prev = prev.saturating_sub(hold.held);
+        let shown = time.seconds.saturating_sub(hold.held);
+
+        // A detected lane is about to turn orange: hold the lights to keep it green
+        let extended = inter.roads.iter().copied().find(|&r| {
+            map.roads()[r].incoming_lanes_to(id).iter().any(|&(l, _)| {
+                detected.contains(&l) && is_green(map, l, shown_prev) && !is_green(map, l, shown)
+            })
+        });
+
+        if let Some(road) = extended.filter(|_| hold.extension < timing.max_extension) {
+            let delay = elapsed.min(timing.max_extension - hold.extension);
+            hold.extension += delay;
+            hold.held += delay;
+            hold.road = Some(road);
+            continue;
+        }
+        hold.extension = 0;
+
+        // Give the held seconds back at the start of the extended road's next green,
+        // skipping at most to the end of it so that it still goes through orange
+        let lanes = unwrap_or!(
+            hold.road
+                .and_then(|r| map.roads().get(r))
+                .map(|r| r.incoming_lanes_to(id)),
+            continue
+        );
+        let green = |s| lanes.iter().any(|&(l, _)| is_green(map, l, s));
+        if hold.held == 0 || !green(shown) {
+            continue;
+        }
+        let mut skip = 0;
+        while skip < hold.held && green(shown + skip + 1) {
+            skip += 1;
+        }
+        hold.held -= skip;
+        if hold.held == 0 {
+            hold.road = None;
+        }
+    }
+}
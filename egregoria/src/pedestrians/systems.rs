@@ -1,12 +1,12 @@
-use crate::map_dynamic::Itinerary;
+use crate::map_dynamic::{Itinerary, SignalActuation, TurnReservations};
 use crate::pedestrians::Pedestrian;
 use crate::physics::{Collider, CollisionWorld, Kinematics, PhysicsObject};
 use crate::rendering::meshrender_component::MeshRender;
 use crate::utils::Restrict;
 use common::GameTime;
 use geom::{angle_lerp, Transform, Vec2};
-use legion::system;
-use map_model::{Map, TraverseDirection};
+use legion::{system, Entity};
+use map_model::{Map, TraverseDirection, TraverseKind};
 
 register_system!(pedestrian_decision);
 #[system(par_for_each)]
@@ -14,6 +14,9 @@ pub fn pedestrian_decision(
     #[resource] cow: &CollisionWorld,
     #[resource] map: &Map,
     #[resource] time: &GameTime,
+    #[resource] reservations: &TurnReservations,
+    #[resource] signals: &SignalActuation,
+    me: &Entity,
     coll: &Collider,
     it: &mut Itinerary,
     trans: &mut Transform,
@@ -27,7 +30,13 @@ pub fn pedestrian_decision(
     let objs =
         neighbors.map(|(id, pos)| (pos, cow.get(id).expect("Handle not in collision world").1));
 
-    let (desired_v, desired_dir) = calc_decision(pedestrian, trans, kin, map, my_obj, it, objs);
+    // Let the vehicles know someone is on the crosswalk
+    if let Some(TraverseKind::Turn(t)) = it.get_travers().map(|x| x.kind) {
+        reservations.occupy(t, *me, time.timestamp);
+    }
+
+    let (desired_v, desired_dir) =
+        calc_decision(pedestrian, trans, kin, map, signals, time, my_obj, it, objs);
 
     walk_anim(pedestrian, mr, time, kin);
     physics(kin, trans, time, desired_v, desired_dir);
@@ -79,6 +88,8 @@ pub fn calc_decision<'a>(
     trans: &Transform,
     kin: &Kinematics,
     map: &Map,
+    signals: &SignalActuation,
+    time: &GameTime,
     my_obj: &PhysicsObject,
     it: &Itinerary,
     neighs: impl Iterator<Item = (Vec2, &'a PhysicsObject)>,
@@ -97,6 +108,17 @@ pub fn calc_decision<'a>(
         None => return (Vec2::ZERO, trans.direction()),
     };
 
+    // Wait at the curb for the walk signal
+    if it.remaining_points() == 1 && position.is_close(objective, 1.0) {
+        if let Some(travers) = it.get_travers() {
+            let seconds =
+                signals.seconds(travers.destination_intersection(map.lanes()), time.seconds);
+            if !travers.can_pass(seconds, it.peek_travers(), map) {
+                return (Vec2::ZERO, direction);
+            }
+        }
+    }
+
     let mut desired_v = dir_to_pos * pedestrian.walking_speed;
 
     for (his_pos, his_obj) in neighs {
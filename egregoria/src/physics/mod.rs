@@ -45,6 +45,10 @@ impl Default for PhysicsObject {
 
 pub type CollisionWorld = flat_spatial::DenseGrid<PhysicsObject>;
 
+/// New collider positions, applied to the CollisionWorld all at once by coworld_maintain
+#[derive(Default)]
+pub struct CollisionUpdates(pub Vec<(GridHandle, Vec2)>);
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Collider(pub GridHandle);
 
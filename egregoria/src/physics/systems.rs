@@ -1,4 +1,4 @@
-use crate::physics::{Collider, Kinematics};
+use crate::physics::{Collider, CollisionUpdates, Kinematics};
 use crate::vehicles::Vehicle;
 use crate::{CollisionWorld, Deleted};
 use common::GameTime;
@@ -19,12 +19,13 @@ register_system!(coworld_synchronize);
 #[system(for_each)]
 pub fn coworld_synchronize(
     #[resource] coworld: &mut CollisionWorld,
+    #[resource] updates: &mut CollisionUpdates,
     transform: &Transform,
     kin: &Kinematics,
     collider: &Collider,
     v: Option<&Vehicle>,
 ) {
-    coworld.set_position(collider.0, transform.position());
+    updates.0.push((collider.0, transform.position()));
     let (_, po) = coworld.get_mut(collider.0).unwrap(); // Unwrap ok: handle is deleted only when entity is deleted too
     po.dir = transform.direction();
     po.speed = kin.velocity.magnitude();
@@ -37,11 +38,13 @@ register_system!(coworld_maintain);
 #[system]
 pub fn coworld_maintain(
     #[resource] coworld: &mut CollisionWorld,
+    #[resource] updates: &mut CollisionUpdates,
     #[resource] evts: &mut Deleted<Collider>,
 ) {
     for Collider(handle) in evts.drain() {
         coworld.remove(handle);
     }
 
-    coworld.maintain();
+    coworld.par_update(&updates.0);
+    updates.0.clear();
 }
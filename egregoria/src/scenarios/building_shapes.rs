@@ -0,0 +1,144 @@
+use super::console::LuaConsole;
+use super::LuaColor;
+use geom::Vec2;
+use map_model::procgen::{BuildingGen, ColoredMesh};
+use map_model::{BuildingKind, Map};
+use mods::mlua::{Function, Lua, Table};
+use mods::{LuaApi, LuaFn, LuaPolygon, LuaVec2};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Every script in that directory can register building shapes
+const SHAPES_DIR: &str = "lua/buildings";
+
+/// Name of the global table mapping building kinds to their shape function
+const SHAPES: &str = "__building_shapes";
+
+struct LuaBuildingGen {
+    l: Mutex<Lua>,
+}
+
+impl LuaBuildingGen {
+    fn mesh(faces: Table) -> mods::mlua::Result<ColoredMesh> {
+        let mut mesh = ColoredMesh::default();
+        for face in faces.sequence_values::<Table>() {
+            let face = face?;
+            let poly: LuaPolygon = face.get(1)?;
+            let col: LuaColor = face.get(2)?;
+            mesh.faces.push((poly.0, col.0.into()));
+        }
+        Ok(mesh)
+    }
+}
+
+impl BuildingGen for LuaBuildingGen {
+    fn gen(&self, kind: BuildingKind, size: f32) -> Option<(ColoredMesh, Vec2)> {
+        let l = self.l.lock().unwrap();
+        let shapes: Table = l.globals().get(SHAPES).ok()?;
+        let f: Function = shapes
+            .get::<_, Option<Function>>(format!("{:?}", kind))
+            .ok()??;
+
+        // the map cannot reach the console, errors only go to the log before falling back
+        let (faces, door): (Table, LuaVec2) = mods::call(&l, &f, size)?;
+        match Self::mesh(faces) {
+            Ok(mesh) => Some((mesh, door.0)),
+            Err(e) => {
+                log::error!("invalid mesh returned for {:?}: {}", kind, e);
+                None
+            }
+        }
+    }
+}
+
+/// Globals of the building shape scripts
+pub(crate) const SHAPES_API: LuaApi = LuaApi {
+    doc: "Building shapes, registered by the scripts of lua/buildings",
+    class: None,
+    functions: &[LuaFn {
+        name: "register_building",
+        doc: "Replaces the builtin shape of a building kind, f returns the faces of the building \
+              centered\non the origin as a list of { Polygon, Color } and the door \
+              position",
+        params: &[
+            (
+                "kind",
+                "string \"House\" | \"Workplace\" | \"Supermarket\" | \"CerealFarm\" | ...",
+            ),
+            ("f", "fun(size: number): table, Vec2"),
+        ],
+        ret: "",
+    }],
+};
+
+fn register_building(l: &Lua, (kind, f): (String, Function)) -> mods::mlua::Result<()> {
+    let shapes: Table = l.globals().get(SHAPES)?;
+    shapes.set(kind, f)
+}
+
+register_resource_noserialize!(BuildingShapes);
+/// Building shapes registered by the scripts of `lua/buildings` with `register_building`.
+/// Kinds without a registered shape keep the one generated in rust.
+#[derive(Default)]
+pub struct BuildingShapes {
+    kinds: Vec<String>,
+    files: Vec<PathBuf>,
+    loaded: bool,
+}
+
+impl BuildingShapes {
+    /// Building kinds with a shape defined in lua
+    pub fn kinds(&self) -> &[String] {
+        &self.kinds
+    }
+
+    /// Loads every script of `lua/buildings` in a fresh VM,
+    /// its shapes are used for the buildings of the map built from now on
+    pub fn reload(&mut self, console: &mut LuaConsole, map: &mut Map) {
+        self.loaded = true;
+        let l = match mods::try_new() {
+            Ok(l) => l,
+            Err(e) => {
+                console.error(SHAPES_DIR, e);
+                return;
+            }
+        };
+        if let Err(e) = l.create_table().and_then(|t| l.globals().set(SHAPES, t)) {
+            console.error(SHAPES_DIR, e);
+            return;
+        }
+        super::add_egregoria_lua_stdlib(&l);
+        mods::add_fn(&l, SHAPES_API.name("register_building"), register_building);
+
+        self.files = mods::exec_dir(&l, SHAPES_DIR, |file, e| {
+            console.error(&file.to_string_lossy(), e)
+        });
+
+        let shapes: Option<Table> = l.globals().get(SHAPES).ok();
+        self.kinds = shapes
+            .into_iter()
+            .flat_map(|t| t.pairs::<String, Function>())
+            .filter_map(|x| x.ok())
+            .map(|(kind, _)| kind)
+            .collect();
+        self.kinds.sort();
+
+        map.set_building_gen(Some(Arc::new(LuaBuildingGen { l: Mutex::new(l) })));
+    }
+
+    /// Reloads the shapes if a script of `lua/buildings` or a module they require changed
+    pub fn reload_if_affected(
+        &mut self,
+        changed: &[PathBuf],
+        console: &mut LuaConsole,
+        map: &mut Map,
+    ) {
+        if !self.loaded {
+            return;
+        }
+        if mods::is_affected(changed, SHAPES_DIR, &self.files) {
+            self.reload(console, map);
+            console.info(SHAPES_DIR, "reloaded building shapes");
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+/// Older lines are dropped past that many
+const MAX_LINES: usize = 200;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConsoleLevel {
+    Info,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConsoleLine {
+    pub level: ConsoleLevel,
+    /// Script the message is about
+    pub source: String,
+    pub msg: String,
+}
+
+register_resource_noserialize!(LuaConsole);
+/// Messages from the scripts shown in game, mostly their errors
+#[derive(Default)]
+pub struct LuaConsole {
+    lines: VecDeque<ConsoleLine>,
+    /// Number of errors since the console was last cleared
+    pub errors: usize,
+}
+
+impl LuaConsole {
+    pub fn info(&mut self, source: &str, msg: impl Display) {
+        log::info!("{}: {}", source, msg);
+        self.push(ConsoleLevel::Info, source, msg);
+    }
+
+    pub fn error(&mut self, source: &str, msg: impl Display) {
+        log::error!("{}: {}", source, msg);
+        self.errors += 1;
+        self.push(ConsoleLevel::Error, source, msg);
+    }
+
+    fn push(&mut self, level: ConsoleLevel, source: &str, msg: impl Display) {
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(ConsoleLine {
+            level,
+            source: source.to_string(),
+            msg: msg.to_string(),
+        });
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &ConsoleLine> {
+        self.lines.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.errors = 0;
+    }
+}
@@ -0,0 +1,314 @@
+use super::console::LuaConsole;
+use super::{borrow, borrow_mut, lane_pattern, LuaIntersection, LuaRoad};
+use geom::vec2;
+use map_model::procgen::heightmap;
+use map_model::{Map, RoadSegmentKind};
+use mods::mlua::{Function, Lua, Table, UserData, UserDataMethods};
+use mods::{LuaApi, LuaFn, LuaVec2};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Every script in that directory can register generators
+const GENERATORS_DIR: &str = "lua/mapgen";
+
+/// Name of the global table mapping generator names to their function
+const GENERATORS: &str = "__map_generators";
+
+/// The map as seen by a generator, only valid during `MapGenerators::generate`
+struct LuaMapGen<'a> {
+    map: RefCell<&'a mut Map>,
+    rng: RefCell<StdRng>,
+    seed: u64,
+}
+
+pub(crate) const MAPGEN_API: LuaApi = LuaApi {
+    doc: "",
+    class: Some("MapGen"),
+    functions: &[
+        LuaFn {
+            name: "add_intersection",
+            doc: "",
+            params: &[("pos", "Vec2")],
+            ret: "Intersection",
+        },
+        LuaFn {
+            name: "connect",
+            doc: "",
+            params: &[
+                ("src", "Intersection"),
+                ("dst", "Intersection"),
+                ("pattern", "LanePattern|nil"),
+            ],
+            ret: "Road|nil",
+        },
+        LuaFn {
+            name: "split_road",
+            doc: "Splits the road in two at pos, returning the new intersection",
+            params: &[("road", "Road"), ("pos", "Vec2")],
+            ret: "Intersection|nil",
+        },
+        LuaFn {
+            name: "intersection_pos",
+            doc: "",
+            params: &[("i", "Intersection")],
+            ret: "Vec2|nil",
+        },
+        LuaFn {
+            name: "height",
+            doc: "Terrain height between 0 and 1",
+            params: &[("pos", "Vec2")],
+            ret: "number",
+        },
+        LuaFn {
+            name: "seed",
+            doc: "",
+            params: &[],
+            ret: "number",
+        },
+        LuaFn {
+            name: "random",
+            doc: "Seeded random number between 0 and 1",
+            params: &[],
+            ret: "number",
+        },
+        LuaFn {
+            name: "random_in",
+            doc: "",
+            params: &[("min", "number"), ("max", "number")],
+            ret: "number",
+        },
+        LuaFn {
+            name: "random_vec2",
+            doc: "Seeded random position in the rectangle between min and max",
+            params: &[("min", "Vec2"), ("max", "Vec2")],
+            ret: "Vec2",
+        },
+    ],
+};
+
+impl<'a> UserData for LuaMapGen<'a> {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(
+            MAPGEN_API.name("add_intersection"),
+            |_, sel, pos: LuaVec2| {
+                Ok(LuaIntersection(
+                    borrow_mut(&sel.map)?.add_intersection(pos.0),
+                ))
+            },
+        );
+
+        methods.add_method(
+            MAPGEN_API.name("connect"),
+            |_, sel, (src, dst, pattern): (LuaIntersection, LuaIntersection, Option<Table>)| {
+                let pattern = lane_pattern(pattern)?;
+                let mut map = borrow_mut(&sel.map)?;
+                if src.0 == dst.0
+                    || !map.intersections().contains_key(src.0)
+                    || !map.intersections().contains_key(dst.0)
+                {
+                    return Ok(None);
+                }
+                Ok(Some(LuaRoad(map.connect(
+                    src.0,
+                    dst.0,
+                    &pattern,
+                    RoadSegmentKind::Straight,
+                ))))
+            },
+        );
+
+        methods.add_method(
+            MAPGEN_API.name("split_road"),
+            |_, sel, (r, pos): (LuaRoad, LuaVec2)| {
+                let mut map = borrow_mut(&sel.map)?;
+                if !map.roads().contains_key(r.0) {
+                    return Ok(None);
+                }
+                Ok(Some(LuaIntersection(map.split_road(r.0, pos.0))))
+            },
+        );
+
+        methods.add_method(
+            MAPGEN_API.name("intersection_pos"),
+            |_, sel, i: LuaIntersection| {
+                Ok(borrow(&sel.map)?
+                    .intersections()
+                    .get(i.0)
+                    .map(|x| LuaVec2(x.pos)))
+            },
+        );
+
+        methods.add_method(MAPGEN_API.name("height"), |_, _, pos: LuaVec2| {
+            Ok(heightmap::height(pos.0))
+        });
+
+        methods.add_method(MAPGEN_API.name("seed"), |_, sel, ()| Ok(sel.seed));
+
+        methods.add_method(MAPGEN_API.name("random"), |_, sel, ()| {
+            Ok(borrow_mut(&sel.rng)?.gen::<f32>())
+        });
+
+        methods.add_method(
+            MAPGEN_API.name("random_in"),
+            |_, sel, (min, max): (f32, f32)| {
+                Ok(min + borrow_mut(&sel.rng)?.gen::<f32>() * (max - min))
+            },
+        );
+
+        methods.add_method(
+            MAPGEN_API.name("random_vec2"),
+            |_, sel, (min, max): (LuaVec2, LuaVec2)| {
+                let mut rng = borrow_mut(&sel.rng)?;
+                Ok(LuaVec2(
+                    min.0 + vec2(rng.gen(), rng.gen()) * (max.0 - min.0),
+                ))
+            },
+        );
+    }
+}
+
+/// Globals of the map generator scripts
+pub(crate) const GENERATORS_API: LuaApi = LuaApi {
+    doc: "Map generators, registered by the scripts of lua/mapgen",
+    class: None,
+    functions: &[LuaFn {
+        name: "register_generator",
+        doc: "Registers a generator shown in the Map window, f is called with a MapGen on a \
+              cleared map",
+        params: &[("name", "string"), ("f", "fun(gen: MapGen)")],
+        ret: "",
+    }],
+};
+
+fn register_generator(l: &Lua, (name, f): (String, Function)) -> mods::mlua::Result<()> {
+    let generators: Table = l.globals().get(GENERATORS)?;
+    generators.set(name, f)
+}
+
+register_resource_noserialize!(MapGenerators);
+/// Map generators registered by the scripts of `lua/mapgen` with `register_generator`
+#[derive(Default)]
+pub struct MapGenerators {
+    l: Option<Mutex<Lua>>,
+    names: Vec<String>,
+    files: Vec<PathBuf>,
+    /// Seed of the generators' random number generator
+    pub seed: i32,
+}
+
+impl MapGenerators {
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.l.is_some()
+    }
+
+    /// Loads every script of `lua/mapgen` in a fresh VM, reporting errors to the console.
+    /// A script that fails to load doesn't prevent the others from registering their generators.
+    pub fn reload(&mut self, console: &mut LuaConsole) {
+        let l = match mods::try_new() {
+            Ok(l) => l,
+            Err(e) => {
+                console.error(GENERATORS_DIR, e);
+                return;
+            }
+        };
+        if let Err(e) = l
+            .create_table()
+            .and_then(|t| l.globals().set(GENERATORS, t))
+        {
+            console.error(GENERATORS_DIR, e);
+            return;
+        }
+        mods::add_fn(
+            &l,
+            GENERATORS_API.name("register_generator"),
+            register_generator,
+        );
+
+        self.files = mods::exec_dir(&l, GENERATORS_DIR, |file, e| {
+            console.error(&file.to_string_lossy(), e)
+        });
+
+        let generators: Option<Table> = l.globals().get(GENERATORS).ok();
+        self.names = generators
+            .into_iter()
+            .flat_map(|t| t.pairs::<String, Function>())
+            .filter_map(|x| x.ok())
+            .map(|(name, _)| name)
+            .collect();
+        self.names.sort();
+        self.l = Some(Mutex::new(l));
+    }
+
+    /// Reloads the generators if a script of `lua/mapgen` or a module they require changed
+    pub fn reload_if_affected(&mut self, changed: &[PathBuf], console: &mut LuaConsole) {
+        if !self.is_loaded() {
+            return;
+        }
+        if mods::is_affected(changed, GENERATORS_DIR, &self.files) {
+            self.reload(console);
+            console.info(GENERATORS_DIR, "reloaded map generators");
+        }
+    }
+
+    /// Clears the map and runs the generator with that name on it
+    pub fn generate(&self, name: &str, map: &mut Map, console: &mut LuaConsole) {
+        let l = unwrap_or!(self.l.as_ref(), return).lock().unwrap();
+
+        let r = l.globals().get::<_, Table>(GENERATORS).and_then(|t| {
+            let f: Function = t.get(name)?;
+            map.clear();
+            l.scope(|scope| {
+                let gen = scope.create_nonstatic_userdata(LuaMapGen {
+                    map: RefCell::new(map),
+                    rng: RefCell::new(StdRng::seed_from_u64(self.seed as u64)),
+                    seed: self.seed as u64,
+                })?;
+                mods::try_call::<_, ()>(&l, &f, gen)
+            })
+        });
+
+        match r {
+            Ok(()) => console.info(name, format!("generated with seed {}", self.seed)),
+            Err(e) => console.error(name, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LuaMapGen, MapGenerators, GENERATORS_API, MAPGEN_API};
+    use crate::scenarios::console::LuaConsole;
+    use map_model::Map;
+    use mods::mlua::Value;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+
+    #[test]
+    fn documented_functions_exist() {
+        let mut generators = MapGenerators::default();
+        generators.reload(&mut LuaConsole::default());
+        let l = generators.l.as_ref().unwrap().lock().unwrap();
+        let none: Vec<&str> = vec![];
+        assert_eq!(GENERATORS_API.missing(&l, Value::Table(l.globals())), none);
+
+        let mut map = Map::empty();
+        l.scope(|scope| {
+            let gen = scope.create_nonstatic_userdata(LuaMapGen {
+                map: RefCell::new(&mut map),
+                rng: RefCell::new(StdRng::seed_from_u64(0)),
+                seed: 0,
+            })?;
+            assert_eq!(MAPGEN_API.missing(&l, Value::UserData(gen)), none);
+            Ok(())
+        })
+        .unwrap();
+    }
+}
@@ -1,29 +1,282 @@
-use crate::map_dynamic::Itinerary;
+use crate::economy::{CommodityKind, Market};
+use crate::map_dynamic::{BuildingInfos, Itinerary};
 use crate::rendering::immediate::ImmediateDraw;
+use crate::souls::goods_company::GOODS_BUILDINGS;
+use crate::souls::human::spawn_human;
+use crate::souls::spawn_company;
 use crate::vehicles::{make_vehicle_entity, Vehicle, VehicleKind, VehicleState};
-use crate::{Egregoria, ParCommandBuffer};
+use crate::{Egregoria, ParCommandBuffer, SoulID};
+use common::GameTime;
 use geom::Color;
 use geom::Transform;
 use geom::Vec2;
-use legion::{Entity, Resources};
-use mods::mlua::{Lua, ToLua, UserData, UserDataMethods, Value};
-use mods::LuaVec2;
+use legion::Entity;
+use map_model::{
+    BuildingID, IntersectionID, LanePattern, LanePatternBuilder, LotID, LotKind, Map, ProjectKind,
+    RoadID, RoadSegmentKind,
+};
+use mods::mlua::{Function, Lua, Table, ToLua, UserData, UserDataMethods, Value};
+use mods::{LuaApi, LuaFn, LuaVec2};
+use ordered_float::OrderedFloat;
+use std::cell::{Ref, RefCell, RefMut};
 
+pub mod building_shapes;
+pub mod console;
+pub mod map_generators;
 pub mod scenario_runner;
 
-struct LuaWorld {
-    w: *mut Egregoria,
+/// Name of the global table holding the functions registered with `on_tick`
+const TICK_CALLBACKS: &str = "__tick_callbacks";
+
+/// Name of the registry value holding the color used by `draw`
+const DRAW_COLOR: &str = "draw_color";
+
+/// Tables nested deeper than that are not carried over on reload, which also breaks cycles
+const MAX_TRANSFER_DEPTH: u32 = 32;
+
+/// The part of `lua/stdlib.lua` that isn't generated from a `LuaApi`
+const STDLIB_HEADER: &str = r#"---
+--- EmmyLua stubs of the functions exposed to the scripts, generated from their LuaApi
+--- Run the stdlib_is_generated test with UPDATE_STDLIB=1 to regenerate it
+---
+
+---@type World
+world = nil
+
+---@type Draw
+draw = nil
+
+---@class Entity
+---@class Color
+---@class Intersection
+---@class Road
+---@class Lot
+---@class Building
+
+---@class Time
+---@field timestamp number
+---@field delta number
+---@field seconds number
+---@field day number
+---@field hour number
+---@field second number
+
+---@class LanePattern
+---@field n_lanes number
+---@field sidewalks boolean
+---@field parking boolean
+---@field one_way boolean
+
+---@class ScenarioExpected
+---@field time number|nil maximum seconds until Success() returns true
+---@field gridlocks number|nil maximum number of gridlocks
+
+--- Optional table a scenario declares to configure how it is run
+---@class ScenarioConfig
+---@field timeout number|nil seconds after which the scenario fails, 300 by default
+---@field map string|nil "empty" | "testfield" | "paris", the current map is kept if nil
+---@field map_file string|nil path to a saved map, takes precedence over map
+---@field expected ScenarioExpected|nil
+
+--- Hooks a scenario can define, all optional:
+--- Init() after the map is loaded
+--- OnTick(dt) every tick
+--- Draw() every tick, to draw debug shapes
+--- Fail() every tick, the scenario fails when it returns true
+--- Success() every tick, the scenario succeeds when it returns true and the expectations are met
+--- Cleanup() when the scenario ends or is replaced
+--- Persist() when the script changes on disk, returns data given to Restore
+--- Restore(data) instead of Init() when the script is reloaded, only if both are defined
+"#;
+
+/// The apis documented in `lua/stdlib.lua`, in order
+const STDLIB_APIS: &[&LuaApi] = &[
+    &mods::STD_API,
+    &mods::VEC2_API,
+    &mods::POLYGON_API,
+    &EGREGORIA_API,
+    &DRAW_API,
+    &WORLD_API,
+    &map_generators::GENERATORS_API,
+    &map_generators::MAPGEN_API,
+    &building_shapes::SHAPES_API,
+    &crate::souls::desire::DESIRES_API,
+    &crate::souls::desire::SOUL_API,
+];
+
+/// EmmyLua stubs of every function exposed to the scripts, the content of `lua/stdlib.lua`
+pub fn lua_stubs() -> String {
+    mods::stubs(STDLIB_HEADER, STDLIB_APIS)
+}
+
+/// Borrows what a callback works on, failing the script instead of panicking when a callback
+/// called from another one tries to borrow it too
+fn borrow<T>(cell: &RefCell<T>) -> mods::mlua::Result<Ref<'_, T>> {
+    cell.try_borrow()
+        .map_err(|e| mods::mlua::Error::RuntimeError(format!("reentrant callback: {}", e)))
+}
+
+fn borrow_mut<T>(cell: &RefCell<T>) -> mods::mlua::Result<RefMut<'_, T>> {
+    cell.try_borrow_mut()
+        .map_err(|e| mods::mlua::Error::RuntimeError(format!("reentrant callback: {}", e)))
 }
 
-unsafe impl Send for LuaWorld {}
+/// The world as seen by scripts, only valid during a call made through `with_world`
+struct LuaWorld<'a, 'g> {
+    w: &'a RefCell<&'g mut Egregoria>,
+}
+
+pub const WORLD_API: LuaApi = LuaApi {
+    doc: "",
+    class: Some("World"),
+    functions: &[
+        LuaFn {
+            name: "add_car",
+            doc: "",
+            params: &[("pos", "Vec2"), ("dir", "Vec2"), ("objective", "Vec2")],
+            ret: "Entity",
+        },
+        LuaFn {
+            name: "pos",
+            doc: "",
+            params: &[("e", "Entity")],
+            ret: "Vec2|nil",
+        },
+        LuaFn {
+            name: "remove",
+            doc: "",
+            params: &[("e", "Entity")],
+            ret: "",
+        },
+        LuaFn {
+            name: "time",
+            doc: "",
+            params: &[],
+            ret: "Time",
+        },
+        LuaFn {
+            name: "capital",
+            doc: "Capital of the soul in the given commodity, e.g \"Bread\"",
+            params: &[("soul", "Entity"), ("commodity", "string")],
+            ret: "number",
+        },
+        LuaFn {
+            name: "intersections",
+            doc: "",
+            params: &[],
+            ret: "Intersection[]",
+        },
+        LuaFn {
+            name: "intersection_pos",
+            doc: "",
+            params: &[("i", "Intersection")],
+            ret: "Vec2|nil",
+        },
+        LuaFn {
+            name: "nearest_intersection",
+            doc: "",
+            params: &[("pos", "Vec2")],
+            ret: "Intersection|nil",
+        },
+        LuaFn {
+            name: "roads",
+            doc: "",
+            params: &[],
+            ret: "Road[]",
+        },
+        LuaFn {
+            name: "road_ends",
+            doc: "",
+            params: &[("road", "Road")],
+            ret: "Intersection, Intersection",
+        },
+        LuaFn {
+            name: "lots_around",
+            doc: "",
+            params: &[("pos", "Vec2"), ("radius", "number")],
+            ret: "Lot[]",
+        },
+        LuaFn {
+            name: "buildings_around",
+            doc: "",
+            params: &[("pos", "Vec2"), ("radius", "number")],
+            ret: "Building[]",
+        },
+        LuaFn {
+            name: "building_kind",
+            doc: "",
+            params: &[("b", "Building")],
+            ret: "string|nil",
+        },
+        LuaFn {
+            name: "add_intersection",
+            doc: "",
+            params: &[("pos", "Vec2")],
+            ret: "Intersection",
+        },
+        LuaFn {
+            name: "connect",
+            doc: "Returns nil if the intersections are the same or do not exist",
+            params: &[
+                ("src", "Intersection"),
+                ("dst", "Intersection"),
+                ("pattern", "LanePattern|nil"),
+            ],
+            ret: "Road|nil",
+        },
+        LuaFn {
+            name: "remove_road",
+            doc: "",
+            params: &[("road", "Road")],
+            ret: "boolean",
+        },
+        LuaFn {
+            name: "remove_intersection",
+            doc: "",
+            params: &[("i", "Intersection")],
+            ret: "",
+        },
+        LuaFn {
+            name: "set_lot_kind",
+            doc: "",
+            params: &[
+                ("lot", "Lot"),
+                (
+                    "kind",
+                    "string \"unassigned\" | \"residential\" | \"commercial\"",
+                ),
+            ],
+            ret: "",
+        },
+        LuaFn {
+            name: "build_houses",
+            doc: "Builds houses on the residential lots",
+            params: &[],
+            ret: "Building[]",
+        },
+        LuaFn {
+            name: "spawn_human",
+            doc: "",
+            params: &[("house", "Building")],
+            ret: "Entity|nil",
+        },
+        LuaFn {
+            name: "spawn_company",
+            doc: "Spawns the company matching the kind of the building",
+            params: &[("building", "Building")],
+            ret: "Entity|nil",
+        },
+    ],
+};
 
-impl UserData for LuaWorld {
+impl<'a, 'g> UserData for LuaWorld<'a, 'g> {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method(
-            "add_car",
-            |_: &Lua, sel: &Self, (pos, dir, objective): (LuaVec2, LuaVec2, LuaVec2)| unsafe {
+            WORLD_API.name("add_car"),
+            |_: &Lua, sel: &Self, (pos, dir, objective): (LuaVec2, LuaVec2, LuaVec2)| {
+                let mut goria = borrow_mut(sel.w)?;
                 let e = make_vehicle_entity(
-                    &mut (*sel.w),
+                    &mut goria,
                     Transform::new_cos_sin(pos.0, dir.0.try_normalize().unwrap_or(Vec2::UNIT_X)),
                     Vehicle {
                         ang_velocity: 0.0,
@@ -39,40 +292,302 @@ impl UserData for LuaWorld {
             },
         );
 
-        methods.add_method("pos", |l: &Lua, sel: &Self, e: LuaEntity| unsafe {
-            Ok(match (*sel.w).comp::<Transform>(e.0) {
-                Some(t) => LuaVec2(t.position()).to_lua(l).unwrap(),
-                None => Value::Nil,
-            })
+        methods.add_method(
+            WORLD_API.name("pos"),
+            |l: &Lua, sel: &Self, e: LuaEntity| {
+                Ok(match borrow(sel.w)?.comp::<Transform>(e.0) {
+                    Some(t) => LuaVec2(t.position()).to_lua(l).unwrap(),
+                    None => Value::Nil,
+                })
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("remove"),
+            |_: &Lua, sel: &Self, e: LuaEntity| {
+                borrow(sel.w)?.write::<ParCommandBuffer>().kill(e.0);
+                Ok(())
+            },
+        );
+
+        methods.add_method(WORLD_API.name("time"), |l: &Lua, sel: &Self, ()| {
+            let time = *borrow(sel.w)?.read::<GameTime>();
+            let t = l.create_table()?;
+            t.set("timestamp", time.timestamp)?;
+            t.set("delta", time.delta)?;
+            t.set("seconds", time.seconds)?;
+            t.set("day", time.daytime.day)?;
+            t.set("hour", time.daytime.hour)?;
+            t.set("second", time.daytime.second)?;
+            Ok(t)
         });
 
-        methods.add_method("remove", |_: &Lua, sel: &Self, e: LuaEntity| unsafe {
-            (*sel.w).write::<ParCommandBuffer>().kill(e.0);
-            Ok(())
+        methods.add_method(
+            WORLD_API.name("capital"),
+            |_: &Lua, sel: &Self, (soul, commodity): (LuaEntity, String)| {
+                let kind = CommodityKind::from_name(&commodity).ok_or_else(|| {
+                    mods::mlua::Error::RuntimeError(format!("unknown commodity {}", commodity))
+                })?;
+                Ok(borrow(sel.w)?
+                    .read::<Market>()
+                    .capital(SoulID(soul.0), kind))
+            },
+        );
+
+        // Map queries
+
+        methods.add_method(
+            WORLD_API.name("intersections"),
+            |_: &Lua, sel: &Self, ()| {
+                let goria = borrow(sel.w)?;
+                let map = goria.read::<Map>();
+                Ok(map
+                    .intersections()
+                    .keys()
+                    .map(LuaIntersection)
+                    .collect::<Vec<_>>())
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("intersection_pos"),
+            |_: &Lua, sel: &Self, i: LuaIntersection| {
+                let goria = borrow(sel.w)?;
+                let map = goria.read::<Map>();
+                Ok(map.intersections().get(i.0).map(|x| LuaVec2(x.pos)))
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("nearest_intersection"),
+            |_: &Lua, sel: &Self, pos: LuaVec2| {
+                let goria = borrow(sel.w)?;
+                let map = goria.read::<Map>();
+                Ok(map
+                    .intersections()
+                    .values()
+                    .min_by_key(|x| OrderedFloat(x.pos.distance2(pos.0)))
+                    .map(|x| LuaIntersection(x.id)))
+            },
+        );
+
+        methods.add_method(WORLD_API.name("roads"), |_: &Lua, sel: &Self, ()| {
+            let goria = borrow(sel.w)?;
+            let map = goria.read::<Map>();
+            Ok(map.roads().keys().map(LuaRoad).collect::<Vec<_>>())
         });
+
+        methods.add_method(
+            WORLD_API.name("road_ends"),
+            |_: &Lua, sel: &Self, r: LuaRoad| {
+                let goria = borrow(sel.w)?;
+                let map = goria.read::<Map>();
+                let ends = map.roads().get(r.0).map(|r| (r.src, r.dst));
+                Ok((
+                    ends.map(|(src, _)| LuaIntersection(src)),
+                    ends.map(|(_, dst)| LuaIntersection(dst)),
+                ))
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("lots_around"),
+            |_: &Lua, sel: &Self, (pos, radius): (LuaVec2, f32)| {
+                let goria = borrow(sel.w)?;
+                let map = goria.read::<Map>();
+                Ok(map
+                    .spatial_map()
+                    .query_around(pos.0, radius)
+                    .filter_map(|x| match x {
+                        ProjectKind::Lot(id) => Some(LuaLot(id)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>())
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("buildings_around"),
+            |_: &Lua, sel: &Self, (pos, radius): (LuaVec2, f32)| {
+                let goria = borrow(sel.w)?;
+                let map = goria.read::<Map>();
+                Ok(map
+                    .spatial_map()
+                    .query_around(pos.0, radius)
+                    .filter_map(|x| match x {
+                        ProjectKind::Building(id) => Some(LuaBuilding(id)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>())
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("building_kind"),
+            |_: &Lua, sel: &Self, b: LuaBuilding| {
+                let goria = borrow(sel.w)?;
+                let map = goria.read::<Map>();
+                Ok(map.buildings().get(b.0).map(|b| format!("{:?}", b.kind)))
+            },
+        );
+
+        // Map edition
+
+        methods.add_method(
+            WORLD_API.name("add_intersection"),
+            |_: &Lua, sel: &Self, pos: LuaVec2| {
+                Ok(LuaIntersection(
+                    borrow(sel.w)?.write::<Map>().add_intersection(pos.0),
+                ))
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("connect"),
+            |_: &Lua,
+             sel: &Self,
+             (src, dst, pattern): (LuaIntersection, LuaIntersection, Option<Table>)| {
+                let pattern = lane_pattern(pattern)?;
+                let goria = borrow(sel.w)?;
+
+                let mut map = goria.write::<Map>();
+                if src.0 == dst.0
+                    || !map.intersections().contains_key(src.0)
+                    || !map.intersections().contains_key(dst.0)
+                {
+                    return Ok(None);
+                }
+                Ok(Some(LuaRoad(map.connect(
+                    src.0,
+                    dst.0,
+                    &pattern,
+                    RoadSegmentKind::Straight,
+                ))))
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("remove_road"),
+            |_: &Lua, sel: &Self, r: LuaRoad| {
+                Ok(borrow(sel.w)?.write::<Map>().remove_road(r.0).is_some())
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("remove_intersection"),
+            |_: &Lua, sel: &Self, i: LuaIntersection| {
+                let goria = borrow(sel.w)?;
+                let mut map = goria.write::<Map>();
+                if map.intersections().contains_key(i.0) {
+                    map.remove_intersection(i.0);
+                }
+                Ok(())
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("set_lot_kind"),
+            |_: &Lua, sel: &Self, (lot, kind): (LuaLot, String)| {
+                let kind = match kind.as_str() {
+                    "unassigned" => LotKind::Unassigned,
+                    "residential" => LotKind::Residential,
+                    "commercial" => LotKind::Commercial,
+                    _ => {
+                        return Err(mods::mlua::Error::RuntimeError(format!(
+                            "unknown lot kind {}",
+                            kind
+                        )))
+                    }
+                };
+                borrow(sel.w)?.write::<Map>().set_lot_kind(lot.0, kind);
+                Ok(())
+            },
+        );
+
+        methods.add_method(WORLD_API.name("build_houses"), |_: &Lua, sel: &Self, ()| {
+            let goria = borrow(sel.w)?;
+            let mut infos = goria.write::<BuildingInfos>();
+            let mut built = vec![];
+            for build in goria.write::<Map>().build_buildings() {
+                infos.insert(build);
+                built.push(LuaBuilding(build));
+            }
+            Ok(built)
+        });
+
+        // Souls
+
+        methods.add_method(
+            WORLD_API.name("spawn_human"),
+            |_: &Lua, sel: &Self, house: LuaBuilding| {
+                let mut goria = borrow_mut(sel.w)?;
+                if goria.read::<Map>().buildings().get(house.0).is_none() {
+                    return Ok(None);
+                }
+                Ok(Some(LuaEntity(spawn_human(&mut goria, house.0).0)))
+            },
+        );
+
+        methods.add_method(
+            WORLD_API.name("spawn_company"),
+            |_: &Lua, sel: &Self, building: LuaBuilding| {
+                let mut goria = borrow_mut(sel.w)?;
+                let kind = unwrap_or!(
+                    goria
+                        .read::<Map>()
+                        .buildings()
+                        .get(building.0)
+                        .map(|b| b.kind),
+                    return Ok(None)
+                );
+                let des = unwrap_or!(
+                    GOODS_BUILDINGS.iter().find(|d| d.bkind == kind),
+                    return Ok(None)
+                );
+                Ok(spawn_company(&mut goria, building.0, des).map(|soul| LuaEntity(soul.0)))
+            },
+        );
     }
 }
 
-struct LuaDraw {
-    w: *mut Resources,
-    col: Color,
+struct LuaDraw<'a, 'g> {
+    w: &'a RefCell<&'g mut Egregoria>,
 }
 
-unsafe impl Send for LuaDraw {}
+pub const DRAW_API: LuaApi = LuaApi {
+    doc: "",
+    class: Some("Draw"),
+    functions: &[
+        LuaFn {
+            name: "circle",
+            doc: "",
+            params: &[("pos", "Vec2"), ("radius", "number")],
+            ret: "",
+        },
+        LuaFn {
+            name: "color",
+            doc: "Color of the shapes drawn afterwards, white by default",
+            params: &[("col", "Color")],
+            ret: "",
+        },
+    ],
+};
 
-impl UserData for LuaDraw {
+impl<'a, 'g> UserData for LuaDraw<'a, 'g> {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_method_mut("circle", |_, sel, (pos, size): (LuaVec2, f32)| unsafe {
-            (*sel.w)
-                .get_mut::<ImmediateDraw>()
-                .unwrap()
-                .circle(pos.0, size)
-                .color(sel.col);
-            Ok(())
-        });
-        methods.add_method_mut("color", |_, sel, col: LuaColor| {
-            sel.col = col.0;
-            Ok(())
+        methods.add_method(
+            DRAW_API.name("circle"),
+            |l, sel, (pos, size): (LuaVec2, f32)| {
+                let col: Option<LuaColor> = l.named_registry_value(DRAW_COLOR).ok();
+                borrow(sel.w)?
+                    .write::<ImmediateDraw>()
+                    .circle(pos.0, size)
+                    .color(col.map_or(Color::WHITE, |c| c.0));
+                Ok(())
+            },
+        );
+        methods.add_method(DRAW_API.name("color"), |l, _, col: LuaColor| {
+            l.set_named_registry_value(DRAW_COLOR, col)
         });
     }
 }
@@ -83,30 +598,200 @@ impl UserData for LuaColor {}
 
 #[derive(Copy, Clone)]
 struct LuaEntity(Entity);
-
 impl UserData for LuaEntity {}
 
+#[derive(Copy, Clone)]
+struct LuaIntersection(IntersectionID);
+impl UserData for LuaIntersection {}
+
+#[derive(Copy, Clone)]
+struct LuaRoad(RoadID);
+impl UserData for LuaRoad {}
+
+#[derive(Copy, Clone)]
+struct LuaLot(LotID);
+impl UserData for LuaLot {}
+
+#[derive(Copy, Clone)]
+pub(crate) struct LuaBuilding(pub(crate) BuildingID);
+impl UserData for LuaBuilding {}
+
+/// Reads the optional pattern table taken by `connect`, missing fields keep their default
+fn lane_pattern(t: Option<Table>) -> mods::mlua::Result<LanePattern> {
+    let mut builder = LanePatternBuilder::new();
+    if let Some(t) = t {
+        let n_lanes: Option<u32> = t.get("n_lanes")?;
+        builder.n_lanes = n_lanes.unwrap_or(builder.n_lanes).max(1);
+        builder.sidewalks = t
+            .get::<_, Option<bool>>("sidewalks")?
+            .unwrap_or(builder.sidewalks);
+        builder.parking = t
+            .get::<_, Option<bool>>("parking")?
+            .unwrap_or(builder.parking);
+        builder.one_way = t
+            .get::<_, Option<bool>>("one_way")?
+            .unwrap_or(builder.one_way);
+    }
+    Ok(builder.build())
+}
+
 fn color(_: &Lua, (r, g, b, a): (f32, f32, f32, f32)) -> mods::mlua::Result<LuaColor> {
     Ok(LuaColor(Color { r, g, b, a }))
 }
 
-pub fn add_egregoria_lua_stdlib(lua: &Lua, w: &mut Egregoria) {
-    lua.globals()
-        .set(
+fn on_tick(l: &Lua, f: Function) -> mods::mlua::Result<()> {
+    let callbacks: Table = l.globals().get(TICK_CALLBACKS)?;
+    callbacks.set(callbacks.raw_len() + 1, f)
+}
+
+/// Calls every function registered with `on_tick`, passing them the time elapsed since last tick
+pub(crate) fn call_tick_callbacks(l: &Lua, dt: f32) -> mods::mlua::Result<()> {
+    let callbacks: Table = l.globals().get(TICK_CALLBACKS)?;
+    for f in callbacks.sequence_values::<Function>() {
+        mods::try_call::<_, ()>(l, &f?, dt)?;
+    }
+    Ok(())
+}
+
+/// Copies a value from one VM to another, so a reloaded script can pick up the old one's state.
+/// Functions and unknown userdata cannot be copied and become nil.
+pub(crate) fn transfer<'b>(v: Value, to: &'b Lua, depth: u32) -> mods::mlua::Result<Value<'b>> {
+    Ok(match v {
+        Value::Nil => Value::Nil,
+        Value::Boolean(x) => Value::Boolean(x),
+        Value::Integer(x) => Value::Integer(x),
+        Value::Number(x) => Value::Number(x),
+        Value::String(x) => Value::String(to.create_string(x.as_bytes())?),
+        Value::Table(t) => {
+            if depth >= MAX_TRANSFER_DEPTH {
+                return Ok(Value::Nil);
+            }
+            let copy = to.create_table()?;
+            for kv in t.pairs::<Value, Value>() {
+                let (k, v) = kv?;
+                let k = transfer(k, to, depth + 1)?;
+                if !matches!(k, Value::Nil) {
+                    copy.raw_set(k, transfer(v, to, depth + 1)?)?;
+                }
+            }
+            Value::Table(copy)
+        }
+        Value::UserData(ud) => {
+            macro_rules! copy_ud {
+                ($($t: ty),*) => {
+                    $(
+                        if let Ok(x) = ud.borrow::<$t>() {
+                            return (*x).to_lua(to);
+                        }
+                    )*
+                };
+            }
+            copy_ud!(
+                LuaVec2,
+                LuaColor,
+                LuaEntity,
+                LuaIntersection,
+                LuaRoad,
+                LuaLot,
+                LuaBuilding
+            );
+            Value::Nil
+        }
+        _ => Value::Nil,
+    })
+}
+
+/// Exposes the world to the scripts as the `world` and `draw` globals for the duration of `f`.
+/// Accesses are borrow checked at runtime, and the globals are invalidated once `f` returns.
+pub fn with_world<R>(lua: &Lua, goria: &mut Egregoria, f: impl FnOnce(&Lua) -> R) -> Option<R> {
+    let w = RefCell::new(goria);
+    let r = lua.scope(|scope| {
+        lua.globals().set(
             "world",
-            LuaWorld {
-                w: w as *mut Egregoria,
-            },
-        )
-        .unwrap();
+            scope.create_nonstatic_userdata(LuaWorld { w: &w })?,
+        )?;
+        lua.globals()
+            .set("draw", scope.create_nonstatic_userdata(LuaDraw { w: &w })?)?;
+        Ok(f(lua))
+    });
+    match r {
+        Ok(x) => Some(x),
+        Err(e) => {
+            log::error!("could not expose the world to lua: {}", e);
+            None
+        }
+    }
+}
+
+/// Globals added by `add_egregoria_lua_stdlib`
+pub const EGREGORIA_API: LuaApi = LuaApi {
+    doc: "",
+    class: None,
+    functions: &[
+        LuaFn {
+            name: "color",
+            doc: "",
+            params: &[
+                ("r", "number"),
+                ("g", "number"),
+                ("b", "number"),
+                ("a", "number"),
+            ],
+            ret: "Color",
+        },
+        LuaFn {
+            name: "on_tick",
+            doc: "Registers a function to be called every tick with the time elapsed since the \
+                  last one",
+            params: &[("f", "fun(dt: number)")],
+            ret: "",
+        },
+    ],
+};
+
+pub fn add_egregoria_lua_stdlib(lua: &Lua) {
     lua.globals()
-        .set(
-            "draw",
-            LuaDraw {
-                w: &mut w.resources as *mut Resources,
-                col: Color::WHITE,
-            },
-        )
+        .set(TICK_CALLBACKS, lua.create_table().unwrap())
+        .unwrap();
+    mods::add_fn(lua, EGREGORIA_API.name("color"), color);
+    mods::add_fn(lua, EGREGORIA_API.name("on_tick"), on_tick);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scenario_runner::headless_egregoria;
+    use super::{add_egregoria_lua_stdlib, lua_stubs, with_world};
+    use super::{DRAW_API, EGREGORIA_API, WORLD_API};
+    use mods::mlua::Value;
+    use std::path::Path;
+
+    #[test]
+    fn documented_functions_exist() {
+        let l = mods::try_new().unwrap();
+        add_egregoria_lua_stdlib(&l);
+        let none: Vec<&str> = vec![];
+        assert_eq!(EGREGORIA_API.missing(&l, Value::Table(l.globals())), none);
+
+        let mut goria = headless_egregoria();
+        with_world(&l, &mut goria, |l| {
+            let world: Value = l.globals().get("world").unwrap();
+            let draw: Value = l.globals().get("draw").unwrap();
+            assert_eq!(WORLD_API.missing(l, world), none);
+            assert_eq!(DRAW_API.missing(l, draw), none);
+        })
         .unwrap();
-    mods::add_fn(lua, "color", color)
+    }
+
+    #[test]
+    fn stdlib_is_generated() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../lua/stdlib.lua");
+        let stubs = lua_stubs();
+        if std::env::var_os("UPDATE_STDLIB").is_some() {
+            std::fs::write(&path, &stubs).unwrap();
+        }
+        assert!(
+            std::fs::read_to_string(&path).unwrap() == stubs,
+            "lua/stdlib.lua is outdated, run this test with UPDATE_STDLIB=1 to regenerate it"
+        );
+    }
 }
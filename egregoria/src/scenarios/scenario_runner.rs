@@ -1,49 +1,482 @@
+use super::console::LuaConsole;
+use crate::physics::Kinematics;
+use crate::vehicles::Vehicle;
 use crate::Egregoria;
-use legion::system;
-use mods::mlua::Lua;
+use common::GameTime;
+use geom::{vec3, Camera};
+use legion::IntoQuery;
+use map_model::{Map, SerializedMap};
+use mods::mlua::{FromLuaMulti, Lua, Table, ToLuaMulti, Value};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
+/// Time step used when running scenarios without a window, in seconds
+pub const HEADLESS_TIMESTEP: f64 = 1.0 / 30.0;
+
+/// The vehicles are considered gridlocked when none of them moved for that long, in seconds
+const GRIDLOCK_SECONDS: f64 = 10.0;
+
+/// Game seconds a scenario is given when it doesn't declare a timeout
+const DEFAULT_TIMEOUT: f64 = 300.0;
+
+/// Map the scenario starts from, declared as `Scenario.map` or `Scenario.map_file`
+#[derive(Clone, Debug)]
+pub enum ScenarioMap {
+    /// Keep whatever map the world already has
+    Current,
+    Empty,
+    Testfield,
+    Paris,
+    /// A map saved by the game, e.g. `world/map.bc`
+    File(String),
+}
+
+/// What a scenario declares in its global `Scenario` table
+#[derive(Clone, Debug)]
+pub struct ScenarioConfig {
+    /// Game seconds after which the scenario fails
+    pub timeout: f64,
+    pub map: ScenarioMap,
+    /// Maximum game seconds `Success()` may take to return true
+    pub max_time: Option<f64>,
+    /// Maximum number of gridlocks allowed
+    pub max_gridlocks: Option<u32>,
+}
+
+impl Default for ScenarioConfig {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            map: ScenarioMap::Current,
+            max_time: None,
+            max_gridlocks: None,
+        }
+    }
+}
+
+impl ScenarioConfig {
+    fn from_lua(l: &Lua) -> mods::mlua::Result<Self> {
+        let mut config = Self::default();
+        let t: Table = match l.globals().get::<_, Option<Table>>("Scenario")? {
+            Some(t) => t,
+            None => return Ok(config),
+        };
+
+        if let Some(timeout) = t.get::<_, Option<f64>>("timeout")? {
+            config.timeout = timeout;
+        }
+
+        let preset: Option<String> = t.get("map")?;
+        config.map = match (preset.as_deref(), t.get::<_, Option<String>>("map_file")?) {
+            (_, Some(file)) => ScenarioMap::File(file),
+            (None, None) => ScenarioMap::Current,
+            (Some("empty"), _) => ScenarioMap::Empty,
+            (Some("testfield"), _) => ScenarioMap::Testfield,
+            (Some("paris"), _) => ScenarioMap::Paris,
+            (Some(x), _) => {
+                return Err(mods::mlua::Error::RuntimeError(format!(
+                    "unknown map preset {}",
+                    x
+                )))
+            }
+        };
+
+        if let Some(expected) = t.get::<_, Option<Table>>("expected")? {
+            config.max_time = expected.get("time")?;
+            config.max_gridlocks = expected.get("gridlocks")?;
+        }
+
+        Ok(config)
+    }
+
+    fn load_map(&self, goria: &mut Egregoria) -> Option<()> {
+        let mut map = goria.write::<Map>();
+        match self.map {
+            ScenarioMap::Current => {}
+            ScenarioMap::Empty => map.clear(),
+            ScenarioMap::Testfield => {
+                map.clear();
+                map_model::procgen::load_testfield(&mut map);
+            }
+            ScenarioMap::Paris => {
+                map.clear();
+                map_model::procgen::load_parismap(&mut map);
+            }
+            ScenarioMap::File(ref path) => {
+                let gen = map.building_gen();
+                *map = Map::from(common::saveload::load_path::<SerializedMap>(path)?);
+                map.set_building_gen(gen);
+            }
+        }
+        Some(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum ScenarioStatus {
+    NotStarted,
+    Running,
+    Success,
+    /// Why the scenario failed: an error, a timeout, `Fail()` or an unmet expectation
+    Failure(String),
+}
+
+register_resource_noserialize!(ScenarioResult);
+/// State of the current or last scenario, written to `world/scenario_result.json` when it ends
+#[derive(Clone, Debug, Serialize)]
+pub struct ScenarioResult {
+    pub name: String,
+    pub status: ScenarioStatus,
+    /// Game seconds since the scenario started
+    pub elapsed: f64,
+    /// Number of times every vehicle stood still for `GRIDLOCK_SECONDS`
+    pub gridlocks: u32,
+}
+
+impl Default for ScenarioResult {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            status: ScenarioStatus::NotStarted,
+            elapsed: 0.0,
+            gridlocks: 0,
+        }
+    }
+}
+
 register_resource_noserialize!(RunningScenario);
 #[derive(Default)]
 pub struct RunningScenario {
     pub l: Option<Mutex<Lua>>,
+    pub config: ScenarioConfig,
+    /// Script the scenario was loaded from
+    path: PathBuf,
+    /// The script and the modules it requires, the scenario is reloaded when one of them changes
+    files: Vec<PathBuf>,
+    /// Game seconds every vehicle has been standing still
+    still_for: f64,
+    /// Headless runs keep the result in memory instead of writing it to disk
+    headless: bool,
+}
+
+fn track_gridlocks(goria: &mut Egregoria, dt: f64) {
+    let still = {
+        let mut query = <(&Vehicle, &Kinematics)>::query();
+        let mut vehicles = query.iter(&goria.world).peekable();
+        // no vehicles at all is not a gridlock
+        vehicles.peek().is_some() && vehicles.all(|(_, kin)| kin.velocity.magnitude2() <= 0.01)
+    };
+
+    let mut scenario = goria.write::<RunningScenario>();
+    if !still {
+        scenario.still_for = 0.0;
+        return;
+    }
+    let before = scenario.still_for;
+    scenario.still_for += dt;
+    if before < GRIDLOCK_SECONDS && scenario.still_for >= GRIDLOCK_SECONDS {
+        goria.write::<ScenarioResult>().gridlocks += 1;
+    }
 }
 
-register_system!(run_scenario);
-#[system]
-pub fn run_scenario(#[resource] scenario: &mut RunningScenario) {
-    if let Some(l) = &scenario.l {
-        let l = l.lock().unwrap();
-        mods::eval_f(&l, "Draw");
+/// Calls the global function with that name if the script defines it
+fn call_hook<'a, A: ToLuaMulti<'a>, R: FromLuaMulti<'a>>(
+    l: &'a Lua,
+    name: &str,
+    args: A,
+) -> mods::mlua::Result<Option<R>> {
+    match mods::get_f(l, name) {
+        Some(f) => mods::try_call(l, &f, args).map(Some),
+        None => Ok(None),
+    }
+}
 
-        let r: Option<bool> = mods::call_f(&l, "Success");
-        let is_success = match r {
-            Some(x) => x,
-            None => {
-                drop(l);
+/// Calls the hooks of one tick, returning the final status if the scenario ended
+fn tick_hooks(lua: &Lua, dt: f32) -> mods::mlua::Result<Option<ScenarioStatus>> {
+    super::call_tick_callbacks(lua, dt)?;
+    call_hook::<_, ()>(lua, "OnTick", dt)?;
+    call_hook::<_, ()>(lua, "Draw", ())?;
+    if call_hook::<_, Option<bool>>(lua, "Fail", ())?.flatten() == Some(true) {
+        return Ok(Some(ScenarioStatus::Failure(
+            "Fail() returned true".to_string(),
+        )));
+    }
+    if call_hook::<_, Option<bool>>(lua, "Success", ())?.flatten() == Some(true) {
+        return Ok(Some(ScenarioStatus::Success));
+    }
+    Ok(None)
+}
 
-                scenario.l.take();
-                return;
-            }
-        };
-        if is_success {
-            info!("scenario success");
-            mods::eval_f(&l, "Cleanup");
+/// Calls a hook with the world exposed, reporting errors to the console
+fn world_hook<'a, A: ToLuaMulti<'a>>(l: &'a Lua, goria: &mut Egregoria, name: &str, args: A) {
+    let r = super::with_world(l, goria, |_| call_hook::<_, ()>(l, name, args));
+    if let Some(Err(e)) = r {
+        let source = scenario_name(&goria.read::<RunningScenario>().path);
+        goria.write::<LuaConsole>().error(&source, e);
+    }
+}
+
+fn scenario_name(path: &Path) -> String {
+    path.file_stem()
+        .map_or_else(String::new, |x| x.to_string_lossy().into_owned())
+}
+
+/// Turns a success into a failure if the run didn't meet the declared expectations
+fn check_expected(config: &ScenarioConfig, result: &ScenarioResult) -> ScenarioStatus {
+    if let Some(max) = config.max_time {
+        if result.elapsed > max {
+            return ScenarioStatus::Failure(format!(
+                "took {:.1}s, expected at most {:.1}s",
+                result.elapsed, max
+            ));
+        }
+    }
+    if let Some(max) = config.max_gridlocks {
+        if result.gridlocks > max {
+            return ScenarioStatus::Failure(format!(
+                "{} gridlocks, expected at most {}",
+                result.gridlocks, max
+            ));
+        }
+    }
+    ScenarioStatus::Success
+}
+
+/// Runs the scenario outside of the schedule, as lua callbacks are free to access the whole world
+pub fn run_scenario(goria: &mut Egregoria) {
+    let l = unwrap_or!(goria.write::<RunningScenario>().l.take(), return);
+    let lua = l.lock().unwrap();
+
+    let dt = goria.read::<GameTime>().delta;
+    track_gridlocks(goria, dt as f64);
+    goria.write::<ScenarioResult>().elapsed += dt as f64;
+
+    let r = super::with_world(&lua, goria, |lua| tick_hooks(lua, dt));
+
+    let scenario = goria.read::<RunningScenario>();
+    let config = scenario.config.clone();
+    let headless = scenario.headless;
+    drop(scenario);
+    let mut result = goria.write::<ScenarioResult>();
+    let status = match r {
+        Some(Ok(Some(ScenarioStatus::Success))) => check_expected(&config, &result),
+        Some(Ok(Some(status))) => status,
+        Some(Ok(None)) if result.elapsed > config.timeout => {
+            ScenarioStatus::Failure(format!("timed out after {:.0}s", config.timeout))
+        }
+        Some(Ok(None)) => {
+            drop(result);
+            drop(lua);
+            goria.write::<RunningScenario>().l.get_or_insert(l);
+            return;
+        }
+        Some(Err(e)) => {
+            goria.write::<LuaConsole>().error(&result.name, &e);
+            ScenarioStatus::Failure(e.to_string())
+        }
+        None => ScenarioStatus::Failure("could not access the world".to_string()),
+    };
+
+    goria
+        .write::<LuaConsole>()
+        .info(&result.name, format!("ended: {:?}", status));
+    result.status = status;
+    if !headless {
+        common::saveload::save_json(&*result, "scenario_result");
+    }
+    drop(result);
 
-            drop(l);
-            scenario.l.take();
+    world_hook(&lua, goria, "Cleanup", ());
+}
+
+/// Loads a scenario script and its config, reporting errors to the console
+fn load_scenario(goria: &mut Egregoria, path: &Path) -> Option<(Lua, ScenarioConfig)> {
+    let r = mods::try_load(path).and_then(|l| {
+        super::add_egregoria_lua_stdlib(&l);
+        let config = ScenarioConfig::from_lua(&l)?;
+        Ok((l, config))
+    });
+    match r {
+        Ok(x) => Some(x),
+        Err(e) => {
+            goria.write::<LuaConsole>().error(&scenario_name(path), e);
+            None
         }
     }
 }
 
+fn scenario_files(l: &Lua, path: &Path) -> Vec<PathBuf> {
+    let mut files = mods::required_files(l);
+    files.push(path.to_path_buf());
+    files
+        .into_iter()
+        .map(|x| x.canonicalize().unwrap_or(x))
+        .collect()
+}
+
 pub fn set_scenario(goria: &mut Egregoria, name: &str) {
-    if let Some(l) = mods::load(name) {
-        super::add_egregoria_lua_stdlib(&l, goria);
-        mods::eval_f(&l, "Init");
-        goria
-            .write::<RunningScenario>()
-            .l
-            .replace(Mutex::new(l))
-            .map(|old| mods::eval_f(&old.lock().unwrap(), "Cleanup"));
+    let path = PathBuf::from(name);
+    let (l, config) = unwrap_or!(load_scenario(goria, &path), return);
+
+    let old = goria.write::<RunningScenario>().l.take();
+    if let Some(old) = old {
+        world_hook(&old.into_inner().unwrap(), goria, "Cleanup", ());
+    }
+
+    config.load_map(goria);
+    let files = scenario_files(&l, &path);
+    let headless = goria.read::<RunningScenario>().headless;
+    *goria.write::<RunningScenario>() = RunningScenario {
+        l: None,
+        config,
+        path,
+        files,
+        still_for: 0.0,
+        headless,
+    };
+    world_hook(&l, goria, "Init", ());
+
+    let mut scenario = goria.write::<RunningScenario>();
+    scenario.l = Some(Mutex::new(l));
+    *goria.write::<ScenarioResult>() = ScenarioResult {
+        name: scenario_name(&scenario.path),
+        status: ScenarioStatus::Running,
+        elapsed: 0.0,
+        gridlocks: 0,
+    };
+}
+
+/// Reloads the running scenario in place if one of its files changed.
+/// If the old script defines `Persist()` and the new one `Restore(data)`, the table returned by
+/// the former is handed to the latter so the entities it created are kept.
+/// Otherwise the old script is cleaned up and the new one initialized.
+pub fn reload_scenario(goria: &mut Egregoria, changed: &[PathBuf]) {
+    let scenario = goria.read::<RunningScenario>();
+    if scenario.l.is_none() {
+        return;
+    }
+    let affected = changed
+        .iter()
+        .filter_map(|x| x.canonicalize().ok())
+        .any(|x| scenario.files.contains(&x));
+    if !affected {
+        return;
+    }
+    let path = scenario.path.clone();
+    drop(scenario);
+
+    // keep the old scenario running if the new one doesn't even load
+    let (l, config) = unwrap_or!(load_scenario(goria, &path), return);
+    let old = unwrap_or!(goria.write::<RunningScenario>().l.take(), return);
+    let old = old.into_inner().unwrap();
+
+    let persist = mods::get_f(&old, "Persist");
+    let restore = mods::get_f(&l, "Restore");
+    match (persist, restore) {
+        (Some(persist), Some(restore)) => {
+            let data = super::with_world(&old, goria, |_| {
+                let data: Value = mods::try_call(&old, &persist, ())?;
+                super::transfer(data, &l, 0)
+            });
+            match data {
+                Some(Ok(data)) => {
+                    let r = super::with_world(&l, goria, |_| {
+                        mods::try_call::<_, ()>(&l, &restore, data)
+                    });
+                    if let Some(Err(e)) = r {
+                        goria.write::<LuaConsole>().error(&scenario_name(&path), e);
+                    }
+                }
+                Some(Err(e)) => goria.write::<LuaConsole>().error(&scenario_name(&path), e),
+                None => {}
+            }
+        }
+        _ => {
+            world_hook(&old, goria, "Cleanup", ());
+            world_hook(&l, goria, "Init", ());
+        }
+    }
+
+    let files = scenario_files(&l, &path);
+    let mut scenario = goria.write::<RunningScenario>();
+    scenario.l = Some(Mutex::new(l));
+    scenario.config = config;
+    scenario.files = files;
+    drop(scenario);
+
+    goria
+        .write::<LuaConsole>()
+        .info(&scenario_name(&path), "reloaded");
+}
+
+/// A world with an empty map and the resources usually provided by the window
+pub fn headless_egregoria() -> Egregoria {
+    let mut goria = Egregoria::init();
+    goria.insert(Map::empty());
+    goria.insert(Camera::new(1.0, 1.0, vec3(0.0, 0.0, 1000.0)));
+    goria
+}
+
+/// Loads the scenario in a new headless world and ticks it until it ends
+pub fn run_headless(path: impl AsRef<Path>) -> ScenarioResult {
+    let path = path.as_ref();
+    let mut goria = headless_egregoria();
+    goria.write::<RunningScenario>().headless = true;
+    set_scenario(&mut goria, &path.to_string_lossy());
+
+    loop {
+        let result = goria.read::<ScenarioResult>();
+        match result.status {
+            ScenarioStatus::Running => {}
+            ScenarioStatus::NotStarted => {
+                return ScenarioResult {
+                    name: path.to_string_lossy().into_owned(),
+                    status: ScenarioStatus::Failure("could not load the scenario".to_string()),
+                    ..ScenarioResult::default()
+                }
+            }
+            _ => return result.clone(),
+        }
+        drop(result);
+
+        let t = goria.read::<GameTime>().timestamp;
+        *goria.write::<GameTime>() = GameTime::new(HEADLESS_TIMESTEP as f32, t + HEADLESS_TIMESTEP);
+        goria.run();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_headless, ScenarioStatus};
+    use std::path::Path;
+
+    #[test]
+    fn scenarios_succeed() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../lua/scenarios");
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .collect();
+        paths.sort();
+
+        let mut report = String::new();
+        let mut failed = 0;
+        for path in paths {
+            let result = run_headless(&path);
+            let line = format!(
+                "{:<20} {:?} after {:.1}s, {} gridlocks",
+                result.name, result.status, result.elapsed, result.gridlocks
+            );
+            println!("{}", line);
+            report += &line;
+            report.push('\n');
+            if result.status != ScenarioStatus::Success {
+                failed += 1;
+            }
+        }
+
+        assert_eq!(failed, 0, "{} scenarios failed:\n{}", failed, report);
     }
 }
@@ -0,0 +1,466 @@
+use crate::economy::{Bought, CommodityKind, Market};
+use crate::map_dynamic::{BuildingInfos, Destination, Router};
+use crate::scenarios::console::LuaConsole;
+use crate::scenarios::LuaBuilding;
+use crate::souls::desire::{Desire, Home};
+use crate::{ParCommandBuffer, SoulID};
+use common::GameTime;
+use geom::{Transform, Vec2};
+use legion::{system, Entity};
+use map_model::{BuildingID, Map};
+use mods::mlua::{Function, Lua, Table, ToLua, UserData, UserDataMethods, Value};
+use mods::{LuaApi, LuaFn, LuaVec2};
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Every script in that directory can register desires
+const DESIRES_DIR: &str = "lua/desires";
+
+/// Name of the global table mapping desire names to their score and apply functions
+const DESIRES: &str = "__desires";
+
+/// Values a lua desire can remember between ticks, kept with the soul when saving
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum LuaMemValue {
+    Number(f64),
+    Bool(bool),
+    Str(String),
+    Building(BuildingID),
+}
+
+/// State of the desires defined in lua for one soul
+#[derive(Default, Serialize, Deserialize)]
+pub struct LuaDesire {
+    /// Desire with the highest score at the last evaluation
+    current: Option<String>,
+    memory: HashMap<String, LuaMemValue>,
+}
+
+/// A soul as seen by the score and apply functions of a lua desire
+struct LuaSoul<'a> {
+    soul: SoulID,
+    pos: Vec2,
+    time: &'a GameTime,
+    map: &'a Map,
+    binfos: &'a BuildingInfos,
+    cbuf: &'a ParCommandBuffer,
+    router: RefCell<&'a mut Router>,
+    bought: RefCell<&'a mut Bought>,
+    memory: RefCell<&'a mut HashMap<String, LuaMemValue>>,
+}
+
+fn commodity(name: &str) -> mods::mlua::Result<CommodityKind> {
+    CommodityKind::from_name(name)
+        .ok_or_else(|| mods::mlua::Error::RuntimeError(format!("unknown commodity {}", name)))
+}
+
+pub(crate) const SOUL_API: LuaApi = LuaApi {
+    doc: "",
+    class: Some("Soul"),
+    functions: &[
+        LuaFn {
+            name: "pos",
+            doc: "",
+            params: &[],
+            ret: "Vec2",
+        },
+        LuaFn {
+            name: "hour",
+            doc: "",
+            params: &[],
+            ret: "number",
+        },
+        LuaFn {
+            name: "seconds",
+            doc: "",
+            params: &[],
+            ret: "number",
+        },
+        LuaFn {
+            name: "get",
+            doc: "Remembered values are kept across ticks and saves",
+            params: &[("key", "string")],
+            ret: "number|boolean|string|Building|nil",
+        },
+        LuaFn {
+            name: "set",
+            doc: "",
+            params: &[
+                ("key", "string"),
+                ("value", "number|boolean|string|Building|nil"),
+            ],
+            ret: "",
+        },
+        LuaFn {
+            name: "go_to",
+            doc: "Returns true once the soul arrived",
+            params: &[("building", "Building")],
+            ret: "boolean",
+        },
+        LuaFn {
+            name: "use_car",
+            doc: "",
+            params: &[("use_car", "boolean")],
+            ret: "",
+        },
+        LuaFn {
+            name: "buy",
+            doc: "Puts a buy order on the market, see Soul:bought for the result",
+            params: &[("commodity", "string"), ("quantity", "number")],
+            ret: "",
+        },
+        LuaFn {
+            name: "bought",
+            doc: "Buildings of the sellers of the trades made since last call",
+            params: &[("commodity", "string")],
+            ret: "Building[]",
+        },
+        LuaFn {
+            name: "nearest_building",
+            doc: "",
+            params: &[("kind", "string")],
+            ret: "Building|nil",
+        },
+    ],
+};
+
+impl<'a> UserData for LuaSoul<'a> {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(SOUL_API.name("pos"), |_, sel, ()| Ok(LuaVec2(sel.pos)));
+
+        methods.add_method(SOUL_API.name("hour"), |_, sel, ()| {
+            Ok(sel.time.daytime.hour)
+        });
+
+        methods.add_method(SOUL_API.name("seconds"), |_, sel, ()| {
+            Ok(sel.time.timestamp)
+        });
+
+        methods.add_method(SOUL_API.name("get"), |l, sel, key: String| {
+            Ok(match sel.memory.borrow().get(&key) {
+                Some(LuaMemValue::Number(x)) => Value::Number(*x),
+                Some(LuaMemValue::Bool(x)) => Value::Boolean(*x),
+                Some(LuaMemValue::Str(x)) => x.as_str().to_lua(l)?,
+                Some(LuaMemValue::Building(x)) => LuaBuilding(*x).to_lua(l)?,
+                None => Value::Nil,
+            })
+        });
+
+        methods.add_method(SOUL_API.name("set"), |_, sel, (key, v): (String, Value)| {
+            let v = match v {
+                Value::Nil => {
+                    sel.memory.borrow_mut().remove(&key);
+                    return Ok(());
+                }
+                Value::Integer(x) => LuaMemValue::Number(x as f64),
+                Value::Number(x) => LuaMemValue::Number(x),
+                Value::Boolean(x) => LuaMemValue::Bool(x),
+                Value::String(x) => LuaMemValue::Str(x.to_str()?.to_string()),
+                Value::UserData(ud) => LuaMemValue::Building(ud.borrow::<LuaBuilding>()?.0),
+                _ => {
+                    return Err(mods::mlua::Error::RuntimeError(format!(
+                        "cannot remember {} as it's not a number, boolean, string or building",
+                        key
+                    )))
+                }
+            };
+            sel.memory.borrow_mut().insert(key, v);
+            Ok(())
+        });
+
+        methods.add_method(SOUL_API.name("go_to"), |_, sel, b: LuaBuilding| {
+            Ok(sel.router.borrow_mut().go_to(Destination::Building(b.0)))
+        });
+
+        methods.add_method(SOUL_API.name("use_car"), |_, sel, use_car: bool| {
+            let mut router = sel.router.borrow_mut();
+            let v = if use_car { router.personal_car } else { None };
+            router.use_vehicle(v);
+            Ok(())
+        });
+
+        methods.add_method(
+            SOUL_API.name("buy"),
+            |_, sel, (name, qty): (String, i32)| {
+                let kind = commodity(&name)?;
+                let (soul, pos) = (sel.soul, sel.pos);
+                sel.cbuf
+                    .exec_on(move |market: &mut Market| market.buy(soul, pos, kind, qty));
+                Ok(())
+            },
+        );
+
+        methods.add_method(SOUL_API.name("bought"), |_, sel, name: String| {
+            let kind = commodity(&name)?;
+            let mut bought = sel.bought.borrow_mut();
+            Ok(bought
+                .0
+                .entry(kind)
+                .or_default()
+                .drain(..)
+                .filter_map(|trade| sel.binfos.building_owned_by(trade.seller))
+                .map(LuaBuilding)
+                .collect::<Vec<_>>())
+        });
+
+        methods.add_method(SOUL_API.name("nearest_building"), |_, sel, kind: String| {
+            Ok(sel
+                .map
+                .buildings()
+                .values()
+                .filter(|b| format!("{:?}", b.kind) == kind)
+                .min_by_key(|b| OrderedFloat(b.door_pos.distance2(sel.pos)))
+                .map(|b| LuaBuilding(b.id)))
+        });
+    }
+}
+
+/// Globals of the desire scripts
+pub(crate) const DESIRES_API: LuaApi = LuaApi {
+    doc: "Desires, registered by the scripts of lua/desires",
+    class: None,
+    functions: &[LuaFn {
+        name: "register_desire",
+        doc: "Adds a desire to every human. Each tick the desire with the highest score is \
+              applied.\nThe builtin ones score 0.2 for going home, 0.5 for working hours, \
+              and grow with hunger.",
+        params: &[
+            ("name", "string"),
+            ("score", "fun(soul: Soul): number"),
+            ("apply", "fun(soul: Soul)"),
+        ],
+        ret: "",
+    }],
+};
+
+fn register_desire(
+    l: &Lua,
+    (name, score, apply): (String, Function, Function),
+) -> mods::mlua::Result<()> {
+    let desires: Table = l.globals().get(DESIRES)?;
+    let d = l.create_table()?;
+    d.set("score", score)?;
+    d.set("apply", apply)?;
+    desires.set(name, d)
+}
+
+register_resource_noserialize!(LuaDesires);
+/// Desires registered by the scripts of `lua/desires` with `register_desire`
+#[derive(Default)]
+pub struct LuaDesires {
+    l: Option<Mutex<Lua>>,
+    names: Vec<String>,
+    files: Vec<PathBuf>,
+}
+
+impl LuaDesires {
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Loads every script of `lua/desires` in a fresh VM, reporting errors to the console
+    pub fn reload(&mut self, console: &mut LuaConsole) {
+        let l = match mods::try_new() {
+            Ok(l) => l,
+            Err(e) => {
+                console.error(DESIRES_DIR, e);
+                return;
+            }
+        };
+        if let Err(e) = l.create_table().and_then(|t| l.globals().set(DESIRES, t)) {
+            console.error(DESIRES_DIR, e);
+            return;
+        }
+        mods::add_fn(&l, DESIRES_API.name("register_desire"), register_desire);
+
+        self.files = mods::exec_dir(&l, DESIRES_DIR, |file, e| {
+            console.error(&file.to_string_lossy(), e)
+        });
+
+        let desires: Option<Table> = l.globals().get(DESIRES).ok();
+        self.names = desires
+            .into_iter()
+            .flat_map(|t| t.pairs::<String, Table>())
+            .filter_map(|x| x.ok())
+            .map(|(name, _)| name)
+            .collect();
+        self.names.sort();
+        self.l = Some(Mutex::new(l));
+    }
+
+    /// Reloads the desires if a script of `lua/desires` or a module they require changed
+    pub fn reload_if_affected(&mut self, changed: &[PathBuf], console: &mut LuaConsole) {
+        if self.l.is_none() {
+            return;
+        }
+        if mods::is_affected(changed, DESIRES_DIR, &self.files) {
+            self.reload(console);
+            console.info(DESIRES_DIR, "reloaded desires");
+        }
+    }
+
+    /// Applies the desire that won last tick if it is still the soul's priority,
+    /// then scores them all.
+    /// A desire that errors is unregistered so the console isn't flooded by every soul.
+    fn update(&self, soul: LuaSoul, d: &mut Desire<LuaDesire>, console: &mut LuaConsole) {
+        let l = unwrap_or!(self.l.as_ref(), return).lock().unwrap();
+        let desires: Table = unwrap_or!(l.globals().get(DESIRES).ok(), return);
+        let current = d.v.current.clone();
+        let was_max = d.was_max;
+
+        let mut failed = None;
+        let r = l.scope(|scope| {
+            let ud = scope.create_nonstatic_userdata(soul)?;
+
+            if let (true, Some(name)) = (was_max, current) {
+                if let Ok(desire) = desires.get::<_, Table>(name.as_str()) {
+                    let apply: Function = desire.get("apply")?;
+                    if let Err(e) = mods::try_call::<_, ()>(&l, &apply, ud.clone()) {
+                        failed = Some((name, e));
+                        return Ok(None);
+                    }
+                }
+            }
+
+            let mut best: Option<(String, f32)> = None;
+            for kv in desires.clone().pairs::<String, Table>() {
+                let (name, desire) = kv?;
+                let score: Function = desire.get("score")?;
+                let score = match mods::try_call::<_, f32>(&l, &score, ud.clone()) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        failed = Some((name, e));
+                        return Ok(None);
+                    }
+                };
+                if best.as_ref().map_or(true, |(_, s)| score > *s) {
+                    best = Some((name, score));
+                }
+            }
+            Ok(best)
+        });
+
+        if let Some((name, e)) = failed {
+            console.error(&name, format!("{}, the desire is disabled", e));
+            let _ = desires.set(name.as_str(), Value::Nil);
+        }
+
+        match r {
+            Ok(Some((name, score))) => {
+                d.v.current = Some(name);
+                d.score = score;
+            }
+            Ok(None) => {
+                d.v.current = None;
+                d.score = f32::NEG_INFINITY;
+            }
+            Err(e) => console.error(DESIRES_DIR, e),
+        }
+    }
+}
+
+register_system!(attach_lua_desire);
+/// Lua desires are opt-in: humans only get them once a script registered one, which also covers
+/// the humans of saves made without them
+#[system(for_each)]
+pub fn attach_lua_desire(
+    #[resource] desires: &LuaDesires,
+    #[resource] cbuf: &ParCommandBuffer,
+    me: &Entity,
+    _: &Desire<Home>,
+    d: Option<&Desire<LuaDesire>>,
+) {
+    if d.is_none() && !desires.names.is_empty() {
+        cbuf.add_component(*me, Desire::new(LuaDesire::default()));
+    }
+}
+
+register_system!(desire_lua);
+#[system(for_each)]
+#[allow(clippy::too_many_arguments)]
+pub fn desire_lua(
+    #[resource] desires: &LuaDesires,
+    #[resource] console: &mut LuaConsole,
+    #[resource] cbuf: &ParCommandBuffer,
+    #[resource] binfos: &BuildingInfos,
+    #[resource] time: &GameTime,
+    #[resource] map: &Map,
+    me: &Entity,
+    trans: &Transform,
+    router: &mut Router,
+    d: &mut Desire<LuaDesire>,
+    bought: &mut Bought,
+) {
+    if desires.names.is_empty() {
+        d.score = f32::NEG_INFINITY;
+        return;
+    }
+
+    let mut memory = std::mem::take(&mut d.v.memory);
+    let soul = LuaSoul {
+        soul: SoulID(*me),
+        pos: trans.position(),
+        time,
+        map,
+        binfos,
+        cbuf,
+        router: RefCell::new(router),
+        bought: RefCell::new(bought),
+        memory: RefCell::new(&mut memory),
+    };
+    desires.update(soul, d, console);
+    d.v.memory = memory;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LuaDesires, LuaSoul, DESIRES_API, SOUL_API};
+    use crate::economy::Bought;
+    use crate::map_dynamic::{BuildingInfos, Router};
+    use crate::scenarios::console::LuaConsole;
+    use crate::{ParCommandBuffer, SoulID};
+    use common::GameTime;
+    use geom::Vec2;
+    use legion::World;
+    use map_model::Map;
+    use mods::mlua::Value;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    #[test]
+    fn documented_functions_exist() {
+        let mut desires = LuaDesires::default();
+        desires.reload(&mut LuaConsole::default());
+        let l = desires.l.as_ref().unwrap().lock().unwrap();
+        let none: Vec<&str> = vec![];
+        assert_eq!(DESIRES_API.missing(&l, Value::Table(l.globals())), none);
+
+        let time = GameTime::new(0.0, 0.0);
+        let map = Map::empty();
+        let binfos = BuildingInfos::default();
+        let cbuf = ParCommandBuffer::default();
+        let mut router = Router::new(None);
+        let mut bought = Bought::default();
+        let mut memory = HashMap::new();
+        l.scope(|scope| {
+            let soul = scope.create_nonstatic_userdata(LuaSoul {
+                soul: SoulID(World::default().push(())),
+                pos: Vec2::ZERO,
+                time: &time,
+                map: &map,
+                binfos: &binfos,
+                cbuf: &cbuf,
+                router: RefCell::new(&mut router),
+                bought: RefCell::new(&mut bought),
+                memory: RefCell::new(&mut memory),
+            })?;
+            assert_eq!(SOUL_API.missing(&l, Value::UserData(soul)), none);
+            Ok(())
+        })
+        .unwrap();
+    }
+}
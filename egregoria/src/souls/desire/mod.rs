@@ -2,10 +2,12 @@ use serde::{Deserialize, Serialize};
 
 mod buyfood;
 mod home;
+mod lua;
 mod work;
 
 pub use buyfood::*;
 pub use home::*;
+pub use lua::*;
 pub use work::*;
 
 #[derive(Serialize, Deserialize)]
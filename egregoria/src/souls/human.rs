@@ -2,13 +2,16 @@ use crate::economy::CommodityKind::JobOpening;
 use crate::economy::{Bought, Market};
 use crate::map_dynamic::{BuildingInfos, Router};
 use crate::pedestrians::{spawn_pedestrian, Pedestrian};
-use crate::souls::desire::{BuyFood, Home, Work};
+use crate::souls::desire::{BuyFood, Home, LuaDesire, Work};
 use crate::vehicles::{spawn_parked_vehicle, VehicleKind};
 use crate::{Egregoria, SoulID};
 use common::GameTime;
 use map_model::{BuildingID, Map};
 
-pub fn spawn_human(goria: &mut Egregoria, house: BuildingID) {
+/// Money a human starts with, spent on paid parking
+const STARTING_MONEY: u32 = 100;
+
+pub fn spawn_human(goria: &mut Egregoria, house: BuildingID) -> SoulID {
     let map = goria.read::<Map>();
     let housepos = map.buildings()[house].door_pos;
     drop(map);
@@ -18,6 +21,7 @@ pub fn spawn_human(goria: &mut Egregoria, house: BuildingID) {
 
     let mut m = goria.write::<Market>();
     m.buy(human, housepos, JobOpening, 1);
+    m.give_money(human, STARTING_MONEY);
     drop(m);
 
     goria.write::<BuildingInfos>().set_owner(house, human);
@@ -30,6 +34,8 @@ pub fn spawn_human(goria: &mut Egregoria, house: BuildingID) {
     e.add_component(Desire::new(BuyFood::new(time)));
     e.add_component(Bought::default());
     e.add_component(Router::new(car));
+
+    human
 }
 
-desires_system!(human_desires, Pedestrian, Home;0 Work;1 BuyFood;2);
+desires_system!(human_desires, Pedestrian, Home;0 Work;1 BuyFood;2 LuaDesire;3);
@@ -1,8 +1,10 @@
 use crate::map_dynamic::BuildingInfos;
-use crate::souls::goods_company::{company_soul, CompanyKind, GoodsCompany, GOODS_BUILDINGS};
+use crate::souls::goods_company::{
+    company_soul, CompanyKind, GoodsCompany, GoodsCompanyDescription, GOODS_BUILDINGS,
+};
 use crate::souls::human::spawn_human;
 use crate::vehicles::{spawn_parked_vehicle, VehicleKind};
-use crate::Egregoria;
+use crate::{Egregoria, SoulID};
 use geom::Vec2;
 use map_model::{BuildingID, BuildingKind, Map};
 use rand::seq::SliceRandom;
@@ -44,31 +46,10 @@ pub fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
     }
 
     for des in GOODS_BUILDINGS {
-        for &(build_id, pos) in empty_buildings.get(&des.bkind).unwrap_or(&vec![]) {
-            let mut trucks = vec![];
-
-            if let CompanyKind::Factory { n_trucks } = des.kind {
-                for _ in 0..n_trucks {
-                    trucks.extend(spawn_parked_vehicle(goria, VehicleKind::Truck, pos))
-                }
-                if trucks.is_empty() {
-                    continue;
-                }
+        for &(build_id, _) in empty_buildings.get(&des.bkind).unwrap_or(&vec![]) {
+            if spawn_company(goria, build_id, des).is_some() {
+                n_souls_added += 1;
             }
-
-            let comp = GoodsCompany {
-                kind: des.kind,
-                building: build_id,
-                recipe: des.recipe,
-                workers: des.n_workers,
-                work_seconds: 0.0,
-                driver: None,
-                trucks,
-            };
-
-            company_soul(goria, comp);
-
-            n_souls_added += 1;
         }
     }
 
@@ -76,3 +57,35 @@ pub fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
         log::info!("{} souls added", n_souls_added);
     }
 }
+
+/// Spawns the company described by `des` in the building, returns None if it needs trucks but
+/// none could be parked.
+pub fn spawn_company(
+    goria: &mut Egregoria,
+    build_id: BuildingID,
+    des: &GoodsCompanyDescription,
+) -> Option<SoulID> {
+    let pos = goria.read::<Map>().buildings().get(build_id)?.door_pos;
+    let mut trucks = vec![];
+
+    if let CompanyKind::Factory { n_trucks } = des.kind {
+        for _ in 0..n_trucks {
+            trucks.extend(spawn_parked_vehicle(goria, VehicleKind::Truck, pos))
+        }
+        if trucks.is_empty() {
+            return None;
+        }
+    }
+
+    let comp = GoodsCompany {
+        kind: des.kind,
+        building: build_id,
+        recipe: des.recipe,
+        workers: des.n_workers,
+        work_seconds: 0.0,
+        driver: None,
+        trucks,
+    };
+
+    Some(company_soul(goria, comp))
+}
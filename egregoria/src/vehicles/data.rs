@@ -16,6 +16,9 @@ use serde::{Deserialize, Serialize};
 /// The duration for the parking animation.
 pub const TIME_TO_PARK: f32 = 4.0;
 
+/// In m/s², slows down vehicles going uphill.
+const GRAVITY: f32 = 9.81;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct VehicleID(pub Entity);
 
@@ -83,6 +86,13 @@ impl VehicleKind {
         }
     }
 
+    /// Acceleration when going up a slope of that grade, gravity taking part of the engine
+    /// power. Going down doesn't help, the driver brakes instead.
+    pub fn acceleration_on(self, grade: f32) -> f32 {
+        let acc = self.acceleration();
+        (acc - GRAVITY * grade.max(0.0)).max(acc * 0.25)
+    }
+
     pub fn deceleration(self) -> f32 {
         match self {
             VehicleKind::Car => 9.0,
@@ -1,4 +1,6 @@
-use crate::map_dynamic::{Itinerary, ParkingManagement, OBJECTIVE_OK_DIST};
+use crate::map_dynamic::{
+    Itinerary, ParkingManagement, SignalActuation, TurnReservations, OBJECTIVE_OK_DIST,
+};
 use crate::physics::Kinematics;
 use crate::physics::{Collider, CollisionWorld, PhysicsGroup, PhysicsObject};
 use crate::utils::Restrict;
@@ -29,6 +31,8 @@ pub fn vehicle_decision(
     #[resource] map: &Map,
     #[resource] time: &GameTime,
     #[resource] cow: &CollisionWorld,
+    #[resource] reservations: &TurnReservations,
+    #[resource] signals: &SignalActuation,
     me: &Entity,
     it: &mut Itinerary,
     trans: &mut Transform,
@@ -47,7 +51,26 @@ pub fn vehicle_decision(
         let objs =
             neighbors.map(|(id, pos)| (pos, cow.get(id).expect("Handle not in collision world").1));
 
-        let (s, d) = calc_decision(*me, vehicle, &map, &time, trans, self_obj, it, objs);
+        if let Some(Traversable {
+            kind: TraverseKind::Turn(t_id),
+            ..
+        }) = it.get_travers()
+        {
+            reservations.occupy(*t_id, *me, time.timestamp);
+        }
+
+        let (s, d) = calc_decision(
+            *me,
+            vehicle,
+            &map,
+            signals,
+            &time,
+            reservations,
+            trans,
+            self_obj,
+            it,
+            objs,
+        );
         desired_speed = s;
         desired_dir = d;
     }
@@ -59,6 +82,7 @@ pub fn vehicle_decision(
         &time,
         self_obj,
         &map,
+        it,
         desired_speed,
         desired_dir,
     );
@@ -94,6 +118,7 @@ fn physics(
     time: &GameTime,
     obj: &PhysicsObject,
     map: &Map,
+    it: &Itinerary,
     desired_speed: f32,
     desired_dir: Vec2,
 ) {
@@ -114,11 +139,14 @@ fn physics(
     let speed = obj.speed;
     let kind = vehicle.kind;
     let direction = trans.direction();
+    let grade = it
+        .get_travers()
+        .map_or(0.0, |t| t.grade_at(map, trans.position()));
 
     let speed = speed
         + (desired_speed - speed).restrict(
             -time.delta * kind.deceleration(),
-            time.delta * kind.acceleration(),
+            time.delta * kind.acceleration_on(grade),
         );
 
     let max_ang_vel = (speed.abs() / kind.min_turning_radius()).restrict(0.0, 2.0);
@@ -145,7 +173,9 @@ pub fn calc_decision<'a>(
     me: Entity,
     vehicle: &mut Vehicle,
     map: &Map,
+    signals: &SignalActuation,
     time: &GameTime,
+    reservations: &TurnReservations,
     trans: &Transform,
     self_obj: &PhysicsObject,
     it: &Itinerary,
@@ -211,24 +241,42 @@ pub fn calc_decision<'a>(
     {
         if let Some(l) = map.lanes().get(*l_id) {
             let light = l.control_point();
-            match l.control.get_behavior(time.seconds) {
+            let stop_line_dist = OBJECTIVE_OK_DIST * 1.05
+                + 2.0
+                + stop_dist
+                + (vehicle.kind.width() * 0.5 - OBJECTIVE_OK_DIST).max(0.0);
+
+            let next_turn = match it.peek_travers().map(|t| t.kind) {
+                Some(TraverseKind::Turn(t_id)) => Some(t_id),
+                _ => None,
+            };
+
+            match map.lane_behavior(l, next_turn, signals.seconds(l.dst, time.seconds)) {
                 TrafficBehavior::RED | TrafficBehavior::ORANGE => {
-                    if light.is_close(
-                        position,
-                        OBJECTIVE_OK_DIST * 1.05
-                            + 2.0
-                            + stop_dist
-                            + (vehicle.kind.width() * 0.5 - OBJECTIVE_OK_DIST).max(0.0),
-                    ) {
+                    if light.is_close(position, stop_line_dist) {
                         return (0.0, dir_to_pos);
                     }
                 }
-                TrafficBehavior::STOP => {
-                    if light.is_close(position, OBJECTIVE_OK_DIST * 0.95 + stop_dist) {
+                behavior => {
+                    if let Some(t_id) = next_turn {
+                        // Announce ourselves a bit before the stop line so that others
+                        // know we're coming, then only cross if we have right of way
+                        if light.is_close(position, stop_line_dist + 3.0) {
+                            reservations.wait(t_id, me, time.timestamp);
+                            if light.is_close(position, stop_line_dist)
+                                && !reservations.may_enter(t_id, me, map)
+                            {
+                                return (0.0, dir_to_pos);
+                            }
+                        }
+                    }
+
+                    if matches!(behavior, TrafficBehavior::STOP)
+                        && light.is_close(position, OBJECTIVE_OK_DIST * 0.95 + stop_dist)
+                    {
                         return (0.0, dir_to_pos);
                     }
                 }
-                _ => {}
             }
         }
     }
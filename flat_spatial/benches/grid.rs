@@ -0,0 +1,134 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use flat_spatial::grid::GridHandle;
+use flat_spatial::{DenseGrid, SparseGrid};
+use geom::{Ray, Vec2};
+
+/// Objects spread over a 4km wide square with 100m cells, about what the collision world
+/// looks like with a lot of cars on a big map.
+const SIZE: f32 = 4000.0;
+const CELL_SIZE: i32 = 100;
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+fn random_points(n: usize) -> Vec<Vec2> {
+    let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+    let mut rand = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % 1_000_000) as f32 / 1_000_000.0
+    };
+    (0..n)
+        .map(|_| Vec2::new(rand() - 0.5, rand() - 0.5) * SIZE)
+        .collect()
+}
+
+fn setup(n: usize) -> (DenseGrid<()>, Vec<(GridHandle, Vec2)>) {
+    let mut g = DenseGrid::new(CELL_SIZE);
+    let objs = random_points(n)
+        .into_iter()
+        .map(|p| (g.insert(p, ()), p))
+        .collect();
+    (g, objs)
+}
+
+/// Moves every object by about a frame's worth of driving, going back and forth
+fn step(objs: &mut [(GridHandle, Vec2)], forward: &mut bool) {
+    *forward = !*forward;
+    let delta = if *forward { 0.8 } else { -0.8 };
+    for (_, pos) in objs {
+        pos.x += delta;
+        pos.y += delta * 0.5;
+    }
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("grid insert");
+    for &n in &SIZES {
+        let points = random_points(n);
+        group.bench_with_input(BenchmarkId::new("dense", n), &points, |b, points| {
+            b.iter(|| {
+                let mut g = DenseGrid::new(CELL_SIZE);
+                for &p in points {
+                    g.insert(p, ());
+                }
+                g
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("sparse", n), &points, |b, points| {
+            b.iter(|| {
+                let mut g = SparseGrid::new(CELL_SIZE);
+                for &p in points {
+                    g.insert(p, ());
+                }
+                g
+            })
+        });
+    }
+    group.finish();
+}
+
+fn query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("grid query");
+    for &n in &SIZES {
+        let (g, objs) = setup(n);
+        let centers: Vec<Vec2> = random_points(100);
+
+        // Vehicles look around them at about that distance
+        group.bench_with_input(BenchmarkId::new("query_around", n), &g, |b, g| {
+            b.iter(|| {
+                centers
+                    .iter()
+                    .map(|&p| g.query_around(p, 30.0).count())
+                    .sum::<usize>()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("knn 10", n), &g, |b, g| {
+            b.iter(|| {
+                for &p in &centers {
+                    black_box(g.knn(p, 10));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("raycast", n), &g, |b, g| {
+            b.iter(|| {
+                centers
+                    .iter()
+                    .zip(&objs)
+                    .map(|(&p, &(_, dir))| g.raycast(Ray::new(p, dir), 200.0).count())
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn update_maintain(c: &mut Criterion) {
+    let mut group = c.benchmark_group("grid update+maintain");
+    for &n in &SIZES {
+        group.bench_with_input(BenchmarkId::new("sequential", n), &n, |b, &n| {
+            let (mut g, mut objs) = setup(n);
+            let mut forward = false;
+            b.iter(|| {
+                step(&mut objs, &mut forward);
+                for &(h, pos) in &objs {
+                    g.set_position(h, pos);
+                }
+                g.maintain();
+            })
+        });
+
+        #[cfg(feature = "rayon")]
+        group.bench_with_input(BenchmarkId::new("parallel", n), &n, |b, &n| {
+            let (mut g, mut objs) = setup(n);
+            let mut forward = false;
+            b.iter(|| {
+                step(&mut objs, &mut forward);
+                g.par_update(&objs);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert, query, update_maintain);
+criterion_main!(benches);
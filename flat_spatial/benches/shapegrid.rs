@@ -0,0 +1,120 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use flat_spatial::SparseShapeGrid;
+use geom::{Vec2, AABB};
+
+/// Shapes spread over a 10km wide square, mostly lot-sized with some long roads,
+/// like the spatial map of a big city.
+const SIZE: f32 = 10000.0;
+const CELL_SIZE: i32 = 50;
+const SIZES: [usize; 3] = [1_000, 10_000, 50_000];
+
+fn random_shapes(n: usize) -> Vec<AABB> {
+    let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut rand = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % 1_000_000) as f32 / 1_000_000.0
+    };
+    (0..n)
+        .map(|i| {
+            let ll = Vec2::new(rand() - 0.5, rand() - 0.5) * SIZE;
+            let size = if i % 10 == 0 {
+                Vec2::new(rand() * 400.0, rand() * 400.0)
+            } else {
+                Vec2::new(10.0 + rand() * 20.0, 10.0 + rand() * 20.0)
+            };
+            AABB::new(ll, ll + size)
+        })
+        .collect()
+}
+
+fn grids(shapes: &[AABB]) -> Vec<(&'static str, SparseShapeGrid<(), AABB>)> {
+    let mut single = SparseShapeGrid::new(CELL_SIZE);
+    let mut multi = SparseShapeGrid::new_multilevel(CELL_SIZE, 5);
+    for &s in shapes {
+        single.insert(s, ());
+        multi.insert(s, ());
+    }
+    vec![("single level", single), ("multilevel", multi)]
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shapegrid insert");
+    for &n in &SIZES {
+        let shapes = random_shapes(n);
+        group.bench_with_input(BenchmarkId::new("single level", n), &shapes, |b, shapes| {
+            b.iter(|| {
+                let mut g = SparseShapeGrid::new(CELL_SIZE);
+                for &s in shapes {
+                    g.insert(s, ());
+                }
+                g
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("multilevel", n), &shapes, |b, shapes| {
+            b.iter(|| {
+                let mut g = SparseShapeGrid::new_multilevel(CELL_SIZE, 5);
+                for &s in shapes {
+                    g.insert(s, ());
+                }
+                g
+            })
+        });
+    }
+    group.finish();
+}
+
+fn query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shapegrid query");
+    for &n in &SIZES {
+        let shapes = random_shapes(n);
+        let centers: Vec<Vec2> = random_shapes(100).iter().map(|x| x.ll).collect();
+
+        for (name, g) in grids(&shapes) {
+            // About the size of the cursor projection and lot brush queries
+            group.bench_with_input(
+                BenchmarkId::new(format!("around {}", name), n),
+                &g,
+                |b, g| {
+                    b.iter(|| {
+                        centers
+                            .iter()
+                            .map(|&p| g.query_around(p, 20.0).count())
+                            .sum::<usize>()
+                    })
+                },
+            );
+            group.bench_with_input(BenchmarkId::new(format!("knn {}", name), n), &g, |b, g| {
+                b.iter(|| centers.iter().map(|&p| g.knn(p, 5).len()).sum::<usize>())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn set_shape(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shapegrid set_shape");
+    for &n in &SIZES {
+        let shapes = random_shapes(n);
+        for (name, mut g) in grids(&shapes) {
+            let handles: Vec<_> = g.handles().take(100).collect();
+            let mut offset = 5.0;
+            group.bench_function(BenchmarkId::new(name, n), |b| {
+                b.iter(|| {
+                    offset = -offset;
+                    for &h in &handles {
+                        let s = *g.get(h).unwrap().0;
+                        let moved =
+                            AABB::new(s.ll + Vec2::splat(offset), s.ur + Vec2::splat(offset));
+                        g.set_shape(h, moved);
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert, query, set_shape);
+criterion_main!(benches);
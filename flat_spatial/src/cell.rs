@@ -57,3 +57,42 @@ impl GridCell {
         }
     }
 }
+
+#[cfg(feature = "rayon")]
+impl GridCell {
+    /// Same as maintain, but only reads the objects so that cells can be maintained in parallel.
+    /// Objects whose state must be reset are pushed to `changes` along with that state.
+    pub fn par_maintain<T: Copy>(
+        &mut self,
+        objects: &GridObjects<T>,
+        changes: &mut Vec<(GridHandle, ObjectState)>,
+    ) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        let mut i = 0;
+        while i < self.objs.len() {
+            let (obj_id, obj_pos) = unsafe { self.objs.get_unchecked_mut(i) };
+
+            let store_obj = &objects[*obj_id];
+
+            match store_obj.state {
+                ObjectState::NewPos(pos) => {
+                    changes.push((*obj_id, store_obj.state));
+                    *obj_pos = pos;
+                    i += 1
+                }
+                ObjectState::Relocate(..) | ObjectState::Removed => {
+                    changes.push((*obj_id, store_obj.state));
+                    self.objs.swap_remove(i);
+                }
+                ObjectState::Unchanged => {
+                    *obj_pos = store_obj.pos;
+                    i += 1
+                }
+            }
+        }
+    }
+}
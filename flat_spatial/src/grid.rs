@@ -1,8 +1,16 @@
 use crate::cell::{CellObject, GridCell};
-use crate::storage::{cell_range, CellIdx, SparseStorage, Storage};
-use geom::Vec2;
+use crate::storage::{
+    cell_range, cell_ray, cell_ring, ring_clearance, CellIdx, SparseStorage, Storage,
+};
+use geom::{Ray, Vec2};
 use serde::{Deserialize, Serialize};
 use slotmap::{new_key_type, SlotMap};
+use std::cmp::Ordering;
+
+#[cfg(feature = "rayon")]
+use crate::storage::ParStorage;
+#[cfg(feature = "rayon")]
+use std::sync::Mutex;
 
 pub type GridObjects<O> = SlotMap<GridHandle, StoreObject<O>>;
 
@@ -298,6 +306,82 @@ impl<ST: Storage<GridCell>, O: Copy> Grid<O, ST> {
             .flat_map(|x| x.objs.iter().copied())
     }
 
+    /// Iterates over all objects by increasing distance to pos.
+    /// Cells are visited ring by ring around pos, so only the cells that can contain the
+    /// yielded objects (and one ring more) are looked at.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flat_spatial::Grid;
+    /// use geom::Vec2;
+    ///
+    /// let mut g: Grid<()> = Grid::new(10);
+    /// let a = g.insert(Vec2::new(30.0, 0.0), ());
+    /// let b = g.insert(Vec2::new(0.0, 5.0), ());
+    /// let c = g.insert(Vec2::new(-100.0, 0.0), ());
+    ///
+    /// let nearest: Vec<_> = g.query_nearest(Vec2::ZERO).map(|(id, _pos)| id).collect();
+    ///
+    /// assert_eq!(nearest, vec![b, a, c]);
+    /// ```
+    pub fn query_nearest(&self, pos: Vec2) -> impl Iterator<Item = CellObject> + '_ {
+        NearestIter {
+            grid: self,
+            pos,
+            center: self.storage.cell_id(pos),
+            ring: 0,
+            clearance: f32::NEG_INFINITY,
+            seen: 0,
+            candidates: vec![],
+        }
+    }
+
+    /// Returns the k objects closest to pos, closest first.
+    /// Fewer are returned if the grid doesn't contain k objects.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flat_spatial::Grid;
+    /// use geom::Vec2;
+    ///
+    /// let mut g: Grid<()> = Grid::new(10);
+    /// let a = g.insert(Vec2::new(30.0, 0.0), ());
+    /// let b = g.insert(Vec2::new(0.0, 5.0), ());
+    /// g.insert(Vec2::new(-100.0, 0.0), ());
+    ///
+    /// let knn: Vec<_> = g.knn(Vec2::ZERO, 2).into_iter().map(|(id, _pos)| id).collect();
+    ///
+    /// assert_eq!(knn, vec![b, a]);
+    /// ```
+    pub fn knn(&self, pos: Vec2, k: usize) -> Vec<CellObject> {
+        self.query_nearest(pos).take(k).collect()
+    }
+
+    /// Queries for all objects in the cells crossed by the ray, up to max_dist from its origin.
+    /// Objects are yielded cell by cell in the order the ray crosses them, but are not filtered:
+    /// check their distance to the ray yourself.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flat_spatial::Grid;
+    /// use geom::{Ray, Vec2};
+    ///
+    /// let mut g: Grid<()> = Grid::new(10);
+    /// let a = g.insert(Vec2::new(35.0, 5.0), ());
+    /// let b = g.insert(Vec2::new(15.0, 5.0), ());
+    /// g.insert(Vec2::new(15.0, 25.0), ());
+    ///
+    /// let ray = Ray::new(Vec2::new(1.0, 1.0), Vec2::UNIT_X);
+    /// let hits: Vec<_> = g.raycast(ray, 50.0).map(|(id, _pos)| id).collect();
+    ///
+    /// assert_eq!(hits, vec![b, a]);
+    /// ```
+    pub fn raycast(&self, ray: Ray, max_dist: f32) -> impl Iterator<Item = CellObject> + '_ {
+        cell_ray(&self.storage, ray, max_dist)
+            .flat_map(move |id| self.storage.cell(id))
+            .flat_map(|x| x.objs.iter().copied())
+    }
+
     /// Returns the number of objects currently available
     /// (removals that were not confirmed with maintain() are still counted)
     pub fn len(&self) -> usize {
@@ -310,3 +394,141 @@ impl<ST: Storage<GridCell>, O: Copy> Grid<O, ST> {
         self.objects.is_empty()
     }
 }
+
+#[cfg(feature = "rayon")]
+impl<ST: ParStorage<GridCell>, O: Copy + Sync> Grid<O, ST> {
+    /// Sets the position of many objects at once and maintains the grid, with the cells being
+    /// maintained in parallel.
+    /// The result is the same as calling set_position for each update followed by maintain(),
+    /// but every object is only written to once, which makes it faster for large batches.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flat_spatial::Grid;
+    /// use geom::Vec2;
+    /// let mut g: Grid<()> = Grid::new(10);
+    /// let a = g.insert(Vec2::new(5.0, 3.0), ());
+    /// let b = g.insert(Vec2::new(6.0, 3.0), ());
+    ///
+    /// g.remove(b);
+    /// g.par_update(&[(a, Vec2::new(25.0, 3.0))]);
+    ///
+    /// assert_eq!(g.get(a), Some((Vec2::new(25.0, 3.0), &())));
+    /// assert!(g.get(b).is_none());
+    /// assert_eq!(g.query_around(Vec2::new(25.0, 3.0), 1.0).count(), 1);
+    /// ```
+    pub fn par_update(&mut self, updates: &[(GridHandle, Vec2)]) {
+        let Self {
+            storage, objects, ..
+        } = self;
+
+        for &(handle, pos) in updates {
+            let obj = match objects.get_mut(handle) {
+                Some(x) => x,
+                None => {
+                    debug_assert!(false, "Object not in grid anymore");
+                    continue;
+                }
+            };
+
+            if matches!(obj.state, ObjectState::Removed) {
+                continue;
+            }
+
+            // The position is applied right away, only the cell entries are left to be updated
+            let target_id = storage.cell_id(pos);
+            obj.pos = pos;
+            obj.state = if target_id == obj.cell_id {
+                ObjectState::Unchanged
+            } else {
+                ObjectState::Relocate(pos, target_id)
+            };
+
+            storage.cell_mut_unchecked(obj.cell_id).dirty = true;
+        }
+
+        let changes = Mutex::new(vec![]);
+        let objs: &GridObjects<O> = objects;
+
+        storage.par_modify(|cell| {
+            let mut cell_changes = vec![];
+            cell.par_maintain(objs, &mut cell_changes);
+            if !cell_changes.is_empty() {
+                changes.lock().unwrap().extend(cell_changes);
+            }
+            cell.objs.is_empty()
+        });
+
+        // The cells finish in any order, sorting keeps the order of the objects in the cells
+        // (and of the handles given out after removals) the same on every run
+        let mut changes = changes.into_inner().unwrap();
+        changes.sort_unstable_by_key(|&(handle, _)| handle);
+
+        for (handle, state) in changes {
+            if let ObjectState::Removed = state {
+                objects.remove(handle);
+                continue;
+            }
+
+            let obj = &mut objects[handle];
+            obj.state = ObjectState::Unchanged;
+            match state {
+                ObjectState::NewPos(pos) => obj.pos = pos,
+                ObjectState::Relocate(pos, target_id) => {
+                    obj.pos = pos;
+                    obj.cell_id = target_id;
+                    storage.cell_mut(pos).1.objs.push((handle, pos));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+struct NearestIter<'a, O, ST: Storage<GridCell>> {
+    grid: &'a Grid<O, ST>,
+    pos: Vec2,
+    center: CellIdx,
+    ring: i32,
+    /// Objects not seen yet are at least this far from pos
+    clearance: f32,
+    seen: usize,
+    /// Sorted by decreasing distance so the closest is popped first
+    candidates: Vec<(f32, CellObject)>,
+}
+
+impl<'a, O: Copy, ST: Storage<GridCell>> Iterator for NearestIter<'a, O, ST> {
+    type Item = CellObject;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let all_seen = self.seen == self.grid.objects.len();
+            if let Some(&(dist, _)) = self.candidates.last() {
+                if all_seen || dist <= self.clearance {
+                    return self.candidates.pop().map(|(_, obj)| obj);
+                }
+            }
+            if all_seen {
+                return None;
+            }
+
+            let storage = &self.grid.storage;
+            for id in cell_ring(self.center, self.ring) {
+                let cell = match storage.cell(id) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                self.seen += cell.objs.len();
+                let pos = self.pos;
+                self.candidates
+                    .extend(cell.objs.iter().map(|&(h, p)| (p.distance(pos), (h, p))));
+            }
+
+            self.clearance = ring_clearance(storage, self.pos, self.center, self.ring);
+            self.ring += 1;
+
+            self.candidates
+                .sort_unstable_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+        }
+    }
+}
@@ -4,6 +4,7 @@
 //!
 //! Both DenseGrid and SparseGrid partition the space using cells of user defined width.
 //! DenseGrid uses a Vec of cells and SparseGrid a HashMap (so cells are lazily allocated).
+//! LooseShapeGrid stores shapes no bigger than a cell in a single cell with loosened bounds.
 //!
 
 pub mod cell;
@@ -15,6 +16,7 @@ pub use grid::Grid;
 pub use shapegrid::ShapeGrid;
 
 use storage::DenseStorage;
+use storage::LooseStorage;
 use storage::SparseStorage;
 
 pub type DenseGrid<O> = Grid<O, DenseStorage<cell::GridCell>>;
@@ -22,3 +24,4 @@ pub type SparseGrid<O> = Grid<O, SparseStorage<cell::GridCell>>;
 
 pub type DenseShapeGrid<O, S> = ShapeGrid<O, S, DenseStorage<cell::ShapeGridCell>>;
 pub type SparseShapeGrid<O, S> = ShapeGrid<O, S, SparseStorage<cell::ShapeGridCell>>;
+pub type LooseShapeGrid<O, S> = ShapeGrid<O, S, LooseStorage<SparseStorage<cell::ShapeGridCell>>>;
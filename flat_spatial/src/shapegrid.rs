@@ -1,8 +1,11 @@
 use crate::cell::ShapeGridCell;
-use crate::storage::{cell_range, SparseStorage, Storage};
-use geom::{Circle, Intersect, Shape, Vec2, AABB};
+use crate::storage::{
+    cell_range, cell_ray, cell_ring, ring_clearance, CellIdx, RayCells, SparseStorage, Storage,
+};
+use geom::{Circle, Intersect, Ray, Shape, Vec2, AABB};
 use serde::{Deserialize, Serialize};
 use slotmap::{new_key_type, SlotMap};
+use std::cmp::Ordering;
 use std::collections::HashSet;
 
 pub type ShapeGridObjects<O, S> = SlotMap<ShapeGridHandle, StoreObject<O, S>>;
@@ -19,6 +22,8 @@ pub struct StoreObject<O: Copy, S: Shape> {
     /// User-defined object to be associated with a value
     obj: O,
     pub shape: S,
+    /// Level of the cells the shape is stored in
+    level: u8,
 }
 
 /// ShapeGrid is a generic shape-based spatial partitioning structure that uses a generic storage of cells which acts as a
@@ -34,6 +39,11 @@ pub struct StoreObject<O: Copy, S: Shape> {
 /// ShapeGrid's allows eager removals and position updates, however for big shapes (spanning many cells)
 /// this can be expensive, so beware.
 ///
+/// ## Levels
+/// If the shapes are of very different sizes, a multilevel grid (see `new_multilevel`) stores
+/// each shape in the level whose cells are about its size, so that big shapes only span a few
+/// cells. Queries look into every level.
+///
 /// Use this grid for mostly static objects with the occasional removal/position update if needed.
 ///
 /// A SlotMap is used for objects managing, adding a level of indirection between shapes and objects.
@@ -65,7 +75,8 @@ pub struct ShapeGrid<
     S: Shape + Intersect<AABB> + Copy,
     ST: Storage<ShapeGridCell> = SparseStorage<ShapeGridCell>,
 > {
-    storage: ST,
+    /// Level n cells are 2^n times wider than level 0 ones
+    levels: Vec<ST>,
     objects: ShapeGridObjects<O, S>,
 }
 
@@ -73,27 +84,64 @@ impl<S: Shape + Intersect<AABB> + Copy, ST: Storage<ShapeGridCell>, O: Copy> Sha
     /// Creates an empty grid.   
     /// The cell size should be about the same magnitude as your queries size.
     pub fn new(cell_size: i32) -> Self {
-        Self {
-            storage: ST::new(cell_size),
-            objects: ShapeGridObjects::default(),
-        }
+        Self::with_storage(ST::new(cell_size))
     }
 
     /// Creates an empty grid.   
     /// The cell size should be about the same magnitude as your queries size.
     pub fn with_storage(st: ST) -> Self {
         Self {
-            storage: st,
+            levels: vec![st],
             objects: ShapeGridObjects::default(),
         }
     }
 
-    fn cells_apply(storage: &mut ST, shape: &S, f: impl Fn(&mut ShapeGridCell, bool)) {
+    /// Creates an empty grid with `levels` levels of cells, the cells of each level being twice as
+    /// wide as the ones of the level below, starting at cell_size.  
+    /// Shapes are stored in the first level whose cells are at least as big as them.  
+    /// Panics if the cells of the last level are wider than i32::MAX.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flat_spatial::ShapeGrid;
+    /// use geom::{Vec2, AABB};
+    ///
+    /// let mut g: ShapeGrid<(), AABB> = ShapeGrid::new_multilevel(10, 4);
+    /// let small = g.insert(AABB::new(Vec2::ZERO, Vec2::new(5.0, 5.0)), ());
+    /// let big = g.insert(AABB::new(Vec2::ZERO, Vec2::new(70.0, 3.0)), ());
+    ///
+    /// let around: Vec<_> = g.query_around(Vec2::new(60.0, 1.0), 2.0).map(|(h, _, _)| h).collect();
+    /// assert_eq!(around, vec![big]);
+    /// assert_eq!(g.query_around(Vec2::new(2.0, 2.0), 1.0).count(), 2);
+    /// ```
+    pub fn new_multilevel(cell_size: i32, levels: usize) -> Self {
+        assert!(levels > 0, "A grid needs at least one level");
+        assert!(
+            levels <= 32 && (cell_size as i64) << (levels - 1) <= i32::MAX as i64,
+            "The cells of the last level ({} << {}) are too big",
+            cell_size,
+            levels - 1
+        );
+        Self {
+            levels: (0..levels).map(|l| ST::new(cell_size << l)).collect(),
+            objects: ShapeGridObjects::default(),
+        }
+    }
+
+    fn level_for(levels: &[ST], shape: &S) -> usize {
         let bbox = shape.bbox();
-        let ll = storage.cell_mut(bbox.ll).0;
-        let ur = storage.cell_mut(bbox.ur).0;
+        let size = bbox.w().max(bbox.h());
+        levels
+            .iter()
+            .position(|st| size <= st.cell_aabb((0, 0)).w())
+            .unwrap_or(levels.len() - 1)
+    }
+
+    fn cells_apply(storage: &mut ST, shape: &S, f: impl Fn(&mut ShapeGridCell, bool)) {
+        let (ll, ur) = storage.cells_mut_for(shape.bbox());
+        let loose = storage.looseness();
         for id in cell_range(ll, ur) {
-            if !shape.intersects(&storage.cell_aabb(id)) {
+            if !shape.intersects(&storage.cell_aabb(id).expand(loose)) {
                 continue;
             }
             f(storage.cell_mut_unchecked(id), ll == ur)
@@ -104,11 +152,16 @@ impl<S: Shape + Intersect<AABB> + Copy, ST: Storage<ShapeGridCell>, O: Copy> Sha
     /// Returns the unique and stable handle to be used with get_obj
     pub fn insert(&mut self, shape: S, obj: O) -> ShapeGridHandle {
         let Self {
-            storage, objects, ..
+            levels, objects, ..
         } = self;
 
-        let h = objects.insert(StoreObject { obj, shape });
-        Self::cells_apply(storage, &shape, |cell, sing_cell| {
+        let level = Self::level_for(levels, &shape);
+        let h = objects.insert(StoreObject {
+            obj,
+            shape,
+            level: level as u8,
+        });
+        Self::cells_apply(&mut levels[level], &shape, |cell, sing_cell| {
             cell.objs.push((h, sing_cell));
         });
         h
@@ -121,9 +174,9 @@ impl<S: Shape + Intersect<AABB> + Copy, ST: Storage<ShapeGridCell>, O: Copy> Sha
             .get_mut(handle)
             .expect("Object not in grid anymore");
 
-        let storage = &mut self.storage;
+        let levels = &mut self.levels;
 
-        Self::cells_apply(storage, &obj.shape, |cell, _| {
+        Self::cells_apply(&mut levels[obj.level as usize], &obj.shape, |cell, _| {
             let p = match cell.objs.iter().position(|(x, _)| *x == handle) {
                 Some(x) => x,
                 None => return,
@@ -131,18 +184,20 @@ impl<S: Shape + Intersect<AABB> + Copy, ST: Storage<ShapeGridCell>, O: Copy> Sha
             cell.objs.swap_remove(p);
         });
 
-        Self::cells_apply(storage, &shape, |cell, sing_cell| {
+        let level = Self::level_for(levels, &shape);
+        Self::cells_apply(&mut levels[level], &shape, |cell, sing_cell| {
             cell.objs.push((handle, sing_cell))
         });
 
         obj.shape = shape;
+        obj.level = level as u8;
     }
 
     /// Removes an object from the grid.
     pub fn remove(&mut self, handle: ShapeGridHandle) -> Option<O> {
         let st = self.objects.remove(handle)?;
 
-        let storage = &mut self.storage;
+        let storage = &mut self.levels[st.level as usize];
         Self::cells_apply(storage, &st.shape, |cell, _| {
             let p = match cell.objs.iter().position(|(x, _)| *x == handle) {
                 Some(x) => x,
@@ -174,9 +229,15 @@ impl<S: Shape + Intersect<AABB> + Copy, ST: Storage<ShapeGridCell>, O: Copy> Sha
         self.objects.get_mut(id).map(|x| (&x.shape, &mut x.obj))
     }
 
-    /// The underlying storage
+    /// The underlying storage of the first level
+    #[deprecated(note = "a grid can have several levels, use storages")]
     pub fn storage(&self) -> &ST {
-        &self.storage
+        &self.levels[0]
+    }
+
+    /// The underlying storages of every level, from the smallest cells to the biggest
+    pub fn storages(&self) -> &[ST] {
+        &self.levels
     }
 
     /// Queries for objects intersecting a given shape.
@@ -198,23 +259,106 @@ impl<S: Shape + Intersect<AABB> + Copy, ST: Storage<ShapeGridCell>, O: Copy> Sha
         shape: QS,
     ) -> impl Iterator<Item = ShapeGridHandle> + 'a {
         let bbox = shape.bbox();
-        let storage = &self.storage;
-
-        let ll_id = storage.cell_id(bbox.ll);
-        let ur_id = storage.cell_id(bbox.ur);
-
-        let iter = cell_range(ll_id, ur_id)
-            .filter(move |&id| shape.intersects(&storage.cell_aabb(id)))
-            .flat_map(move |id| storage.cell(id))
+        let levels = &self.levels;
+
+        // Objects of loose storages can be found in the cells next to the ones the query covers
+        let loose_aabb = move |storage: &ST, id| storage.cell_aabb(id).expand(storage.looseness());
+        let covered = move |storage: &ST| {
+            let bbox = bbox.expand(storage.looseness());
+            (storage.cell_id(bbox.ll), storage.cell_id(bbox.ur))
+        };
+
+        let iter = levels
+            .iter()
+            .enumerate()
+            .flat_map(move |(l, storage)| {
+                let (ll, ur) = covered(storage);
+                cell_range(ll, ur).map(move |id| (l, id))
+            })
+            .filter(move |&(l, id)| shape.intersects(&loose_aabb(&levels[l], id)))
+            .flat_map(move |(l, id)| levels[l].cell(id))
             .flat_map(|x| x.objs.iter().copied());
 
-        if ll_id == ur_id {
+        // An object is only stored in one level, so it can only be seen twice if the query spans
+        // several cells of the same level
+        let single_cell = levels.iter().all(|storage| {
+            let (ll, ur) = covered(storage);
+            ll == ur
+        });
+
+        if single_cell {
             QueryIter::Simple(iter)
         } else {
             QueryIter::Dedup(HashSet::with_capacity(5), iter)
         }
     }
 
+    /// Iterates over all objects by increasing distance to pos, along with that distance.
+    /// The distance is measured to the shape's bounding box, or to the cells the shape was
+    /// first found in if those are further away, so it never exceeds the real distance.
+    ///
+    /// ```rust
+    /// use flat_spatial::ShapeGrid;
+    /// use geom::{Vec2, AABB};
+    ///
+    /// let mut g: ShapeGrid<(), AABB> = ShapeGrid::new(10);
+    /// let a = g.insert(AABB::new(Vec2::new(20.0, -5.0), Vec2::new(25.0, 5.0)), ());
+    /// let b = g.insert(AABB::new(Vec2::new(-12.0, -1.0), Vec2::new(-8.0, 1.0)), ());
+    ///
+    /// let nearest: Vec<_> = g.query_nearest(Vec2::ZERO).collect();
+    ///
+    /// assert_eq!(nearest, vec![(b, 8.0), (a, 20.0)]);
+    /// ```
+    pub fn query_nearest(&self, pos: Vec2) -> impl Iterator<Item = (ShapeGridHandle, f32)> + '_ {
+        NearestIter {
+            grid: self,
+            pos,
+            rings: self
+                .levels
+                .iter()
+                .map(|storage| (storage.cell_id(pos), 0, 0.0))
+                .collect(),
+            seen: HashSet::new(),
+            candidates: vec![],
+        }
+    }
+
+    /// Returns the k objects closest to pos, closest first, as ordered by query_nearest.
+    /// Fewer are returned if the grid doesn't contain k objects.
+    pub fn knn(&self, pos: Vec2, k: usize) -> Vec<ShapeGridHandle> {
+        self.query_nearest(pos).take(k).map(|(h, _)| h).collect()
+    }
+
+    /// Queries for all objects in the cells crossed by the ray, up to max_dist from its origin.
+    /// Objects are yielded in the order the ray reaches their cells, each only once, but are
+    /// not checked against the ray itself.
+    /// With loose storages, the neighbours of the crossed cells are looked into as well.
+    pub fn raycast(&self, ray: Ray, max_dist: f32) -> impl Iterator<Item = ShapeGridHandle> + '_ {
+        let levels = &self.levels;
+        let mut rays: Vec<RayCells> = levels
+            .iter()
+            .map(|storage| cell_ray(storage, ray, max_dist))
+            .collect();
+
+        // Walk the levels together, always advancing the one whose next cell is the closest
+        let iter = std::iter::from_fn(move || {
+            let (l, _) = rays
+                .iter()
+                .enumerate()
+                .filter_map(|(l, cells)| Some((l, cells.next_dist()?)))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))?;
+            Some((l, rays[l].next()?))
+        })
+        .flat_map(move |(l, (x, y))| {
+            let r = if levels[l].looseness() > 0.0 { 1 } else { 0 };
+            cell_range((x - r, y - r), (x + r, y + r)).map(move |id| (l, id))
+        })
+        .flat_map(move |(l, id)| levels[l].cell(id))
+        .flat_map(|x| x.objs.iter().copied());
+
+        QueryIter::Dedup(HashSet::new(), iter)
+    }
+
     /// Returns the number of objects currently available
     /// (removals that were not confirmed with maintain() are still counted)
     pub fn len(&self) -> usize {
@@ -245,6 +389,74 @@ where
     }
 }
 
+struct NearestIter<'a, O: Copy, S: Shape + Intersect<AABB> + Copy, ST: Storage<ShapeGridCell>> {
+    grid: &'a ShapeGrid<O, S, ST>,
+    pos: Vec2,
+    /// For each level: the center cell, the next ring to look at and the clearance.
+    /// Objects of that level not seen yet are at least clearance away from pos.
+    rings: Vec<(CellIdx, i32, f32)>,
+    seen: HashSet<ShapeGridHandle>,
+    /// Sorted by decreasing distance so the closest is popped first
+    candidates: Vec<(f32, ShapeGridHandle)>,
+}
+
+impl<'a, O: Copy, S: Shape + Intersect<AABB> + Copy, ST: Storage<ShapeGridCell>> Iterator
+    for NearestIter<'a, O, S, ST>
+{
+    type Item = (ShapeGridHandle, f32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let all_seen = self.seen.len() == self.grid.objects.len();
+
+            // Expand the level whose clearance lags behind, it bounds every other one
+            let (l, &(center, ring, clearance)) = self
+                .rings
+                .iter()
+                .enumerate()
+                .min_by(|a, b| (a.1).2.partial_cmp(&(b.1).2).unwrap_or(Ordering::Equal))?;
+
+            if let Some(&(dist, _)) = self.candidates.last() {
+                if all_seen || dist <= clearance {
+                    return self.candidates.pop().map(|(dist, h)| (h, dist));
+                }
+            }
+            if all_seen {
+                return None;
+            }
+
+            let storage = &self.grid.levels[l];
+            for id in cell_ring(center, ring) {
+                let cell = match storage.cell(id) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                for &(h, _) in &cell.objs {
+                    if !self.seen.insert(h) {
+                        continue;
+                    }
+                    // The shape wasn't in the previous rings, so it's at least clearance away
+                    let dist = self.grid.objects[h]
+                        .shape
+                        .bbox()
+                        .distance(self.pos)
+                        .max(clearance);
+                    self.candidates.push((dist, h));
+                }
+            }
+
+            self.rings[l] = (
+                center,
+                ring + 1,
+                ring_clearance(storage, self.pos, center, ring),
+            );
+
+            self.candidates
+                .sort_unstable_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+        }
+    }
+}
+
 enum QueryIter<T: Iterator<Item = (ShapeGridHandle, bool)>> {
     Simple(T),
     Dedup(HashSet<ShapeGridHandle>, T),
@@ -1,4 +1,6 @@
-use geom::{Vec2, AABB};
+use geom::{Ray, Vec2, AABB};
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -16,6 +18,72 @@ pub fn cell_range((x1, y1): CellIdx, (x2, y2): CellIdx) -> impl Iterator<Item =
     }
 }
 
+/// Iterates over the cells at exactly `r` cells (chebyshev distance) from the center cell.
+/// Ring 0 is the center itself.
+pub fn cell_ring((x, y): CellIdx, r: i32) -> impl Iterator<Item = CellIdx> {
+    debug_assert!(r >= 0);
+    let sides = if r > 0 {
+        Some(
+            cell_range((x - r, y + r), (x + r, y + r))
+                .chain(cell_range((x - r, y - r + 1), (x - r, y + r - 1)))
+                .chain(cell_range((x + r, y - r + 1), (x + r, y + r - 1))),
+        )
+    } else {
+        None
+    };
+
+    cell_range((x - r, y - r), (x + r, y - r)).chain(sides.into_iter().flatten())
+}
+
+/// Distance from pos to the border of the square made of the cells within `r` rings of center.
+/// Anything outside those cells is at least this far away.
+pub fn ring_clearance<T>(storage: &impl Storage<T>, pos: Vec2, (x, y): CellIdx, r: i32) -> f32 {
+    let ll = storage.cell_aabb((x - r, y - r)).ll;
+    let ur = storage.cell_aabb((x + r, y + r)).ur;
+
+    ((pos.x - ll.x)
+        .min(ur.x - pos.x)
+        .min(pos.y - ll.y)
+        .min(ur.y - pos.y)
+        - storage.looseness())
+    .max(0.0)
+}
+
+/// Iterates over the cells crossed by the ray, in order, up to max_dist.
+pub fn cell_ray<T>(storage: &impl Storage<T>, ray: Ray, max_dist: f32) -> RayCells {
+    let start = storage.cell_id(ray.from);
+    let aabb = storage.cell_aabb(start);
+    let cell_size = aabb.w();
+
+    let dir = ray.dir.try_normalize().unwrap_or(Vec2::ZERO);
+
+    let axis = |d: f32, from: f32, lo: f32, hi: f32| {
+        if d > 0.0 {
+            (1, ((hi - from) / d).max(0.0), cell_size / d)
+        } else if d < 0.0 {
+            (-1, ((lo - from) / d).max(0.0), -cell_size / d)
+        } else {
+            (0, f32::INFINITY, f32::INFINITY)
+        }
+    };
+
+    let (step_x, t_max_x, t_delta_x) = axis(dir.x, ray.from.x, aabb.ll.x, aabb.ur.x);
+    let (step_y, t_max_y, t_delta_y) = axis(dir.y, ray.from.y, aabb.ll.y, aabb.ur.y);
+
+    RayCells {
+        cur: Some(start),
+        dist: 0.0,
+        step_x,
+        step_y,
+        t_max_x,
+        t_max_y,
+        t_delta_x,
+        t_delta_y,
+        // a zero direction only visits the starting cell
+        max_dist: if dir == Vec2::ZERO { 0.0 } else { max_dist },
+    }
+}
+
 /// The storage trait, implement this if you want to use a custom point storage for the Grid.
 pub trait Storage<T> {
     fn new(cell_size: i32) -> Self;
@@ -30,6 +98,24 @@ pub trait Storage<T> {
     fn cell_id(&self, p: Vec2) -> CellIdx;
 
     fn cell_aabb(&self, id: CellIdx) -> AABB;
+
+    /// Allocates the cells an object with this bounding box is stored in and returns them as an
+    /// inclusive range. By default those are all the cells the bounding box covers.
+    fn cells_mut_for(&mut self, bbox: AABB) -> (CellIdx, CellIdx) {
+        (self.cell_mut(bbox.ll).0, self.cell_mut(bbox.ur).0)
+    }
+
+    /// How far objects can stick out of the aabb of the cell they are stored in
+    fn looseness(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Storages whose cells can be modified in parallel, used by the par_ methods of the Grid.
+#[cfg(feature = "rayon")]
+pub trait ParStorage<T>: Storage<T> {
+    /// Same as modify, but f is called on the cells in parallel
+    fn par_modify(&mut self, f: impl Fn(&mut T) -> bool + Sync + Send);
 }
 
 /// DenseStorage stores cells in a Vec to be used for a Grid.
@@ -186,6 +272,14 @@ impl<T: Default> Storage<T> for DenseStorage<T> {
     }
 
     fn cell(&self, id: CellIdx) -> Option<&T> {
+        let (x, y) = id;
+        if x < self.start_x
+            || y < self.start_y
+            || x >= self.start_x + self.width
+            || y >= self.start_y + self.height
+        {
+            return None;
+        }
         self.cells.get(self.pos(id))
     }
 
@@ -213,6 +307,15 @@ impl<T: Default> Storage<T> for DenseStorage<T> {
     }
 }
 
+#[cfg(feature = "rayon")]
+impl<T: Default + Send> ParStorage<T> for DenseStorage<T> {
+    fn par_modify(&mut self, f: impl Fn(&mut T) -> bool + Sync + Send) {
+        self.cells.par_iter_mut().for_each(|x| {
+            f(x);
+        })
+    }
+}
+
 /// SparseStorage stores cells in a HashMap to be used in a Grid.
 /// It is Sparse because cells are eagerly allocated, and cleaned when they are empty.
 /// It implements the Storage trait.
@@ -280,6 +383,116 @@ impl<T: Default> Storage<T> for SparseStorage<T> {
     }
 }
 
+#[cfg(feature = "rayon")]
+impl<T: Default + Send> ParStorage<T> for SparseStorage<T> {
+    fn par_modify(&mut self, f: impl Fn(&mut T) -> bool + Sync + Send) {
+        let to_remove: Vec<CellIdx> = self
+            .cells
+            .par_iter_mut()
+            .filter_map(|(&id, cell)| if f(cell) { Some(id) } else { None })
+            .collect();
+
+        for id in to_remove {
+            self.cells.remove(&id);
+        }
+    }
+}
+
+/// LooseStorage wraps another storage so that shapes no bigger than a cell are stored in the
+/// single cell containing their center, the bounds of the cells being loosened by half a cell on
+/// each side. Bigger shapes are stored in every cell they cover.
+/// It implements the Storage trait, points are stored exactly like with the inner storage.
+///
+/// ```rust
+/// use flat_spatial::storage::{LooseStorage, SparseStorage};
+/// use flat_spatial::{Grid, ShapeGrid};
+/// use geom::{Vec2, AABB};
+///
+/// let mut g: ShapeGrid<(), AABB, LooseStorage<SparseStorage<_>>> = ShapeGrid::new(10);
+/// let a = g.insert(AABB::new(Vec2::new(8.0, 8.0), Vec2::new(12.0, 12.0)), ());
+///
+/// // Stored in one cell but found from all the cells it overlaps
+/// let found: Vec<_> = g.query_around(Vec2::new(11.0, 11.0), 0.5).map(|(h, _, _)| h).collect();
+/// assert_eq!(found, vec![a]);
+/// assert_eq!(g.knn(Vec2::new(20.0, 20.0), 1), vec![a]);
+///
+/// // Points don't stick out of their cell
+/// let mut points: Grid<(), LooseStorage<SparseStorage<_>>> = Grid::new(10);
+/// let b = points.insert(Vec2::new(9.0, 9.0), ());
+/// assert_eq!(points.query_around(Vec2::new(11.0, 11.0), 3.0).next().map(|(h, _)| h), Some(b));
+/// ```
+#[derive(Clone, Deserialize, Serialize)]
+pub struct LooseStorage<ST> {
+    inner: ST,
+    cell_size: f32,
+}
+
+impl<ST> LooseStorage<ST> {
+    pub fn with_storage<T>(inner: ST) -> Self
+    where
+        ST: Storage<T>,
+    {
+        let cell_size = inner.cell_aabb((0, 0)).w();
+        Self { inner, cell_size }
+    }
+
+    pub fn inner(&self) -> &ST {
+        &self.inner
+    }
+}
+
+impl<T, ST: Storage<T>> Storage<T> for LooseStorage<ST> {
+    fn new(cell_size: i32) -> Self {
+        Self {
+            inner: ST::new(cell_size),
+            cell_size: cell_size as f32,
+        }
+    }
+
+    fn modify(&mut self, f: impl FnMut(&mut T) -> bool) {
+        self.inner.modify(f)
+    }
+
+    fn cell_mut(&mut self, pos: Vec2) -> (CellIdx, &mut T) {
+        self.inner.cell_mut(pos)
+    }
+
+    fn cell_mut_unchecked(&mut self, id: CellIdx) -> &mut T {
+        self.inner.cell_mut_unchecked(id)
+    }
+
+    fn cell(&self, id: CellIdx) -> Option<&T> {
+        self.inner.cell(id)
+    }
+
+    fn cell_id(&self, p: Vec2) -> CellIdx {
+        self.inner.cell_id(p)
+    }
+
+    fn cell_aabb(&self, id: CellIdx) -> AABB {
+        self.inner.cell_aabb(id)
+    }
+
+    fn cells_mut_for(&mut self, bbox: AABB) -> (CellIdx, CellIdx) {
+        if bbox.w().max(bbox.h()) <= self.cell_size {
+            let id = self.inner.cell_mut(bbox.center()).0;
+            return (id, id);
+        }
+        self.inner.cells_mut_for(bbox)
+    }
+
+    fn looseness(&self) -> f32 {
+        self.cell_size * 0.5
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T, ST: ParStorage<T>> ParStorage<T> for LooseStorage<ST> {
+    fn par_modify(&mut self, f: impl Fn(&mut T) -> bool + Sync + Send) {
+        self.inner.par_modify(f)
+    }
+}
+
 pub struct XYRange {
     x1: i32,
     x2: i32,
@@ -306,3 +519,51 @@ impl Iterator for XYRange {
         Some(v)
     }
 }
+
+/// Cell traversal along a ray (Amanatides & Woo), see [`cell_ray`].
+pub struct RayCells {
+    cur: Option<CellIdx>,
+    /// Distance along the ray at which it enters cur
+    dist: f32,
+    step_x: i32,
+    step_y: i32,
+    t_max_x: f32,
+    t_max_y: f32,
+    t_delta_x: f32,
+    t_delta_y: f32,
+    max_dist: f32,
+}
+
+impl RayCells {
+    /// Distance along the ray at which it enters the cell that will be returned next
+    pub fn next_dist(&self) -> Option<f32> {
+        self.cur.map(|_| self.dist)
+    }
+}
+
+impl Iterator for RayCells {
+    type Item = CellIdx;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let v = self.cur?;
+        let (x, y) = v;
+
+        self.cur = if self.t_max_x < self.t_max_y {
+            if self.t_max_x > self.max_dist {
+                None
+            } else {
+                self.dist = self.t_max_x;
+                self.t_max_x += self.t_delta_x;
+                Some((x + self.step_x, y))
+            }
+        } else if self.t_max_y > self.max_dist {
+            None
+        } else {
+            self.dist = self.t_max_y;
+            self.t_max_y += self.t_delta_y;
+            Some((x, y + self.step_y))
+        };
+
+        Some(v)
+    }
+}
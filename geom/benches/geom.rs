@@ -0,0 +1,93 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use geom::skeleton::{faces_from_skeleton, skeleton};
+use geom::{vec2, PolyLine, Vec2};
+
+/// A plus shaped building, the kind of footprint procgen gives to the skeleton
+fn cross() -> Vec<Vec2> {
+    vec![
+        vec2(10.0, 0.0),
+        vec2(20.0, 0.0),
+        vec2(20.0, 10.0),
+        vec2(30.0, 10.0),
+        vec2(30.0, 20.0),
+        vec2(20.0, 20.0),
+        vec2(20.0, 30.0),
+        vec2(10.0, 30.0),
+        vec2(10.0, 20.0),
+        vec2(0.0, 20.0),
+        vec2(0.0, 10.0),
+        vec2(10.0, 10.0),
+    ]
+}
+
+/// A star with n branches, which generates a lot of split events
+fn star(n: usize, r: f32) -> Vec<Vec2> {
+    (0..2 * n)
+        .map(|i| {
+            let angle = i as f32 * std::f32::consts::PI / n as f32;
+            let r = if i % 2 == 0 { r } else { r * 0.6 };
+            vec2(angle.cos(), angle.sin()) * r
+        })
+        .collect()
+}
+
+/// A wavy road of n points, with about 5m between points
+fn wavy(n: usize) -> PolyLine {
+    PolyLine::new(
+        (0..n)
+            .map(|i| {
+                let x = i as f32 * 5.0;
+                vec2(x, (x * 0.01).sin() * 50.0)
+            })
+            .collect(),
+    )
+}
+
+fn bench_skeleton(c: &mut Criterion) {
+    let mut group = c.benchmark_group("skeleton");
+
+    let cross = cross();
+    group.bench_function("cross", |b| b.iter(|| skeleton(black_box(&cross), &[])));
+
+    for &n in &[8, 32, 128] {
+        let poly = star(n, 100.0);
+        group.bench_with_input(BenchmarkId::new("star", n), &poly, |b, poly| {
+            b.iter(|| skeleton(black_box(poly), &[]))
+        });
+    }
+
+    let outer = star(16, 100.0);
+    let hole: Vec<Vec2> = star(8, 30.0).into_iter().rev().collect();
+    group.bench_function("star with hole", |b| {
+        b.iter(|| skeleton(black_box(&outer), &[&hole]))
+    });
+
+    let skel = skeleton(&cross, &[]).unwrap();
+    group.bench_function("cross faces", |b| {
+        b.iter(|| faces_from_skeleton(black_box(&cross), &[], &skel, true))
+    });
+
+    group.finish();
+}
+
+fn bench_project_segment(c: &mut Criterion) {
+    let mut group = c.benchmark_group("polyline project_segment");
+    for &n in &[10, 100, 1000] {
+        let p = wavy(n);
+        let length = n as f32 * 5.0;
+        let points: Vec<Vec2> = (0..100)
+            .map(|i| vec2(i as f32 * length / 100.0, (i % 7) as f32 * 20.0 - 60.0))
+            .collect();
+        group.bench_with_input(BenchmarkId::from_parameter(n), &p, |b, p| {
+            b.iter(|| {
+                for &pos in &points {
+                    black_box(p.project_segment(pos));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_skeleton, bench_project_segment);
+criterion_main!(benches);
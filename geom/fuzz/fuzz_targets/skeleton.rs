@@ -0,0 +1,32 @@
+#![no_main]
+use geom::skeleton::{faces_from_skeleton, skeleton};
+use geom::{vec2, Vec2};
+use libfuzzer_sys::fuzz_target;
+
+/// Coordinates on a 10cm grid up to 3km away, like building footprints
+fn to_poly(points: &[(i16, i16)]) -> Vec<Vec2> {
+    points
+        .iter()
+        .map(|&(x, y)| vec2(x as f32, y as f32) * 0.1)
+        .collect()
+}
+
+fuzz_target!(|data: (Vec<(i16, i16)>, Vec<(i16, i16)>)| {
+    let poly = to_poly(&data.0);
+    let hole = to_poly(&data.1);
+    let holes: &[&[Vec2]] = if hole.is_empty() { &[] } else { &[&hole] };
+
+    // Invalid polygons are fine as long as they give an error instead of panicking
+    let skel = match skeleton(&poly, holes) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+
+    for tree in &skel {
+        assert!(tree.source.is_finite());
+        assert!(tree.height.is_finite() && tree.height >= 0.0);
+    }
+
+    let _ = faces_from_skeleton(&poly, holes, &skel, false);
+    let _ = faces_from_skeleton(&poly, holes, &skel, true);
+});
@@ -64,6 +64,13 @@ impl AABB {
         p.x >= self.ll.x && p.y >= self.ll.y && p.x <= self.ur.x && p.y <= self.ur.y
     }
 
+    /// Distance from the point to the `AABB`, zero if the point is inside
+    pub fn distance(&self, p: Vec2) -> f32 {
+        let dx = (self.ll.x - p.x).max(p.x - self.ur.x).max(0.0);
+        let dy = (self.ll.y - p.y).max(p.y - self.ur.y).max(0.0);
+        dx.hypot(dy)
+    }
+
     /// Checks whether the `AABB` contains a `Point`
     pub fn contains_within(&self, point: Vec2, tolerance: f32) -> bool {
         point.x >= self.ll.x - tolerance
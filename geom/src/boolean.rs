@@ -0,0 +1,590 @@
+use crate::{Polygon, Segment, Vec2, AABB};
+use ordered_float::OrderedFloat;
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+
+const EPSILON: f32 = 0.00001;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Op {
+    Union,
+    Intersection,
+    Difference,
+}
+
+/// Boolean operations and offsetting.
+///
+/// The results are lists of contours, outer contours are counter-clockwise and holes are
+/// clockwise. The input polygons can have any orientation but must not cross themselves.
+impl Polygon {
+    /// Positive when the points are counter-clockwise
+    pub fn signed_area(&self) -> f32 {
+        signed_area(&self.0)
+    }
+
+    pub fn area(&self) -> f32 {
+        self.signed_area().abs()
+    }
+
+    /// Parts covered by either polygon
+    pub fn union(&self, other: &Polygon) -> Vec<Polygon> {
+        boolean(&[ccw(&self.0)], &[ccw(&other.0)], Op::Union)
+    }
+
+    /// Parts covered by both polygons
+    pub fn intersection(&self, other: &Polygon) -> Vec<Polygon> {
+        boolean(&[ccw(&self.0)], &[ccw(&other.0)], Op::Intersection)
+    }
+
+    /// Parts covered by self but not by other
+    pub fn difference(&self, other: &Polygon) -> Vec<Polygon> {
+        boolean(&[ccw(&self.0)], &[ccw(&other.0)], Op::Difference)
+    }
+
+    /// Grows the polygon by dist, or shrinks it when dist is negative.
+    /// Corners are mitered, the miters longer than twice dist are squared off at dist from the
+    /// corner.
+    pub fn offset(&self, dist: f32) -> Vec<Polygon> {
+        let poly = ccw(&self.0);
+        if poly.len() < 3 {
+            return vec![];
+        }
+        if dist == 0.0 {
+            return vec![Polygon(poly)];
+        }
+
+        let d = dist.abs();
+        let n = poly.len();
+        let normal = |i: usize| {
+            let v = (poly[(i + 1) % n] - poly[i]).perpendicular();
+            v.try_normalize().map(|x| x * dist.signum())
+        };
+
+        // the offset is the polygon with the band along its contour added or removed,
+        // the band being made of one rectangle per edge and one joint per corner
+        let mut band = vec![];
+        for i in 0..n {
+            let src = poly[i];
+            let dst = poly[(i + 1) % n];
+            let nrm = match normal(i) {
+                Some(x) => x,
+                None => continue,
+            };
+            band.push(vec![src, dst, dst + nrm * d, src + nrm * d]);
+
+            let next_nrm = match normal((i + 1) % n) {
+                Some(x) => x,
+                None => continue,
+            };
+            // only corners pointing toward the band leave a gap between the rectangles
+            if (dst - src).cross(poly[(i + 2) % n] - dst) * dist.signum() <= 0.0 {
+                continue;
+            }
+            let s = nrm + next_nrm;
+            if s.magnitude2() >= 1.0 {
+                let miter = dst + s * (2.0 * d / s.magnitude2());
+                band.push(vec![dst, dst + nrm * d, miter, dst + next_nrm * d]);
+            } else {
+                // cut the miter where it gets further than dist from the corner
+                let b = s.try_normalize().unwrap_or(nrm);
+                let dir_in = (dst - src).normalize();
+                let dir_out = (poly[(i + 2) % n] - dst).normalize();
+                let t = d * (1.0 - nrm.dot(b)) / dir_in.dot(b).max(EPSILON);
+                band.push(vec![
+                    dst,
+                    dst + nrm * d,
+                    dst + nrm * d + dir_in * t,
+                    dst + next_nrm * d - dir_out * t,
+                    dst + next_nrm * d,
+                ]);
+            }
+        }
+
+        let op = if dist > 0.0 {
+            Op::Union
+        } else {
+            Op::Difference
+        };
+        band.into_iter().fold(vec![Polygon(poly)], |acc, piece| {
+            let acc: Vec<_> = acc.into_iter().map(|p| p.0).collect();
+            boolean(&acc, &[ccw(&piece)], op)
+        })
+    }
+}
+
+fn signed_area(p: &[Vec2]) -> f32 {
+    // relative to the first point, so big coordinates don't cancel out
+    let o = match p.first() {
+        Some(&x) => x,
+        None => return 0.0,
+    };
+    let n = p.len();
+    (0..n)
+        .map(|i| (p[i] - o).cross(p[(i + 1) % n] - o))
+        .sum::<f32>()
+        * 0.5
+}
+
+fn ccw(p: &[Vec2]) -> Vec<Vec2> {
+    let mut v = p.to_vec();
+    v.dedup();
+    if v.len() > 1 && v.first() == v.last() {
+        v.pop();
+    }
+    if signed_area(&v) < 0.0 {
+        v.reverse();
+    }
+    v
+}
+
+/// Merges points closer than `eps`, so the edges of both sets can be compared by index
+struct Snapper {
+    eps: f32,
+    grid: HashMap<(i32, i32), Vec<usize>>,
+    points: Vec<Vec2>,
+}
+
+impl Snapper {
+    fn cell(&self, p: Vec2) -> (i32, i32) {
+        (
+            (p.x / self.eps).floor() as i32,
+            (p.y / self.eps).floor() as i32,
+        )
+    }
+
+    fn snap(&mut self, p: Vec2) -> usize {
+        let (x, y) = self.cell(p);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for &i in self.grid.get(&(x + dx, y + dy)).into_iter().flatten() {
+                    if self.points[i].distance(p) <= self.eps {
+                        return i;
+                    }
+                }
+            }
+        }
+        self.points.push(p);
+        self.grid
+            .entry((x, y))
+            .or_default()
+            .push(self.points.len() - 1);
+        self.points.len() - 1
+    }
+}
+
+fn edges(contours: &[Polygon]) -> Vec<Segment> {
+    contours.iter().flat_map(|c| c.segments()).collect()
+}
+
+/// Points along each edge where the other set touches or crosses it, from src to dst
+fn split(edges: &[Segment], others: &[Segment], eps: f32) -> Vec<Vec<Vec2>> {
+    edges
+        .iter()
+        .map(|e| {
+            let r = e.vec();
+            let len2 = r.magnitude2();
+            let mut ts = vec![];
+            if len2 > eps * eps {
+                for f in others {
+                    for &p in &[f.src, f.dst] {
+                        let t = (p - e.src).dot(r) / len2;
+                        if t > 0.0 && t < 1.0 && e.project(p).distance(p) <= eps {
+                            ts.push(t);
+                        }
+                    }
+
+                    // crossings are only taken when both edges are clearly on both sides of each
+                    // other, the ones close to an endpoint are found above
+                    let s = f.vec();
+                    let slen = s.magnitude();
+                    if slen <= eps {
+                        continue;
+                    }
+                    let side_e = |p: Vec2| r.cross(p - e.src) / len2.sqrt();
+                    let side_f = |p: Vec2| s.cross(p - f.src) / slen;
+                    let (d1, d2) = (side_e(f.src), side_e(f.dst));
+                    let (c1, c2) = (side_f(e.src), side_f(e.dst));
+                    if d1.abs() > eps
+                        && d2.abs() > eps
+                        && c1.abs() > eps
+                        && c2.abs() > eps
+                        && (d1 > 0.0) != (d2 > 0.0)
+                        && (c1 > 0.0) != (c2 > 0.0)
+                    {
+                        ts.push(c1 / (c1 - c2));
+                    }
+                }
+            }
+            ts.sort_by_key(|&t| OrderedFloat(t));
+
+            let mut pts = vec![e.src];
+            pts.extend(ts.into_iter().map(|t| e.src + r * t));
+            pts.push(e.dst);
+            pts
+        })
+        .collect()
+}
+
+fn inside(contours: &[Polygon], p: Vec2) -> bool {
+    contours.iter().filter(|c| c.contains(p)).count() % 2 == 1
+}
+
+/// Removes the points lying on the segment between their neighbours,
+/// returns None if nothing is left
+fn clean(mut contour: Vec<Vec2>, eps: f32) -> Option<Vec<Vec2>> {
+    let mut i = 0;
+    while contour.len() >= 3 && i < contour.len() {
+        let n = contour.len();
+        let prev = contour[(i + n - 1) % n];
+        let next = contour[(i + 1) % n];
+        let cur = contour[i];
+        if Segment::new(prev, next).project(cur).distance(cur) <= eps {
+            contour.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+
+    let n = contour.len();
+    let perimeter: f32 = (0..n)
+        .map(|i| contour[i].distance(contour[(i + 1) % n]))
+        .sum();
+    if n < 3 || signed_area(&contour).abs() <= eps * perimeter {
+        return None;
+    }
+    Some(contour)
+}
+
+/// Both sets are lists of oriented contours, a point is in a set if it is inside an odd number
+/// of its contours
+fn boolean(a: &[Vec<Vec2>], b: &[Vec<Vec2>], op: Op) -> Vec<Polygon> {
+    let all: Vec<_> = a.iter().chain(b).flatten().copied().collect();
+    let bbox = match crate::minmax(&all) {
+        Some((min, max)) => AABB::new(min, max),
+        None => return vec![],
+    };
+
+    // working around the origin keeps as much precision as possible
+    let origin = bbox.center();
+    let eps = (bbox.w().max(bbox.h()) * 1e-5).max(1e-6);
+    let centered = |set: &[Vec<Vec2>]| -> Vec<Polygon> {
+        set.iter()
+            .filter(|c| c.len() >= 3)
+            .map(|c| Polygon(c.iter().map(|&p| p - origin).collect()))
+            .collect()
+    };
+    let (a, b) = (centered(a), centered(b));
+    let (edges_a, edges_b) = (edges(&a), edges(&b));
+
+    let mut snapper = Snapper {
+        eps,
+        grid: HashMap::new(),
+        points: vec![],
+    };
+    let mut sub_edges = |edges: &[Segment], others: &[Segment]| -> Vec<(usize, usize)> {
+        let mut v = vec![];
+        for pts in split(edges, others, eps) {
+            let ids: Vec<_> = pts.into_iter().map(|p| snapper.snap(p)).collect();
+            v.extend(ids.windows(2).map(|w| (w[0], w[1])).filter(|(i, j)| i != j));
+        }
+        v
+    };
+    let sub_a = sub_edges(&edges_a, &edges_b);
+    let sub_b = sub_edges(&edges_b, &edges_a);
+    let pts = snapper.points;
+
+    let set_a: HashSet<_> = sub_a.iter().copied().collect();
+    let set_b: HashSet<_> = sub_b.iter().copied().collect();
+    let mid = |(i, j): (usize, usize)| (pts[i] + pts[j]) * 0.5;
+
+    // edges shared by both sets are kept once, from a
+    let mut kept = vec![];
+    for &(i, j) in &sub_a {
+        let keep = if set_b.contains(&(i, j)) {
+            op != Op::Difference
+        } else if set_b.contains(&(j, i)) {
+            op == Op::Difference
+        } else {
+            inside(&b, mid((i, j))) == (op == Op::Intersection)
+        };
+        if keep {
+            kept.push((i, j));
+        }
+    }
+    for &(i, j) in &sub_b {
+        if set_a.contains(&(i, j)) || set_a.contains(&(j, i)) {
+            continue;
+        }
+        match (op, inside(&a, mid((i, j)))) {
+            (Op::Union, false) | (Op::Intersection, true) => kept.push((i, j)),
+            (Op::Difference, true) => kept.push((j, i)),
+            _ => {}
+        }
+    }
+    kept.sort_unstable();
+    kept.dedup();
+
+    let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (k, &(i, _)) in kept.iter().enumerate() {
+        outgoing.entry(i).or_default().push(k);
+    }
+
+    // walk the kept edges into loops, when several edges leave a point the one turning the most
+    // to the left is taken so the loops touching at a point stay separate
+    let mut used = vec![false; kept.len()];
+    let mut result = vec![];
+    for start in 0..kept.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let (first, mut cur) = kept[start];
+        let mut dir = pts[cur] - pts[first];
+        let mut contour = vec![pts[first]];
+
+        let mut closed = false;
+        while contour.len() <= kept.len() {
+            if cur == first {
+                closed = true;
+                break;
+            }
+            contour.push(pts[cur]);
+
+            let turn = |k: &&usize| {
+                let ang = dir.angle(pts[kept[**k].1] - pts[cur]);
+                // going back is the last resort
+                OrderedFloat(if ang >= PI - 1e-6 { -PI } else { ang })
+            };
+            let next = outgoing
+                .get(&cur)
+                .and_then(|v| v.iter().filter(|&&k| !used[k]).max_by_key(turn));
+            let k = match next {
+                Some(&k) => k,
+                None => break,
+            };
+            used[k] = true;
+            dir = pts[kept[k].1] - pts[cur];
+            cur = kept[k].1;
+        }
+
+        if !closed {
+            continue;
+        }
+        if let Some(c) = clean(contour, eps) {
+            result.push(Polygon(c.into_iter().map(|p| p + origin).collect()));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{star, XorShift};
+    use crate::{vec2, Polygon, Vec2};
+
+    fn square(x: f32, y: f32, size: f32) -> Polygon {
+        let mut p = Polygon::rect(size, size);
+        p.translate(vec2(x, y));
+        p
+    }
+
+    fn total_area(polys: &[Polygon]) -> f32 {
+        polys.iter().map(Polygon::signed_area).sum()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let a = square(0.0, 0.0, 2.0);
+        let b = square(1.0, 1.0, 2.0);
+
+        let u = a.union(&b);
+        assert_eq!(u.len(), 1);
+        assert_eq!(u[0].len(), 8);
+        assert_close(total_area(&u), 7.0);
+
+        let i = a.intersection(&b);
+        assert_eq!(i.len(), 1);
+        assert_close(total_area(&i), 1.0);
+
+        let d = a.difference(&b);
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].len(), 6);
+        assert_close(total_area(&d), 3.0);
+    }
+
+    #[test]
+    fn test_disjoint() {
+        let a = square(0.0, 0.0, 1.0);
+        let b = square(5.0, 0.0, 1.0);
+
+        assert_eq!(a.union(&b).len(), 2);
+        assert!(a.intersection(&b).is_empty());
+        assert_close(total_area(&a.difference(&b)), 1.0);
+    }
+
+    #[test]
+    fn test_hole() {
+        let a = square(0.0, 0.0, 4.0);
+        let b = square(1.0, 1.0, 2.0);
+
+        let d = a.difference(&b);
+        assert_eq!(d.len(), 2);
+        assert_eq!(d.iter().filter(|p| p.signed_area() < 0.0).count(), 1);
+        assert_close(total_area(&d), 12.0);
+
+        assert_close(total_area(&a.union(&b)), 16.0);
+        assert_close(total_area(&a.intersection(&b)), 4.0);
+        assert!(b.difference(&a).is_empty());
+    }
+
+    #[test]
+    fn test_shared_edges() {
+        let a = square(0.0, 0.0, 1.0);
+        let b = square(1.0, 0.0, 1.0);
+
+        let u = a.union(&b);
+        assert_eq!(u.len(), 1);
+        assert_eq!(u[0].len(), 4);
+        assert_close(total_area(&u), 2.0);
+
+        assert!(a.intersection(&b).is_empty());
+        assert_close(total_area(&a.difference(&b)), 1.0);
+
+        let u = a.union(&a);
+        assert_eq!(u.len(), 1);
+        assert_close(total_area(&u), 1.0);
+        assert_close(total_area(&a.intersection(&a)), 1.0);
+        assert!(a.difference(&a).is_empty());
+    }
+
+    #[test]
+    fn test_touching_corner() {
+        let a = square(0.0, 0.0, 1.0);
+        let b = square(1.0, 1.0, 1.0);
+
+        let u = a.union(&b);
+        assert_eq!(u.len(), 2);
+        assert_close(total_area(&u), 2.0);
+        assert!(a.intersection(&b).is_empty());
+    }
+
+    #[test]
+    fn test_orientation() {
+        let a = square(0.0, 0.0, 2.0);
+        let b = Polygon(square(1.0, 1.0, 2.0).0.into_iter().rev().collect());
+
+        assert_close(total_area(&a.union(&b)), 7.0);
+        assert_close(total_area(&b.difference(&a)), 3.0);
+    }
+
+    #[test]
+    fn test_far_from_origin() {
+        let a = square(10000.0, -5000.0, 2.0);
+        let b = square(10001.0, -4999.0, 2.0);
+
+        assert_eq!(a.union(&b).len(), 1);
+        assert_close(total_area(&a.intersection(&b)), 1.0);
+    }
+
+    #[test]
+    fn test_offset() {
+        let a = square(0.0, 0.0, 2.0);
+
+        let grown = a.offset(1.0);
+        assert_eq!(grown.len(), 1);
+        assert_eq!(grown[0].len(), 4);
+        assert_close(total_area(&grown), 16.0);
+
+        let shrunk = a.offset(-0.5);
+        assert_eq!(shrunk.len(), 1);
+        assert_close(total_area(&shrunk), 1.0);
+
+        assert!(a.offset(-1.5).is_empty());
+    }
+
+    #[test]
+    fn test_offset_concave() {
+        // L shape, the inner corner gets filled when growing
+        let l = Polygon(vec![
+            Vec2::ZERO,
+            vec2(2.0, 0.0),
+            vec2(2.0, 1.0),
+            vec2(1.0, 1.0),
+            vec2(1.0, 2.0),
+            vec2(0.0, 2.0),
+        ]);
+
+        let grown = l.offset(0.5);
+        assert_eq!(grown.len(), 1);
+        assert_eq!(grown[0].len(), 6);
+        assert_close(total_area(&grown), 3.0 * 2.0 * 2.0 - 2.0 * 2.0);
+
+        // the arms are 1 wide so only a thin L stays
+        let shrunk = l.offset(-0.25);
+        assert_eq!(shrunk.len(), 1);
+        assert_close(total_area(&shrunk), 1.5 * 0.5 * 2.0 - 0.25);
+
+        assert!(l.offset(-0.6).is_empty());
+    }
+
+    #[test]
+    fn test_random_stars() {
+        let mut rng = XorShift(0x2545_f491);
+        for _ in 0..500 {
+            let n = 3 + (rng.next() * 20.0) as usize;
+            let m = 3 + (rng.next() * 20.0) as usize;
+            let a = Polygon(star(&mut rng, Vec2::ZERO, n, 2.0..10.0));
+            let off = vec2(rng.next() * 10.0 - 5.0, rng.next() * 10.0 - 5.0);
+            let b = Polygon(star(&mut rng, off, m, 2.0..10.0));
+
+            let (area_a, area_b) = (a.area(), b.area());
+            let union = total_area(&a.union(&b));
+            let inter = total_area(&a.intersection(&b));
+            let diff = total_area(&a.difference(&b));
+
+            let tol = 1e-3 * (area_a + area_b);
+            assert!(
+                (union + inter - area_a - area_b).abs() < tol,
+                "{:?} {:?}",
+                a,
+                b
+            );
+            assert!((diff + inter - area_a).abs() < tol, "{:?} {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_random_offsets() {
+        let mut rng = XorShift(0x9e37_79b9);
+        for _ in 0..200 {
+            let n = 3 + (rng.next() * 20.0) as usize;
+            let a = Polygon(star(&mut rng, Vec2::ZERO, n, 2.0..10.0));
+
+            let grown = a.offset(0.5);
+            let shrunk = a.offset(-0.5);
+
+            // nothing is closer than the offset to the contour
+            let far = |polys: &[Polygon]| {
+                polys
+                    .iter()
+                    .flat_map(|p| p.iter())
+                    .all(|&p| a.project(p).distance(p) >= 0.4995)
+            };
+            assert_eq!(grown.len(), 1, "{:?}", a);
+            assert!(a.iter().all(|&p| grown[0].contains(p)), "{:?}", a);
+            assert!(far(&grown), "{:?}", a);
+            assert!(far(&shrunk), "{:?}", a);
+            assert!(
+                shrunk.iter().flat_map(|p| p.iter()).all(|&p| a.contains(p)),
+                "{:?}",
+                a
+            );
+            assert!(total_area(&shrunk) < a.area() && a.area() < total_area(&grown));
+        }
+    }
+}
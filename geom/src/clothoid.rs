@@ -0,0 +1,359 @@
+use crate::{PolyLine, Vec2};
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Nodes and weights of the 8 points Gauss-Legendre quadrature on [-1; 1]
+const GAUSS_LEGENDRE: [(f64, f64); 8] = [
+    (-0.960_289_856_497_536_3, 0.101_228_536_290_376_3),
+    (-0.796_666_477_413_626_7, 0.222_381_034_453_374_5),
+    (-0.525_532_409_916_329, 0.313_706_645_877_887_3),
+    (-0.183_434_642_495_649_8, 0.362_683_783_378_362),
+    (0.183_434_642_495_649_8, 0.362_683_783_378_362),
+    (0.525_532_409_916_329, 0.313_706_645_877_887_3),
+    (0.796_666_477_413_626_7, 0.222_381_034_453_374_5),
+    (0.960_289_856_497_536_3, 0.101_228_536_290_376_3),
+];
+
+/// A curve whose curvature changes linearly with the distance along it, also called Euler spiral.
+/// Driving along it means turning the wheel at a constant rate, which is why it is used to go
+/// from a straight road to a turn.
+///
+/// Unlike splines it is parameterized by the distance along it.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Clothoid {
+    pub from: Vec2,
+    /// Unit direction at the start
+    pub dir: Vec2,
+    /// Curvature at the start, positive when turning left
+    pub curvature: f32,
+    /// Change of curvature per unit of length
+    pub sharpness: f32,
+    pub length: f32,
+}
+
+/// Number of pieces for the quadrature of a phase of (a/2 t² + b t + c), each piece turns at
+/// most by an eighth of a turn where the quadrature is very precise
+fn n_pieces(a: f64, b: f64) -> usize {
+    let turn = a.abs() * 0.5 + b.abs();
+    ((turn / (PI * 0.25)).ceil() as usize).clamp(1, 256)
+}
+
+/// Integrals of cos and sin of (a/2 t² + b t + c) for t in [0; 1]
+fn fresnel(a: f64, b: f64, c: f64) -> (f64, f64) {
+    let pieces = n_pieces(a, b);
+    let h = 1.0 / pieces as f64;
+
+    let (mut x, mut y) = (0.0, 0.0);
+    for i in 0..pieces {
+        let mid = h * (i as f64 + 0.5);
+        for &(node, w) in &GAUSS_LEGENDRE {
+            let t = mid + h * 0.5 * node;
+            let phase = a * 0.5 * t * t + b * t + c;
+            x += w * phase.cos();
+            y += w * phase.sin();
+        }
+    }
+    (x * h * 0.5, y * h * 0.5)
+}
+
+/// Integral of (t² - t) cos(a/2 t² + b t + c) for t in [0; 1], derivative of the sin integral
+/// when a/2 grows and b shrinks by the same amount
+fn fresnel_derivative(a: f64, b: f64, c: f64) -> f64 {
+    let pieces = n_pieces(a, b);
+    let h = 1.0 / pieces as f64;
+
+    let mut v = 0.0;
+    for i in 0..pieces {
+        let mid = h * (i as f64 + 0.5);
+        for &(node, w) in &GAUSS_LEGENDRE {
+            let t = mid + h * 0.5 * node;
+            v += w * (t * t - t) * (a * 0.5 * t * t + b * t + c).cos();
+        }
+    }
+    v * h * 0.5
+}
+
+/// Angle in ]-pi; pi]
+fn normalize_angle(mut x: f64) -> f64 {
+    while x > PI {
+        x -= 2.0 * PI;
+    }
+    while x <= -PI {
+        x += 2.0 * PI;
+    }
+    x
+}
+
+impl Clothoid {
+    /// Finds the clothoid going from `from` in the direction `from_dir` to `to` arriving in the
+    /// direction `to_dir`. There is always one but it is not found for some degenerate cases,
+    /// like points too close to each other.
+    ///
+    /// see "G1 fitting with clothoids" by Bertolazzi and Frego
+    pub fn fit(from: Vec2, from_dir: Vec2, to: Vec2, to_dir: Vec2) -> Option<Clothoid> {
+        let from_dir = from_dir.try_normalize()?;
+        let to_dir = to_dir.try_normalize()?;
+        let diff = to - from;
+        let r = diff.magnitude() as f64;
+        if r <= 1e-4 || !r.is_finite() {
+            return None;
+        }
+
+        let angle = |v: Vec2| (v.y as f64).atan2(v.x as f64);
+        let phi = angle(diff);
+        let phi0 = normalize_angle(angle(from_dir) - phi);
+        let phi1 = normalize_angle(angle(to_dir) - phi);
+        let delta = phi1 - phi0;
+
+        // the end is reached when the sin integral is zero, solved with newton's method
+        let mut a = 3.0 * (phi0 + phi1);
+        let mut converged = false;
+        for _ in 0..50 {
+            let (_, g) = fresnel(2.0 * a, delta - a, phi0);
+            if g.abs() <= 1e-10 {
+                converged = true;
+                break;
+            }
+            let dg = fresnel_derivative(2.0 * a, delta - a, phi0);
+            if dg.abs() <= 1e-12 {
+                break;
+            }
+            a -= g / dg;
+        }
+        let (x, y) = fresnel(2.0 * a, delta - a, phi0);
+        if !converged && y.abs() > 1e-6 {
+            return None;
+        }
+        if x <= 1e-6 {
+            return None;
+        }
+
+        let length = r / x;
+        let c = Clothoid {
+            from,
+            dir: from_dir,
+            curvature: ((delta - a) / length) as f32,
+            sharpness: (2.0 * a / (length * length)) as f32,
+            length: length as f32,
+        };
+        if !(c.curvature.is_finite() && c.sharpness.is_finite() && c.length.is_finite()) {
+            return None;
+        }
+        Some(c)
+    }
+
+    /// Rotation of the direction at distance s compared to the start
+    pub fn angle_at(&self, s: f32) -> f32 {
+        self.curvature * s + self.sharpness * s * s * 0.5
+    }
+
+    pub fn dir_at(&self, s: f32) -> Vec2 {
+        self.dir.rotated_by(Vec2::from_angle(self.angle_at(s)))
+    }
+
+    pub fn curvature_at(&self, s: f32) -> f32 {
+        self.curvature + self.sharpness * s
+    }
+
+    /// Highest absolute curvature, which is at one of the ends since it changes linearly
+    pub fn max_curvature(&self) -> f32 {
+        self.curvature
+            .abs()
+            .max(self.curvature_at(self.length).abs())
+    }
+
+    /// Whether the curves offset by thickness on each side would fold on themselves
+    pub fn is_steep(&self, thickness: f32) -> bool {
+        self.max_curvature() * thickness >= 1.0
+    }
+
+    /// Point at distance s along the curve
+    pub fn get(&self, s: f32) -> Vec2 {
+        let s64 = s as f64;
+        let (x, y) = fresnel(
+            self.sharpness as f64 * s64 * s64,
+            self.curvature as f64 * s64,
+            0.0,
+        );
+        let local = Vec2::new((x * s64) as f32, (y * s64) as f32);
+        self.from + local.rotated_by(self.dir)
+    }
+
+    pub fn end(&self) -> Vec2 {
+        self.get(self.length)
+    }
+
+    pub fn end_dir(&self) -> Vec2 {
+        self.dir_at(self.length)
+    }
+
+    /// The part of the curve between the distances start and end
+    pub fn sub(&self, start: f32, end: f32) -> Clothoid {
+        Clothoid {
+            from: self.get(start),
+            dir: self.dir_at(start),
+            curvature: self.curvature_at(start),
+            sharpness: self.sharpness,
+            length: end - start,
+        }
+    }
+
+    pub fn split_at(&self, s: f32) -> (Clothoid, Clothoid) {
+        (self.sub(0.0, s), self.sub(s, self.length))
+    }
+
+    /// Distance along the curve of the point closest to p
+    pub fn project(&self, p: Vec2) -> f32 {
+        let points = self.flatten(0.1);
+        let (mut best_s, mut best_d) = (0.0, f32::INFINITY);
+        let mut s = 0.0;
+        let mut prev = points.first();
+        for &cur in points.iter() {
+            let step = prev.distance(cur);
+            let proj = crate::Segment::new(prev, cur).project(p);
+            let d = proj.distance2(p);
+            if d < best_d {
+                best_d = d;
+                best_s = s + proj.distance(prev);
+            }
+            s += step;
+            prev = cur;
+        }
+
+        // the polyline is a bit shorter than the curve, refine around the guess
+        let window = (self.length / points.n_points() as f32).max(0.01);
+        let dist = |s: f32| OrderedFloat(self.get(s).distance2(p));
+        let (mut lo, mut hi) = (
+            (best_s - window).max(0.0),
+            (best_s + window).min(self.length),
+        );
+        for _ in 0..30 {
+            let m1 = lo + (hi - lo) / 3.0;
+            let m2 = hi - (hi - lo) / 3.0;
+            if dist(m1) < dist(m2) {
+                hi = m2;
+            } else {
+                lo = m1;
+            }
+        }
+        (lo + hi) * 0.5
+    }
+
+    /// Approximates the curve by a polyline, every point of the curve is within tolerance of it
+    pub fn flatten(&self, tolerance: f32) -> PolyLine {
+        let mut points = PolyLine::new(vec![self.from]);
+        let tolerance = tolerance.max(1e-4);
+
+        // an arc of curvature k and length l is at most k l² / 8 away from its chord,
+        // the curvature of the step is the highest of its ends
+        let step = |k: f32| (8.0 * tolerance / k.abs().max(1e-6)).sqrt();
+        let mut s = 0.0;
+        loop {
+            let mut ds = step(self.curvature_at(s));
+            ds = ds.min(step(self.curvature_at(s + ds)));
+            s = (s + ds).min(self.length);
+            points.push(self.get(s));
+            if s >= self.length {
+                return points;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{vec2, Clothoid, Vec2};
+
+    fn check_fit(from: Vec2, from_dir: Vec2, to: Vec2, to_dir: Vec2) -> Clothoid {
+        let c = Clothoid::fit(from, from_dir, to, to_dir).unwrap();
+        let size = from.distance(to);
+        assert!(c.end().distance(to) < size * 1e-3, "{:?} {:?}", c, c.end());
+        assert!(
+            c.end_dir().distance(to_dir.normalize()) < 1e-3,
+            "{:?} {:?}",
+            c,
+            c.end_dir()
+        );
+        c
+    }
+
+    #[test]
+    fn test_straight() {
+        let c = check_fit(Vec2::ZERO, Vec2::UNIT_X, vec2(10.0, 0.0), Vec2::UNIT_X);
+        assert!((c.length - 10.0).abs() < 1e-3);
+        assert!(c.max_curvature() < 1e-4);
+        assert!(!c.is_steep(100.0));
+        assert!(c.get(4.0).distance(vec2(4.0, 0.0)) < 1e-3);
+    }
+
+    #[test]
+    fn test_circle() {
+        // a quarter of a circle of radius 10 has a constant curvature
+        let c = check_fit(
+            vec2(10.0, 0.0),
+            Vec2::UNIT_Y,
+            vec2(0.0, 10.0),
+            -Vec2::UNIT_X,
+        );
+        assert!((c.length - std::f32::consts::FRAC_PI_2 * 10.0).abs() < 1e-2);
+        assert!((c.curvature - 0.1).abs() < 1e-3);
+        assert!(c.sharpness.abs() < 1e-4);
+        assert!(!c.is_steep(9.0));
+        assert!(c.is_steep(11.0));
+        for i in 0..=10 {
+            let p = c.get(c.length * i as f32 / 10.0);
+            assert!((p.magnitude() - 10.0).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_fit() {
+        let dirs = [
+            Vec2::UNIT_X,
+            Vec2::UNIT_Y,
+            vec2(1.0, 1.0),
+            vec2(1.0, -0.5),
+            vec2(-0.2, 1.0),
+            vec2(0.3, -1.0),
+        ];
+        for &from_dir in &dirs {
+            for &to_dir in &dirs {
+                check_fit(vec2(3.0, -2.0), from_dir, vec2(53.0, 18.0), to_dir);
+            }
+        }
+
+        assert!(Clothoid::fit(Vec2::ZERO, Vec2::UNIT_X, Vec2::ZERO, Vec2::UNIT_X).is_none());
+        assert!(Clothoid::fit(Vec2::ZERO, Vec2::ZERO, Vec2::UNIT_X, Vec2::UNIT_X).is_none());
+    }
+
+    #[test]
+    fn test_split_project() {
+        let c = check_fit(Vec2::ZERO, Vec2::UNIT_X, vec2(40.0, 20.0), Vec2::UNIT_Y);
+
+        let (a, b) = c.split_at(12.0);
+        assert!(a.end().distance(b.from) < 1e-3);
+        assert!(a.end_dir().distance(b.dir) < 1e-4);
+        assert!(b.end().distance(c.end()) < 1e-2);
+
+        for i in 0..=10 {
+            let s = c.length * i as f32 / 10.0;
+            assert!((c.project(c.get(s)) - s).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_flatten() {
+        let c = check_fit(Vec2::ZERO, Vec2::UNIT_X, vec2(40.0, 20.0), Vec2::UNIT_Y);
+        let fine = c.flatten(0.01);
+        let coarse = c.flatten(0.5);
+        assert!(coarse.n_points() < fine.n_points());
+        assert!(fine.last().distance(c.end()) < 1e-4);
+
+        for i in 0..=1000 {
+            let p = c.get(c.length * i as f32 / 1000.0);
+            assert!(fine.project_dist(p) <= 0.011);
+            assert!(coarse.project_dist(p) <= 0.51);
+        }
+    }
+}
@@ -2,8 +2,10 @@
 #![allow(clippy::many_single_char_names)]
 
 mod aabb;
+mod boolean;
 mod camera;
 mod circle;
+mod clothoid;
 mod color;
 mod line;
 mod obb;
@@ -20,6 +22,7 @@ mod v3;
 pub use aabb::*;
 pub use camera::*;
 pub use circle::*;
+pub use clothoid::*;
 pub use color::*;
 pub use line::*;
 pub use obb::*;
@@ -115,3 +118,189 @@ pub fn lerp(src: f32, dst: f32, coeff: f32) -> f32 {
     let coeff = coeff.max(0.0).min(1.0);
     src * (1.0 - coeff) + dst * coeff
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::{vec2, Circle, Intersect, Polygon, Segment, Shape, Vec2, AABB, OBB};
+    use proptest::prelude::*;
+    use std::ops::Range;
+
+    /// Points on a 0.25 grid, so that the predicates are computed exactly and
+    /// touching shapes are generated often
+    pub fn grid_vec2() -> impl Strategy<Value = Vec2> {
+        (-128..128, -128..128).prop_map(|(x, y)| vec2(x as f32, y as f32) * 0.25)
+    }
+
+    pub fn any_vec2(range: f32) -> impl Strategy<Value = Vec2> {
+        (-range..range, -range..range).prop_map(|(x, y)| vec2(x, y))
+    }
+
+    pub fn aabb() -> impl Strategy<Value = AABB> {
+        (grid_vec2(), grid_vec2()).prop_map(|(a, b)| AABB::new(a.min(b), a.max(b)))
+    }
+
+    pub fn circle() -> impl Strategy<Value = Circle> {
+        (grid_vec2(), 1..64).prop_map(|(center, r)| Circle::new(center, r as f32 * 0.25))
+    }
+
+    pub fn segment() -> impl Strategy<Value = Segment> {
+        (grid_vec2(), grid_vec2()).prop_map(|(src, dst)| Segment::new(src, dst))
+    }
+
+    pub fn obb() -> impl Strategy<Value = OBB> {
+        (grid_vec2(), 0.0..std::f32::consts::TAU, 1..64, 1..64).prop_map(|(c, angle, w, h)| {
+            OBB::new(c, Vec2::from_angle(angle), w as f32 * 0.25, h as f32 * 0.25)
+        })
+    }
+
+    /// Simple polygons: star shaped around a center, in either orientation
+    pub fn polygon() -> impl Strategy<Value = Polygon> {
+        (
+            any_vec2(30.0),
+            prop::collection::btree_set(0..64u32, 3..12),
+            prop::collection::vec(1.0f32..20.0, 12),
+            any::<bool>(),
+        )
+            .prop_map(|(center, angles, radii, reverse)| {
+                let mut points: Vec<Vec2> = angles
+                    .into_iter()
+                    .zip(radii)
+                    .map(|(a, r)| {
+                        let angle = a as f32 * std::f32::consts::TAU / 64.0;
+                        center + Vec2::from_angle(angle) * r
+                    })
+                    .collect();
+                if reverse {
+                    points.reverse();
+                }
+                Polygon(points)
+            })
+    }
+
+    /// Small deterministic generator, so the random corpora are the same on every run
+    pub struct XorShift(pub u32);
+
+    impl XorShift {
+        pub fn next(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0 as f32 / u32::MAX as f32
+        }
+    }
+
+    /// Star shaped polygon around center with n vertices, simple by construction
+    pub fn star(rng: &mut XorShift, center: Vec2, n: usize, radius: Range<f32>) -> Vec<Vec2> {
+        (0..n)
+            .map(|i| {
+                let angle = (i as f32 + 0.2 + rng.next() * 0.6) / n as f32 * std::f32::consts::TAU;
+                center
+                    + Vec2::from_angle(angle)
+                        * (radius.start + rng.next() * (radius.end - radius.start))
+            })
+            .collect()
+    }
+
+    fn corners(aabb: &AABB) -> Polygon {
+        Polygon(vec![
+            aabb.ll,
+            vec2(aabb.ur.x, aabb.ll.y),
+            aabb.ur,
+            vec2(aabb.ll.x, aabb.ur.y),
+        ])
+    }
+
+    proptest! {
+        #[test]
+        fn intersect_symmetric(
+            a in aabb(), a2 in aabb(),
+            c in circle(), c2 in circle(),
+            s in segment(), s2 in segment(),
+            o in obb(), o2 in obb(),
+            p in polygon(), p2 in polygon(),
+            v in grid_vec2(),
+        ) {
+            prop_assert_eq!(a.intersects(&a2), a2.intersects(&a));
+            prop_assert_eq!(c.intersects(&c2), c2.intersects(&c));
+            prop_assert_eq!(s.intersects(&s2), s2.intersects(&s));
+            prop_assert_eq!(o.intersects(&o2), o2.intersects(&o));
+            prop_assert_eq!(p.intersects(&p2), p2.intersects(&p));
+
+            prop_assert_eq!(a.intersects(&c), c.intersects(&a));
+            prop_assert_eq!(a.intersects(&s), s.intersects(&a));
+            prop_assert_eq!(c.intersects(&s), s.intersects(&c));
+            prop_assert_eq!(a.intersects(&v), v.intersects(&a));
+        }
+
+        #[test]
+        fn intersect_aabb_as_polygon(
+            a in any_vec2(30.0),
+            (w, h) in (0.1f32..10.0, 0.1f32..10.0),
+            o in obb(),
+            p in polygon(),
+        ) {
+            // Generic positions, since touching shapes are counted differently
+            let aabb = AABB::new(a, a + vec2(w, h));
+            prop_assert_eq!(o.intersects(&aabb), o.intersects(&OBB::from(&aabb)));
+            prop_assert_eq!(p.intersects(&aabb), p.intersects(&corners(&aabb)));
+        }
+
+        #[test]
+        fn bbox_contains_shape(
+            c in circle(),
+            s in segment(),
+            o in obb(),
+            p in polygon(),
+            angle in 0.0..std::f32::consts::TAU,
+            t in 0.0f32..1.0,
+            v in any_vec2(40.0),
+        ) {
+            let on_circle = c.center + Vec2::from_angle(angle) * c.radius;
+            prop_assert!(c.bbox().contains(on_circle));
+
+            prop_assert!(s.bbox().contains(s.src));
+            prop_assert!(s.bbox().contains(s.dst));
+            prop_assert!(s.bbox().contains_within(s.src + s.vec() * t, 1e-4));
+
+            for &corner in &o.corners {
+                prop_assert!(o.bbox().contains(corner));
+            }
+            for &point in &p.0 {
+                prop_assert!(p.bbox().contains(point));
+            }
+
+            if c.intersects(&v) {
+                prop_assert!(c.bbox().contains_within(v, 1e-4));
+            }
+            if o.intersects(&v) {
+                prop_assert!(o.bbox().contains_within(v, 1e-4));
+            }
+            if p.intersects(&v) {
+                prop_assert!(p.bbox().contains_within(v, 1e-4));
+            }
+        }
+
+        #[test]
+        fn bbox_broad_phase(
+            q in aabb(),
+            c in circle(),
+            s in segment(),
+            o in obb(),
+            p in polygon(),
+        ) {
+            // The spatial map only tests the narrow shape if the bboxes intersect
+            if c.intersects(&q) {
+                prop_assert!(c.bbox().intersects(&q));
+            }
+            if s.intersects(&q) {
+                prop_assert!(s.bbox().intersects(&q));
+            }
+            if o.intersects(&q) {
+                prop_assert!(o.bbox().intersects(&q));
+            }
+            if p.intersects(&q) {
+                prop_assert!(p.bbox().intersects(&q));
+            }
+        }
+    }
+}
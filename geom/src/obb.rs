@@ -163,10 +163,10 @@ impl Intersect<AABB> for OBB {
             max_y = max_y.max(c.y);
         }
 
-        let v =
+        let separated =
             min_x > shape.ur.x || max_x < shape.ll.x || min_y > shape.ur.y || max_y < shape.ll.y;
 
-        v || self.intersects1way(&shape.into())
+        !separated && self.intersects1way(&shape.into())
     }
 }
 
@@ -276,7 +276,10 @@ impl Intersect<Polygon> for Polygon {
                 || other
                     .0
                     .iter()
-                    .any(|&point| mybbox.contains(point) && self.contains(point)))
+                    .any(|&point| mybbox.contains(point) && self.contains(point))
+                || self
+                    .segments()
+                    .any(|s| other.segments().any(|s2| s.intersects(&s2))))
     }
 }
 
@@ -285,3 +288,35 @@ impl Intersect<OBB> for Polygon {
         self.segments().any(|s| shape.intersects(&s)) || self.contains(shape.corners[0])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{any_vec2, polygon};
+    use crate::{Polygon, Vec2};
+    use proptest::prelude::*;
+
+    /// Reference winding number of the polygon around p
+    fn winding(poly: &Polygon, p: Vec2) -> i32 {
+        poly.segments()
+            .map(|s| {
+                let side = (s.dst - s.src).cross(p - s.src);
+                if s.src.y <= p.y && s.dst.y > p.y && side > 0.0 {
+                    1
+                } else if s.src.y > p.y && s.dst.y <= p.y && side < 0.0 {
+                    -1
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+
+    proptest! {
+        #[test]
+        fn contains_matches_winding(poly in polygon(), p in any_vec2(60.0)) {
+            // On the border, both answers are fine
+            prop_assume!(poly.project(p).distance(p) > 1e-3);
+            prop_assert_eq!(poly.contains(p), winding(&poly, p) != 0);
+        }
+    }
+}
@@ -333,3 +333,38 @@ impl<'a> PointsAlongs<'a> {
         std::iter::from_fn(move || self.next(it.next()?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::any_vec2;
+    use crate::{PolyLine, Segment};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn project_is_closest(
+            points in prop::collection::vec(any_vec2(50.0), 1..10),
+            p in any_vec2(80.0),
+        ) {
+            let poly = PolyLine::new(points);
+            let (proj, seg) = poly.project_segment(p);
+            let dist = proj.distance(p);
+
+            // The projection is on the segment ending at seg
+            let on_seg = if seg == 0 {
+                poly.first().distance(proj)
+            } else {
+                Segment::new(poly[seg - 1], poly[seg]).project(proj).distance(proj)
+            };
+            prop_assert!(on_seg < 1e-3);
+
+            // and no point of the polyline is closer
+            for w in poly.as_slice().windows(2) {
+                for i in 0..=100 {
+                    let sample = w[0] + (w[1] - w[0]) * (i as f32 / 100.0);
+                    prop_assert!(dist <= sample.distance(p) + 1e-3);
+                }
+            }
+        }
+    }
+}
@@ -86,7 +86,7 @@ impl Segment {
 
 impl Shape for Segment {
     fn bbox(&self) -> AABB {
-        AABB::new(self.src, self.dst)
+        AABB::new(self.src.min(self.dst), self.src.max(self.dst))
     }
 }
 
@@ -1,12 +1,53 @@
-#![allow(clippy::ptr_arg)]
-
-use crate::{Ray, Segment, Vec2, Vec3};
+use crate::{Intersect, Polygon, Segment, Vec2, Vec3};
 use ordered_float::OrderedFloat;
-use std::cmp::{Ordering, Reverse};
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 
 const EPSILON: f32 = 0.00001;
 
+/// Points closer than that are merged
+const MIN_EDGE_LENGTH: f32 = 0.001;
+
+/// Contours with a smaller area are degenerate
+const MIN_AREA: f32 = 0.0001;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SkeletonError {
+    /// A contour has less than 3 points once duplicate and collinear points are removed
+    DegenerateContour,
+    /// A coordinate is NaN or infinite
+    NonFinite,
+    /// Two edges of the contours cross each other
+    SelfIntersecting,
+    /// A hole is not inside the polygon
+    HoleOutside,
+    /// The algorithm reached a state it cannot continue from, usually because the input is
+    /// nearly degenerate
+    Inconsistent,
+}
+
+impl Display for SkeletonError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SkeletonError::DegenerateContour => "a contour has less than 3 distinct points",
+            SkeletonError::NonFinite => "a coordinate is not finite",
+            SkeletonError::SelfIntersecting => "the contours intersect",
+            SkeletonError::HoleOutside => "a hole is outside the polygon",
+            SkeletonError::Inconsistent => "the skeleton could not be computed",
+        })
+    }
+}
+
+impl std::error::Error for SkeletonError {}
+
+type Result<T> = std::result::Result<T, SkeletonError>;
+
+/// Links between vertices are always set once the lavs are built,
+/// a missing one means they are corrupted
+fn link<T>(x: Option<T>) -> Result<T> {
+    x.ok_or(SkeletonError::Inconsistent)
+}
+
 fn window<T>(lst: &[T]) -> impl Iterator<Item = (&T, &T, &T)> {
     let prevs = lst.iter().cycle().skip(lst.len() - 1);
     let items = lst.iter();
@@ -23,130 +64,97 @@ fn approx_equal_vec(a: Vec2, b: Vec2) -> bool {
     approx_equal(a.x, b.x) && approx_equal(a.y, b.y)
 }
 
-fn normalize_contour(contour: &[Vec2]) -> Vec<Vec2> {
-    window(&contour.iter().copied().rev().collect::<Vec<_>>())
-        .filter(|&(&prev, &point, &next)| {
-            !approx_equal_vec(point, next)
-                && !approx_equal_vec((point - prev).normalize(), (next - point).normalize())
-        })
-        .map(|(_, p, _)| *p)
-        .collect()
-}
-
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct VertexID(pub usize);
-
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct LavID(pub usize);
-
-type Vertices = Vec<LAVertex>;
-type Lavs = Vec<LAV>;
-
-#[derive(Debug)]
-struct SplitEvent {
-    distance: f32,
-    intersection_point: Vec2,
-    vertex: VertexID,
-    opposite_edge: Segment,
-}
-
-impl SplitEvent {
-    pub fn new(
-        distance: f32,
-        intersection_point: Vec2,
-        vertex: VertexID,
-        opposite_edge: Segment,
-    ) -> Self {
-        SplitEvent {
-            distance,
-            intersection_point,
-            vertex,
-            opposite_edge,
+/// Removes duplicate points, collinear points and spikes until there are none left,
+/// since removing one may make its neighbours collinear
+fn clean_contour(contour: &[Vec2]) -> Vec<Vec2> {
+    let mut cur = contour.to_vec();
+    while cur.len() >= 3 {
+        let next: Vec<Vec2> = window(&cur)
+            .filter(|&(&prev, &point, &next)| {
+                if approx_equal_vec(point, next)
+                    || point.distance2(next) < MIN_EDGE_LENGTH * MIN_EDGE_LENGTH
+                {
+                    return false;
+                }
+                let d1 = (point - prev).normalize();
+                let d2 = (next - point).normalize();
+                !approx_equal_vec(d1, d2) && !approx_equal_vec(d1, -d2)
+            })
+            .map(|(_, p, _)| *p)
+            .collect();
+        if next.len() == cur.len() {
+            break;
         }
+        cur = next;
     }
+    cur
 }
 
-#[derive(Debug)]
-struct EdgeEvent {
-    distance: f32,
-    intersection_point: Vec2,
-    vertex_a: VertexID,
-    vertex_b: VertexID,
+fn signed_area(contour: &[Vec2]) -> f32 {
+    window(contour)
+        .map(|(_, cur, next)| cur.x * next.y - next.x * cur.y)
+        .sum::<f32>()
+        * 0.5
 }
 
-impl EdgeEvent {
-    pub fn new(
-        distance: f32,
-        intersection_point: Vec2,
-        vertex_a: VertexID,
-        vertex_b: VertexID,
-    ) -> Self {
-        EdgeEvent {
-            distance,
-            intersection_point,
-            vertex_a,
-            vertex_b,
-        }
+/// Cleans the contour and orients it, the algorithm wants the polygon clockwise
+/// and the holes counter-clockwise
+fn normalize_contour(contour: &[Vec2], ccw: bool) -> Result<Vec<Vec2>> {
+    if contour.iter().any(|p| !p.x.is_finite() || !p.y.is_finite()) {
+        return Err(SkeletonError::NonFinite);
     }
-}
-
-#[derive(Debug)]
-enum Event {
-    Split(SplitEvent),
-    Edge(EdgeEvent),
-}
-
-impl Event {
-    fn distance(&self) -> f32 {
-        match self {
-            Event::Split(s) => s.distance,
-            Event::Edge(e) => e.distance,
-        }
+    let mut contour = clean_contour(contour);
+    if contour.len() < 3 {
+        return Err(SkeletonError::DegenerateContour);
     }
-
-    fn intersection_point(&self) -> Vec2 {
-        match self {
-            Event::Split(s) => s.intersection_point,
-            Event::Edge(e) => e.intersection_point,
-        }
+    let area = signed_area(&contour);
+    if area.abs() < MIN_AREA {
+        return Err(SkeletonError::DegenerateContour);
     }
-}
-
-impl PartialEq for Event {
-    fn eq(&self, other: &Self) -> bool {
-        self.distance() == other.distance()
+    if (area > 0.0) != ccw {
+        contour.reverse();
     }
+    Ok(contour)
 }
 
-impl Eq for Event {}
-
-impl PartialOrd for Event {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.distance().partial_cmp(&other.distance())
+/// Normalizes the polygon and its holes, checking they form a valid polygon with holes
+fn normalize_contours(polygon: &[Vec2], holes: &[&[Vec2]]) -> Result<Vec<Vec<Vec2>>> {
+    let mut contours = vec![normalize_contour(polygon, false)?];
+    for hole in holes {
+        contours.push(normalize_contour(hole, true)?);
     }
-}
 
-impl Ord for Event {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap_or(Ordering::Equal)
+    let outer = Polygon(contours[0].clone());
+    if contours[1..].iter().any(|hole| !outer.contains(hole[0])) {
+        return Err(SkeletonError::HoleOutside);
     }
-}
 
-#[derive(Debug)]
-struct OriginalEdge {
-    edge: Segment,
-    bisector_left: Ray,
-    bisector_right: Ray,
-}
+    let segments: Vec<(usize, usize, Segment)> = contours
+        .iter()
+        .enumerate()
+        .flat_map(|(c, contour)| {
+            (0..contour.len()).map(move |i| {
+                let next = contour[(i + 1) % contour.len()];
+                (c, i, Segment::new(contour[i], next))
+            })
+        })
+        .collect();
 
-impl OriginalEdge {
-    pub fn new(edge: Segment, bisector_left: Ray, bisector_right: Ray) -> Self {
-        OriginalEdge {
-            edge,
-            bisector_left,
-            bisector_right,
+    for (k, &(ca, ia, sa)) in segments.iter().enumerate() {
+        for &(cb, ib, sb) in &segments[k + 1..] {
+            if ca == cb {
+                let n = contours[ca].len();
+                if ib == (ia + 1) % n || ia == (ib + 1) % n {
+                    continue;
+                }
+            }
+            if sa.intersects(&sb) {
+                return Err(SkeletonError::SelfIntersecting);
+            }
         }
     }
+
+    Ok(contours)
 }
 
 #[derive(Debug)]
@@ -166,648 +174,416 @@ impl Subtree {
     }
 }
 
-#[derive(Clone)]
-struct LAVertex {
-    pub(crate) id: VertexID,
-    pub(crate) point: Vec2,
-    pub(crate) edge_left: Segment,
-    pub(crate) edge_right: Segment,
-    pub(crate) prev: Option<VertexID>,
-    pub(crate) next: Option<VertexID>,
-    pub(crate) lav: Option<LavID>,
-    pub(crate) valid: bool,
-    pub(crate) is_reflex: bool,
-    pub(crate) bisector: Ray,
+/// An edge of the contours, at time t its wavefront is the line of the points p
+/// with `normal.dot(p) == offset + t`
+struct Edge {
+    dir: Vec2,
+    normal: Vec2,
+    offset: f32,
 }
 
-impl LAVertex {
-    #[allow(clippy::let_and_return)]
-    pub(crate) fn next_event(&self, slav: &SLAV, vs: &Vertices) -> Option<Event> {
-        let mut events = vec![];
-        if self.is_reflex {
-            // a reflex vertex may generate a split event
-            // split events happen when a vertex hits an opposite edge, splitting the polygon in two.
-            #[cfg(test)]
-            {
-                println!("looking for split candidates for vertex {:?}", self.point);
-                println!("edge_left {:?}", self.edge_left);
-                println!("edge_right {:?}", self.edge_right);
-            }
-
-            for edge in &slav.original_edges {
-                if edge.edge == self.edge_left || edge.edge == self.edge_right {
-                    continue;
-                }
-                #[cfg(test)]
-                println!("\t considering EDGE {:?}", edge.edge);
-
-                // a potential b is at the intersection of between our own bisector and the bisector of the
-                // angle between the tested edge and any one of our own edges.
-                // we choose the "less parallel" edge (in order to exclude a potentially parallel edge)
-
-                let left_dot = self
-                    .edge_left
-                    .vec()
-                    .normalize()
-                    .dot(edge.edge.vec().normalize())
-                    .abs();
-                let right_dot = self
-                    .edge_right
-                    .vec()
-                    .normalize()
-                    .dot(edge.edge.vec().normalize())
-                    .abs();
-                let self_edge = if left_dot < right_dot {
-                    self.edge_left
-                } else {
-                    self.edge_right
-                };
-
-                // println!("\t\t trying {:?} against {:?}", self_edge, &edge.edge);
-
-                if let Some(i) = self_edge.as_line().intersection_point(&edge.edge.as_line()) {
-                    if approx_equal_vec(i, self.point) {
-                        continue;
-                    }
-                    #[cfg(test)]
-                    println!("\t\t found intersection {:?}", i);
-
-                    let lin_vec = (self.point - i).normalize();
-                    let mut ed_vec = edge.edge.vec().normalize();
-                    if lin_vec.dot(ed_vec) < 0.0 {
-                        ed_vec = -ed_vec;
-                    }
-
-                    let bisect_vec = ed_vec + lin_vec;
-                    if approx_equal_vec(bisect_vec, Vec2::ZERO) {
-                        continue;
-                    }
-                    let bisector = Ray::new(i, bisect_vec);
-                    let b = bisector.intersection_point(&self.bisector);
-                    if b.is_none() {
-                        #[cfg(test)]
-                        println!("\t\t no bisec");
-                        continue;
-                    }
-                    let b = b.unwrap();
-
-                    let left = edge
-                        .bisector_left
-                        .dir
-                        .normalize()
-                        .cross((b - edge.bisector_left.from).normalize())
-                        > -EPSILON;
-                    let right = edge
-                        .bisector_right
-                        .dir
-                        .normalize()
-                        .cross((b - edge.bisector_right.from).normalize())
-                        < EPSILON;
-                    let b_edge = edge
-                        .edge
-                        .vec()
-                        .normalize()
-                        .cross((b - edge.edge.src).normalize())
-                        < EPSILON;
-
-                    if !(left && right && b_edge) {
-                        #[cfg(test)]
-                        println!(
-                            "\t\tDiscarded candidate {:?} ({}-{}-{})",
-                            b, left, right, b_edge
-                        );
-                        continue;
-                    }
-
-                    #[cfg(test)]
-                    println!("\t\tFound valid candidate {:?}", b);
-                    events.push(Event::Split(SplitEvent::new(
-                        edge.edge.as_line().project(b).distance(b),
-                        b,
-                        self.id,
-                        edge.edge,
-                    )));
-                }
-            }
-        }
-
-        let i_prev = self
-            .bisector
-            .intersection_point(&vs[self.prev.unwrap().0].bisector);
-        let i_next = self
-            .bisector
-            .intersection_point(&vs[self.next.unwrap().0].bisector);
-
-        if let Some(i_prev) = i_prev {
-            events.push(Event::Edge(EdgeEvent::new(
-                self.edge_left.as_line().project(i_prev).distance(i_prev),
-                i_prev,
-                self.prev.unwrap(),
-                self.id,
-            )));
-        }
-
-        if let Some(i_next) = i_next {
-            events.push(Event::Edge(EdgeEvent::new(
-                self.edge_right.as_line().project(i_next).distance(i_next),
-                i_next,
-                self.id,
-                self.next.unwrap(),
-            )));
-        }
-
-        if events.is_empty() {
-            return None;
+impl Edge {
+    fn new(src: Vec2, dst: Vec2) -> Self {
+        let dir = (dst - src).normalize();
+        // the contours are oriented so that the inside is on the right of every edge
+        let normal = Vec2::new(dir.y, -dir.x);
+        Edge {
+            dir,
+            normal,
+            offset: normal.dot(src),
         }
+    }
+}
 
-        #[cfg(test)]
-        println!(
-            "choosing events between ({:?}-{:?}-{:?})",
-            vs[self.prev.unwrap().0].bisector,
-            self.bisector,
-            vs[self.next.unwrap().0].bisector
-        );
+/// A vertex of the wavefront, it moves so that it stays on the wavefront of both of its edges
+struct Vertex {
+    /// Position at `time`
+    pos: Vec2,
+    time: f32,
+    vel: Vec2,
+    left: usize,
+    right: usize,
+    prev: usize,
+    next: usize,
+    /// The node of the skeleton the vertex started from, in the coordinates of the polygon
+    node: Vec2,
+    is_reflex: bool,
+    alive: bool,
+}
 
-        let mut min_ev = None;
-        let mut min_v: f32 = f32::INFINITY;
-        for ev in events {
-            #[cfg(test)]
-            println!("    {:?}", &ev);
-            let d = self.point.distance2(ev.intersection_point());
-            if d < min_v {
-                min_v = d;
-                min_ev = Some(ev);
-            }
-        }
-        #[cfg(test)]
-        if let Some(ref e) = min_ev {
-            println!("Generated new event for {:?}: {:?}", self.id, e)
+impl Vertex {
+    fn new(edges: &[Edge], pos: Vec2, time: f32, left: usize, right: usize, node: Vec2) -> Self {
+        let (l, r) = (&edges[left], &edges[right]);
+        let det = l.normal.cross(r.normal);
+        let vel = if det.abs() > EPSILON {
+            Vec2::new(r.normal.y - l.normal.y, l.normal.x - r.normal.x) / det
+        } else if l.normal.dot(r.normal) > 0.0 {
+            // both edges are on the same line
+            l.normal
+        } else {
+            // the edges meet face to face, the vertex is collapsed by the next event
+            Vec2::ZERO
+        };
+        Vertex {
+            pos,
+            time,
+            vel,
+            left,
+            right,
+            prev: 0,
+            next: 0,
+            node,
+            is_reflex: l.dir.cross(r.dir) > EPSILON,
+            alive: true,
         }
-        min_ev
     }
 
-    pub fn invalidate(&mut self, lavs: &mut Lavs) {
-        #[cfg(test)]
-        println!("invalidating {:?}", self.point);
-        self.valid = false;
-        if let Some(id) = self.lav {
-            let lav = &mut lavs[id.0];
-            if lav.head.unwrap() == self.id {
-                lav.head = self.next;
-            }
-            self.lav = None;
-        }
+    fn at(&self, t: f32) -> Vec2 {
+        self.pos + self.vel * (t - self.time)
     }
+}
 
-    pub fn invalidate_known(&mut self, lav: &mut LAV) {
-        #[cfg(test)]
-        println!("invalidating {:?}", self.point);
+#[derive(Copy, Clone, Debug)]
+enum EventKind {
+    /// The edge between the vertex and its next one shrank to nothing
+    Edge(usize),
+    /// The reflex vertex hits the edge between the second vertex and its next one
+    Split(usize, usize),
+}
 
-        self.valid = false;
+#[derive(Copy, Clone, Debug)]
+struct Event {
+    time: f32,
+    point: Vec2,
+    kind: EventKind,
+}
 
-        if let Some(id) = self.lav {
-            if id != lav.id {
-                panic!("invalidating known lav but it's not this vertices lav!");
+/// Shrinks the contours, handling the earliest event each step. Every event is computed from
+/// the current state of the wavefront so there are no outdated events to keep track of.
+struct Wavefront {
+    edges: Vec<Edge>,
+    vs: Vec<Vertex>,
+    /// The contours are moved around the origin to keep the precision, this is where they were
+    origin: Vec2,
+    /// Distances under this are considered zero, depends on the size of the polygon
+    tol: f32,
+    now: f32,
+    /// Time, point and node of the last event
+    last: Option<(f32, Vec2, Vec2)>,
+    output: Vec<Subtree>,
+}
+
+impl Wavefront {
+    fn new(contours: &[Vec<Vec2>]) -> Self {
+        let (ll, ur) = contours[0].iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(ll, ur), &p| (ll.min(p), ur.max(p)),
+        );
+        let origin = (ll + ur) * 0.5;
+        let size = (ur - ll).x.max((ur - ll).y);
+
+        let mut edges = vec![];
+        let mut vs = vec![];
+        for contour in contours {
+            let first = vs.len();
+            let n = contour.len();
+            for i in 0..n {
+                edges.push(Edge::new(
+                    contour[i] - origin,
+                    contour[(i + 1) % n] - origin,
+                ));
             }
-            if lav.head.unwrap() == self.id {
-                lav.head = self.next;
+            for (i, &p) in contour.iter().enumerate() {
+                let left = first + (i + n - 1) % n;
+                let mut v = Vertex::new(&edges, p - origin, 0.0, left, first + i, p);
+                v.prev = left;
+                v.next = first + (i + 1) % n;
+                vs.push(v);
             }
-            self.lav = None;
+        }
+
+        Wavefront {
+            edges,
+            vs,
+            origin,
+            tol: size * 0.0001,
+            now: 0.0,
+            last: None,
+            output: vec![],
         }
     }
-}
 
-impl LAVertex {
-    pub fn new(
-        id: VertexID,
-        point: Vec2,
-        edge_left: Segment,
-        edge_right: Segment,
-        direction_vectors: Option<(Vec2, Vec2)>,
-    ) -> Self {
-        let creator_vectors = (-edge_left.vec().normalize(), edge_right.vec().normalize());
-        let direction_vectors = if let Some(v) = direction_vectors {
-            v
+    fn edge_event(&self, a: usize) -> Option<Event> {
+        let (va, vb) = (&self.vs[a], &self.vs[self.vs[a].next]);
+        let dir = self.edges[va.right].dir;
+
+        let len = (vb.at(self.now) - va.at(self.now)).dot(dir);
+        let shrink = (va.vel - vb.vel).dot(dir);
+        let time = if shrink > EPSILON {
+            self.now + len.max(0.0) / shrink
+        } else if len <= self.tol {
+            self.now
         } else {
-            creator_vectors
+            return None;
         };
 
-        let is_reflex = direction_vectors.0.cross(direction_vectors.1) < 0.0;
-        let bisector = Ray {
-            from: point,
-            dir: (creator_vectors.0 + creator_vectors.1) * if is_reflex { -1.0 } else { 1.0 },
-        };
-        #[cfg(test)]
-        println!(
-            "created vertex ({}) {:?} {:?} {:?} {:?} {:?}",
-            if is_reflex { "reflex" } else { "convex" },
-            id,
-            point,
-            bisector,
-            edge_left,
-            edge_right,
-        );
+        Some(Event {
+            time,
+            point: (va.at(time) + vb.at(time)) * 0.5,
+            kind: EventKind::Edge(a),
+        })
+    }
 
-        LAVertex {
-            id,
-            point,
-            edge_left,
-            edge_right,
-            prev: None,
-            next: None,
-            lav: None,
-            valid: true,
-            is_reflex,
-            bisector,
+    fn split_event(&self, v: usize, a: usize) -> Option<Event> {
+        let vv = &self.vs[v];
+        let b = self.vs[a].next;
+        if a == v || b == v {
+            return None;
         }
-    }
-}
+        let e = &self.edges[self.vs[a].right];
 
-struct SLAV {
-    lavs: Vec<LavID>,
-    original_edges: Vec<OriginalEdge>,
-}
+        // how far in front of the wavefront of the edge the vertex is, and how fast it closes in
+        let dist = e.normal.dot(vv.at(self.now)) - e.offset - self.now;
+        let closing = 1.0 - e.normal.dot(vv.vel);
+        if dist < -self.tol || closing < EPSILON {
+            return None;
+        }
+        let time = self.now + dist.max(0.0) / closing;
 
-impl SLAV {
-    pub fn handle_edge_event(
-        &mut self,
-        vs: &mut Vertices,
-        lavs: &mut Lavs,
-        event: EdgeEvent,
-    ) -> (Option<Subtree>, Vec<Event>) {
-        let mut sinks = vec![];
-        let mut events = vec![];
-
-        let va = &vs[event.vertex_a.0];
-        let vb = &vs[event.vertex_b.0];
-        let vap = va.point;
-        let vbp = vb.point;
-
-        let lav = &mut lavs[va.lav.unwrap().0];
-
-        if va.prev == vb.next {
-            #[cfg(test)]
-            println!(
-                "{} Peak event at intersection {:?} from <{:?},{:?},{:?}> in {:?}",
-                event.distance,
-                event.intersection_point,
-                event.vertex_a,
-                event.vertex_b,
-                va.prev,
-                lav.id
-            );
-            self.lavs
-                .remove(self.lavs.iter().position(|&x| x == lav.id).unwrap());
-            for vertex in lav.iter_keys(vs) {
-                let vertex = &mut vs[vertex.0];
-                sinks.push(vertex.point);
-                vertex.invalidate_known(lav);
-            }
+        let p = vv.at(time);
+        let pa = self.vs[a].at(time);
+        let len = (self.vs[b].at(time) - pa).dot(e.dir);
+        let u = (p - pa).dot(e.dir);
+        if u < -self.tol || u > len + self.tol {
+            return None;
+        }
+        // reaching a neighbour is an edge event
+        if (u < self.tol && a == vv.next) || (u > len - self.tol && b == vv.prev) {
+            return None;
+        }
+        // vertices created together by the last event are moving away from each other
+        let w = if u < self.tol {
+            Some(&self.vs[a])
+        } else if u > len - self.tol {
+            Some(&self.vs[b])
         } else {
-            #[cfg(test)]
-            println!(
-                "{} Edge event at intersection {:?} from <{:?},{:?}> in {:?}",
-                event.distance, event.intersection_point, event.vertex_a, event.vertex_b, lav.id
-            );
-
-            let new_vertex =
-                lav.unify(vs, event.vertex_a, event.vertex_b, event.intersection_point);
-            let h = lav.head.unwrap();
-            if h == event.vertex_a || h == event.vertex_b {
-                lav.head = Some(new_vertex);
-            }
-            sinks.push(vap);
-            sinks.push(vbp);
-            if let Some(next_event) = vs[new_vertex.0].next_event(self, vs) {
-                events.push(next_event);
+            None
+        };
+        if let Some(w) = w {
+            if w.time == self.now && vv.time == self.now && w.node == vv.node {
+                return None;
             }
         }
 
-        (
-            Some(Subtree::new(
-                event.intersection_point,
-                event.distance,
-                sinks,
-            )),
-            events,
-        )
+        Some(Event {
+            time,
+            point: p,
+            kind: EventKind::Split(v, a),
+        })
     }
 
-    pub fn handle_split_event(
-        &mut self,
-        vs: &mut Vertices,
-        lavs: &mut Lavs,
-        event: SplitEvent,
-    ) -> (Option<Subtree>, Vec<Event>) {
-        let v = vs[event.vertex.0].clone();
-        #[cfg(test)]
-        println!(
-            "{} Split event at intersection {:?} from vertex {:?}, for edge {:?} in {:?}",
-            event.distance, event.intersection_point, event.vertex, event.opposite_edge, v.lav,
-        );
-
-        let mut sinks = vec![v.point];
-        let mut vertices: Vec<VertexID> = vec![];
-
-        let mut x = None;
-        let mut y = None;
-
-        let norm = event.opposite_edge.vec().normalize();
-
-        for &l in &self.lavs {
-            for v in lavs[l.0].iter_keys(vs) {
-                let v = &vs[v.0];
-                #[cfg(test)]
-                println!("{:?} in {:?}", v.id, v.lav);
-
-                if approx_equal_vec(norm, v.edge_left.vec().normalize())
-                    && approx_equal_vec(event.opposite_edge.src, v.edge_left.src)
-                {
-                    x = Some(v.id);
-                    y = v.prev;
-                } else if approx_equal_vec(norm, v.edge_right.vec().normalize())
-                    && approx_equal_vec(event.opposite_edge.src, v.edge_right.src)
+    fn next_event(&self) -> Option<Event> {
+        let mut best: Option<Event> = None;
+        let mut consider = |ev: Event| {
+            let better = match best {
+                None => true,
+                // edge events first when they happen at the same time and place, they are simpler
+                Some(b)
+                    if (ev.time - b.time).abs() <= self.tol
+                        && ev.point.distance(b.point) <= self.tol =>
                 {
-                    y = Some(v.id);
-                    x = v.next;
-                }
-
-                if let Some(x_id) = x {
-                    let xx = &vs[x_id.0];
-                    let yy = &vs[y.unwrap().0];
-                    let left = yy
-                        .bisector
-                        .dir
-                        .normalize()
-                        .cross((event.intersection_point - yy.point).normalize())
-                        >= -EPSILON;
-                    let right = xx
-                        .bisector
-                        .dir
-                        .normalize()
-                        .cross((event.intersection_point - xx.point).normalize())
-                        <= EPSILON;
-
-                    #[cfg(test)]
-                    println!(
-                        "Vertex {:?} holds edge as {} edge ({}, {})",
-                        v.id,
-                        if x_id == v.id { "left" } else { "right" },
-                        left,
-                        right
-                    );
-
-                    if left && right {
-                        break;
-                    } else {
-                        x = None;
-                        y = None;
+                    let is_edge = |e: &Event| matches!(e.kind, EventKind::Edge(_));
+                    match (is_edge(&ev), is_edge(&b)) {
+                        (true, false) => true,
+                        (false, true) => false,
+                        _ => ev.time < b.time,
                     }
                 }
+                Some(b) => ev.time < b.time,
+            };
+            if better {
+                best = Some(ev);
             }
-        }
-
-        if x.is_none() {
-            #[cfg(test)]
-            println!(
-                "Failed split event {:?} (equivalent edge event is expected to follow)",
-                event
-            );
-            return (None, vec![]);
-        }
+        };
 
-        let x = x.unwrap();
-        let y = y.unwrap();
-
-        let v1 = VertexID(vs.len());
-        vs.push(LAVertex::new(
-            v1,
-            event.intersection_point,
-            v.edge_left,
-            event.opposite_edge,
-            None,
-        ));
-
-        vs[v1.0].prev = Some(v.prev.unwrap());
-        vs[v1.0].next = Some(x);
-        vs[v.prev.unwrap().0].next = Some(v1);
-        vs[x.0].prev = Some(v1);
-
-        let v2 = VertexID(vs.len());
-        vs.push(LAVertex::new(
-            v2,
-            event.intersection_point,
-            event.opposite_edge,
-            v.edge_right,
-            None,
-        ));
-
-        vs[v2.0].prev = Some(y);
-        vs[v2.0].next = Some(v.next.unwrap());
-        vs[v.next.unwrap().0].prev = Some(v2);
-        vs[y.0].next = Some(v2);
-
-        let new_lavs;
-        self.remove_lav(v.lav.unwrap());
-
-        #[cfg(test)]
-        println!("v1:{:?} v2:{:?}", vs[v1.0].point, vs[v2.0].point);
-
-        if v.lav.unwrap() != vs[x.0].lav.unwrap() {
-            self.remove_lav(vs[x.0].lav.unwrap());
-            new_lavs = vec![LAV::from_chain(lavs, vs, Some(v1))];
-        } else {
-            new_lavs = vec![
-                LAV::from_chain(lavs, vs, Some(v1)),
-                LAV::from_chain(lavs, vs, Some(v2)),
-            ];
+        let alive = || (0..self.vs.len()).filter(|&i| self.vs[i].alive);
+        for a in alive() {
+            if let Some(ev) = self.edge_event(a) {
+                consider(ev);
+            }
         }
-
-        for l in new_lavs {
-            if lavs[l.0].len > 2 {
-                self.lavs.push(l);
-                vertices.push(lavs[l.0].head.unwrap());
-            } else {
-                #[cfg(test)]
-                println!(
-                    "LAV {:?} has collapsed into the line {:?}--{:?}",
-                    l,
-                    vs[lavs[l.0].head.unwrap().0].point,
-                    vs[vs[lavs[l.0].head.unwrap().0].next.unwrap().0].point
-                );
-
-                sinks.push(vs[vs[lavs[l.0].head.unwrap().0].next.unwrap().0].point);
-                for v in lavs[l.0].iter_keys(vs) {
-                    vs[v.0].invalidate_known(&mut lavs[l.0])
+        for v in alive().filter(|&v| self.vs[v].is_reflex) {
+            for a in alive() {
+                if let Some(ev) = self.split_event(v, a) {
+                    consider(ev);
                 }
             }
         }
-
-        let mut events = vec![];
-        for vertex in vertices {
-            events.extend(vs[vertex.0].next_event(self, vs).into_iter());
-        }
-
-        vs[event.vertex.0].invalidate(lavs);
-
-        (
-            Some(Subtree::new(
-                event.intersection_point,
-                event.distance,
-                sinks,
-            )),
-            events,
-        )
+        best
     }
 
-    fn remove_lav(&mut self, id: LavID) {
-        self.lavs
-            .remove(self.lavs.iter().position(|x| *x == id).unwrap());
+    fn add_vertex(&mut self, node: Vec2, edges: (usize, usize), prev: usize, next: usize) -> usize {
+        let (left, right) = edges;
+        let pos = node - self.origin;
+        let mut v = Vertex::new(&self.edges, pos, self.now, left, right, node);
+        v.prev = prev;
+        v.next = next;
+        self.vs.push(v);
+        let id = self.vs.len() - 1;
+        self.vs[prev].next = id;
+        self.vs[next].prev = id;
+        id
     }
-}
 
-impl SLAV {
-    pub fn new(vs: &mut Vertices, lavs: &mut Lavs, polygon: &[Vec2], holes: &[&[Vec2]]) -> Self {
-        let lavs_l = std::iter::once(normalize_contour(polygon))
-            .chain(holes.iter().map(|x| normalize_contour(x)))
-            .map(|x| LAV::from_polygon(lavs, vs, &x))
-            .collect::<Vec<_>>();
-
-        let original_edges: Vec<OriginalEdge> = lavs
-            .iter()
-            .map(|x| x.iter_keys(vs))
-            .flatten()
-            .map(|vertex| {
-                let vertex = &vs[vertex.0];
-                let prev = &vs[vertex.prev.unwrap().0];
-                OriginalEdge::new(
-                    Segment::new(prev.point, vertex.point),
-                    prev.bisector,
-                    vertex.bisector,
-                )
-            })
-            .collect();
-
-        SLAV {
-            lavs: lavs_l,
-            original_edges,
+    fn handle(&mut self, ev: Event) {
+        self.now = self.now.max(ev.time);
+        // events happening together at the same place share their node, so the skeleton doesn't
+        // cross itself between nodes very close to each other
+        let node = match self.last {
+            Some((t, p, node))
+                if (t - self.now).abs() <= self.tol && p.distance(ev.point) <= self.tol =>
+            {
+                node
+            }
+            _ => ev.point + self.origin,
+        };
+        self.last = Some((self.now, ev.point, node));
+        self.output.push(Subtree::new(node, self.now, vec![]));
+
+        match ev.kind {
+            EventKind::Edge(a) => {
+                let b = self.vs[a].next;
+                let prev = self.vs[a].prev;
+                let next = self.vs[b].next;
+                self.kill(a);
+                self.kill(b);
+                if prev == next {
+                    // the last three vertices of the contour meet
+                    self.kill(prev);
+                    return;
+                }
+                let edges = (self.vs[a].left, self.vs[b].right);
+                self.add_vertex(node, edges, prev, next);
+            }
+            EventKind::Split(v, a) => {
+                let b = self.vs[a].next;
+                let (prev, next) = (self.vs[v].prev, self.vs[v].next);
+                let e = self.vs[a].right;
+                self.kill(v);
+                let (left, right) = (self.vs[v].left, self.vs[v].right);
+                let v1 = self.add_vertex(node, (left, e), prev, b);
+                let v2 = self.add_vertex(node, (e, right), a, next);
+
+                // a contour left with two vertices has no area
+                for &n in &[v1, v2] {
+                    let other = self.vs[n].next;
+                    if self.vs[n].alive && self.vs[n].prev == other {
+                        self.kill(n);
+                        self.kill(other);
+                    }
+                }
+            }
         }
     }
-}
-
-struct LAV {
-    id: LavID,
-    head: Option<VertexID>,
-    len: usize,
-}
 
-impl LAV {
-    pub fn iter_keys(&self, vs: &Vertices) -> Vec<VertexID> {
-        if let Some(head) = self.head {
-            std::iter::successors(Some(head), move |&cur| {
-                vs[cur.0].next.filter(|&x| x != head)
-            })
-            .collect()
-        } else {
-            vec![]
+    /// Contours can end up flat, going back and forth over the same points, when all their
+    /// edges meet at once. Nothing moves them anymore so they are turned into skeleton arcs.
+    fn collapse_flat_loops(&mut self) {
+        let mut seen = vec![false; self.vs.len()];
+        for start in 0..self.vs.len() {
+            if !self.vs[start].alive || seen[start] {
+                continue;
+            }
+            let mut ids = vec![];
+            let mut v = start;
+            while !seen[v] {
+                seen[v] = true;
+                ids.push(v);
+                v = self.vs[v].next;
+            }
+            let pts: Vec<Vec2> = ids.iter().map(|&v| self.vs[v].at(self.now)).collect();
+            let perimeter: f32 = window(&pts).map(|(_, a, b)| a.distance(*b)).sum();
+            if signed_area(&pts).abs() <= self.tol * perimeter * 0.1 {
+                self.collapse(&ids, &pts);
+            }
         }
     }
 
-    pub fn from_polygon(lavs: &mut Lavs, vs: &mut Vertices, polygon: &[Vec2]) -> LavID {
-        let lav_id = LavID(lavs.len());
-        let mut len = 0;
-        let mut head = None;
-        for (&prev, &point, &next) in window(polygon) {
-            len += 1;
-            let vertex = VertexID(vs.len());
-            vs.push(LAVertex::new(
-                vertex,
-                point,
-                Segment::new(prev, point),
-                Segment::new(point, next),
-                None,
-            ));
-            vs[vertex.0].lav = Some(lav_id);
-            if let Some(head) = head {
-                vs[vertex.0].next = Some(head);
-                let prev_head = vs[head.0].prev;
-                vs[vertex.0].prev = prev_head;
-                vs[prev_head.unwrap().0].next = Some(vertex);
-                vs[head.0].prev = Some(vertex);
+    fn collapse(&mut self, ids: &[usize], pts: &[Vec2]) {
+        // vertices at the same place share a node
+        let mut nodes: Vec<Vec2> = vec![];
+        let mut node_of = vec![];
+        for (&v, &p) in ids.iter().zip(pts) {
+            let vert = &self.vs[v];
+            let p = if vert.time == self.now {
+                vert.node
             } else {
-                head = Some(vertex);
-                vs[vertex.0].prev = Some(vertex);
-                vs[vertex.0].next = Some(vertex);
+                p + self.origin
+            };
+            let i = match nodes.iter().position(|n| n.distance(p) <= self.tol) {
+                Some(i) => i,
+                None => {
+                    nodes.push(p);
+                    nodes.len() - 1
+                }
+            };
+            node_of.push(i);
+            if nodes[i] != vert.node {
+                let tree = Subtree::new(nodes[i], self.now, vec![vert.node]);
+                self.output.push(tree);
             }
+            self.vs[v].alive = false;
         }
-        lavs.push(LAV {
-            id: lav_id,
-            head,
-            len,
-        });
-        lav_id
-    }
 
-    pub fn from_chain(lavs: &mut Lavs, vs: &mut Vertices, head: Option<VertexID>) -> LavID {
-        let lav_id = LavID(lavs.len());
-        lavs.push({
-            let mut l = LAV {
-                id: lav_id,
-                head,
-                len: 0,
-            };
-            for vertex in l.iter_keys(vs) {
-                l.len += 1;
-                vs[vertex.0].lav = Some(lav_id);
+        let mut arcs = vec![];
+        for k in 0..ids.len() {
+            let (a, b) = (nodes[node_of[k]], nodes[node_of[(k + 1) % ids.len()]]);
+            let len = a.distance(b);
+            if len <= self.tol {
+                continue;
             }
-            l
-        });
-        lav_id
+            // the contour goes back over the nodes in between without stopping at them
+            let dir = (b - a) / len;
+            let mut on: Vec<(f32, usize)> = nodes
+                .iter()
+                .enumerate()
+                .filter_map(|(i, &p)| {
+                    let u = (p - a).dot(dir);
+                    let inside = u > -self.tol && u < len + self.tol;
+                    Some((u, i)).filter(|_| inside && (a + dir * u).distance(p) <= self.tol)
+                })
+                .collect();
+            on.sort_by_key(|&(u, _)| OrderedFloat(u));
+            for w in on.windows(2) {
+                let (i, j) = (w[0].1, w[1].1);
+                arcs.push((i.min(j), i.max(j)));
+            }
+        }
+        arcs.sort_unstable();
+        arcs.dedup();
+        for (i, j) in arcs {
+            self.output
+                .push(Subtree::new(nodes[i], self.now, vec![nodes[j]]));
+        }
     }
 
-    pub fn unify(
-        &mut self,
-        vs: &mut Vertices,
-        vertex_a: VertexID,
-        vertex_b: VertexID,
-        point: Vec2,
-    ) -> VertexID {
-        let va = &vs[vertex_a.0].clone();
-        let vb = &vs[vertex_b.0].clone();
-
-        let replacement = VertexID(vs.len());
-        vs.push(LAVertex::new(
-            replacement,
-            point,
-            va.edge_left,
-            vb.edge_right,
-            Some((vb.bisector.dir.normalize(), va.bisector.dir.normalize())),
-        ));
-
-        vs[replacement.0].lav = Some(self.id);
-
-        let h = self.head.unwrap();
-        if h == vertex_a || h == vertex_b {
-            self.head = Some(replacement);
+    /// Removes the vertex from the wavefront, its path is part of the skeleton
+    fn kill(&mut self, v: usize) {
+        let vert = &mut self.vs[v];
+        if !vert.alive {
+            return;
+        }
+        vert.alive = false;
+        let node = vert.node;
+        if let Some(tree) = self.output.last_mut() {
+            tree.sinks.push(node);
         }
-
-        vs[va.prev.unwrap().0].next = Some(replacement);
-        vs[vb.next.unwrap().0].prev = Some(replacement);
-
-        vs[replacement.0].prev = va.prev;
-        vs[replacement.0].next = vb.next;
-
-        vs[vertex_a.0].invalidate_known(self);
-        vs[vertex_b.0].invalidate_known(self);
-
-        self.len -= 1;
-
-        replacement
     }
 }
 
 /// In highly symmetrical shapes with reflex vertices multiple sources may share the same
-/// location. This function merges those sources.
+/// location. This function merges those sources, and removes the sinks that are the source
+/// itself left by vertices colliding.
 fn merge_sources(skeleton: &mut Vec<Subtree>) {
     let mut sources: HashMap<Vec2, usize> = HashMap::new();
     let mut to_remove = vec![];
@@ -830,115 +606,79 @@ fn merge_sources(skeleton: &mut Vec<Subtree>) {
     for i in to_remove.into_iter().rev() {
         skeleton.swap_remove(i);
     }
+    for tree in skeleton.iter_mut() {
+        let source = tree.source;
+        tree.sinks.retain(|&x| x != source);
+    }
+    skeleton.retain(|tree| !tree.sinks.is_empty());
 }
 
-/// Compute the straight skeleton of a polygon.
+/// Compute the straight skeleton of a polygon with holes.
 ///
-/// The polygon should be given as a list of vertices in counter-clockwise order.
+/// The polygon and the holes can be given in any orientation,
+/// duplicate and collinear points are ignored.
 ///
-/// Please note that the y-axis goes upwards, so specify your ordering accordingly.
+/// Please note that the y-axis goes upwards.
 ///
 /// Returns the straight skeleton as a list of "subtrees", which are in the form of (source, height, sinks),
 /// where source is the highest points, height is its height, and sinks are the point connected to the source.
-pub fn skeleton(polygon: &[Vec2], holes: &[&[Vec2]]) -> Vec<Subtree> {
-    #[cfg(test)]
-    println!("beginning skeleton {:?}", polygon);
-
-    let mut vs = vec![];
-    let mut lavs = vec![];
-    let mut output = vec![];
-    let mut queue = BinaryHeap::new();
-
-    let mut slav = SLAV::new(&mut vs, &mut lavs, polygon, holes);
-
-    for &lav in &slav.lavs {
-        for vertex in lavs[lav.0].iter_keys(&vs) {
-            if let Some(ev) = vs[vertex.0].next_event(&slav, &vs) {
-                queue.push(Reverse(ev))
-            }
-        }
-    }
-
-    #[cfg(test)]
-    let mut counter = 0;
-    while !queue.is_empty() && !slav.lavs.is_empty() {
-        #[cfg(test)]
+pub fn skeleton(polygon: &[Vec2], holes: &[&[Vec2]]) -> Result<Vec<Subtree>> {
+    let contours = normalize_contours(polygon, holes)?;
+    let mut wavefront = Wavefront::new(&contours);
+
+    // every event removes at least one vertex or splits a contour, a lot more events than
+    // vertices means the wavefront is going around in circles
+    let max_events = 16 * (wavefront.vs.len() + 1);
+    for _ in 0..max_events {
+        let ev = match wavefront.next_event() {
+            Some(ev) => ev,
+            None => break,
+        };
+        if !ev.time.is_finite() || !ev.point.is_finite() || ev.time < wavefront.now - wavefront.tol
         {
-            counter += 1;
-            println!("---- round {}", counter);
-            println!(
-                "queue is {:?}",
-                queue
-                    .iter()
-                    .map(|Reverse(x)| {
-                        (
-                            x.distance(),
-                            if matches!(x, Event::Edge(_)) {
-                                "edge"
-                            } else {
-                                "split"
-                            },
-                        )
-                    })
-                    .collect::<Vec<_>>()
-            );
+            return Err(SkeletonError::Inconsistent);
         }
+        wavefront.handle(ev);
+        wavefront.collapse_flat_loops();
+    }
 
-        let i = queue.pop().unwrap().0;
-
-        #[cfg(test)]
-        println!("managing event {:?}", i);
-        let (arc, events) = match i {
-            Event::Edge(e) => {
-                if !vs[e.vertex_a.0].valid || !vs[e.vertex_b.0].valid {
-                    #[cfg(test)]
-                    println!("{} Discarded outdated event {:?}", e.distance, e);
-                    continue;
-                }
-                slav.handle_edge_event(&mut vs, &mut lavs, e)
-            }
-            Event::Split(s) => {
-                if !vs[s.vertex.0].valid {
-                    #[cfg(test)]
-                    println!("{} Discarded outdated event {:?}", s.distance, s);
-                    continue;
-                }
-                slav.handle_split_event(&mut vs, &mut lavs, s)
-            }
-        };
-
-        queue.extend(events.into_iter().map(Reverse));
-        output.extend(arc.into_iter());
+    if wavefront.vs.iter().any(|v| v.alive) {
+        return Err(SkeletonError::Inconsistent);
     }
 
+    let mut output = wavefront.output;
     merge_sources(&mut output);
-
-    output
+    Ok(output)
 }
 
+/// Builds the faces of the roof defined by the skeleton of that polygon with holes.
+/// The faces are counter-clockwise with the z coordinate being the height.
 pub fn faces_from_skeleton(
     poly: &[Vec2],
+    holes: &[&[Vec2]],
     skeleton: &[Subtree],
     merge_triangles: bool,
-) -> Option<Vec<Vec<Vec3>>> {
-    let poly = normalize_contour(poly);
+) -> Result<Vec<Vec<Vec3>>> {
+    let contours = normalize_contours(poly, holes)?;
     let mut graph: HashMap<Vec2, Vec<_>> = HashMap::new();
     let mut heights = HashMap::new();
 
-    for (&prev, &p, _) in window(&poly) {
-        graph.entry(p).or_default().push(prev);
-        graph.entry(prev).or_default().push(p);
-        heights.insert(p, 0.0);
+    for contour in &contours {
+        for (&prev, &p, _) in window(contour) {
+            graph.entry(p).or_default().push(prev);
+            graph.entry(prev).or_default().push(p);
+            heights.insert(p, 0.0);
+        }
     }
 
     for tree in skeleton {
         if tree.source.magnitude2() > 1e10 {
-            return None;
+            return Err(SkeletonError::Inconsistent);
         }
         heights.insert(tree.source, tree.height);
         for &v in &tree.sinks {
             if v.magnitude2() > 1e10 {
-                return None;
+                return Err(SkeletonError::Inconsistent);
             }
             graph.entry(tree.source).or_default().push(v);
             graph.entry(v).or_default().push(tree.source);
@@ -947,25 +687,31 @@ pub fn faces_from_skeleton(
 
     if merge_triangles {
         let mut triangles = vec![];
-        for (_, cur, next) in window(&poly) {
-            let top = *graph.get(cur)?.last()?;
-            let top_next = *graph.get(next)?.last()?;
+        for contour in &contours {
+            for (_, cur, next) in window(contour) {
+                let top = *link(graph.get(cur).and_then(|x| x.last()))?;
+                let top_next = *link(graph.get(next).and_then(|x| x.last()))?;
 
-            if top == top_next {
-                triangles.push((top, *cur, *next));
+                if top == top_next {
+                    triangles.push((top, *cur, *next));
+                }
             }
         }
 
         for (top, cur, next) in triangles {
+            // the top might have been merged with another triangle already
+            if !graph.contains_key(&top) {
+                continue;
+            }
             let new_pos = (cur + next) * 0.5;
 
-            graph.get_mut(&cur).unwrap().retain(|&x| x != next);
-            graph.get_mut(&next).unwrap().retain(|&x| x != cur);
+            link(graph.get_mut(&cur))?.retain(|&x| x != next);
+            link(graph.get_mut(&next))?.retain(|&x| x != cur);
 
-            let neighs = graph.remove(&top).unwrap();
+            let neighs = link(graph.remove(&top))?;
 
             for nei in &neighs {
-                for p in graph.get_mut(nei).unwrap() {
+                for p in link(graph.get_mut(nei))? {
                     if *p == top {
                         *p = new_pos;
                     }
@@ -973,7 +719,8 @@ pub fn faces_from_skeleton(
             }
 
             graph.insert(new_pos, neighs);
-            heights.insert(new_pos, heights[&top]);
+            let h = *link(heights.get(&top))?;
+            heights.insert(new_pos, h);
         }
     }
 
@@ -983,38 +730,45 @@ pub fn faces_from_skeleton(
 
     let mut faces = vec![];
     let mut visited = HashSet::new();
+    let n_edges: usize = graph.values().map(|x| x.len()).sum();
 
-    fn next_v(graph: &HashMap<Vec2, Vec<Vec2>>, cur: Vec2, next: Vec2) -> (Vec2, Vec2) {
-        let l = &graph[&next];
-        let i = l.iter().position(|&x| x == cur).unwrap();
+    fn next_v(graph: &HashMap<Vec2, Vec<Vec2>>, cur: Vec2, next: Vec2) -> Result<(Vec2, Vec2)> {
+        let l = link(graph.get(&next))?;
+        let i = link(l.iter().position(|&x| x == cur))?;
         let prev = if i == 0 { l.len() - 1 } else { i - 1 };
-        (next, l[prev])
+        Ok((next, l[prev]))
     }
 
     fn explore(
         graph: &HashMap<Vec2, Vec<Vec2>>,
         visited: &mut HashSet<(Vec2, Vec2)>,
         heights: &HashMap<Vec2, f32>,
+        n_edges: usize,
         start: Vec2,
         mut next: Vec2,
-    ) -> Vec<Vec3> {
-        let mut face = vec![start.z(heights[&start])];
+    ) -> Result<Vec<Vec3>> {
+        let height = |p: Vec2| link(heights.get(&p)).map(|&h| p.z(h));
+        let mut face = vec![height(start)?];
         let mut cur = start;
         visited.insert((cur, next));
         while next != start {
-            let (c, n) = next_v(graph, cur, next);
+            let (c, n) = next_v(graph, cur, next)?;
             cur = c;
             next = n;
-            face.push(cur.z(heights[&cur]));
+            face.push(height(cur)?);
             visited.insert((cur, next));
+            // a face cannot have more edges than the graph, the walk will never end
+            if face.len() > n_edges {
+                return Err(SkeletonError::Inconsistent);
+            }
         }
-        face
+        Ok(face)
     }
 
     for (&node, l) in &graph {
         for &edge in l {
             if !visited.contains(&(node, edge)) {
-                let face = explore(&graph, &mut visited, &heights, node, edge);
+                let face = explore(&graph, &mut visited, &heights, n_edges, node, edge)?;
 
                 let mut sum = 0.0;
                 for (_, cur, next) in window(&face) {
@@ -1025,22 +779,42 @@ pub fn faces_from_skeleton(
                     continue;
                 }
 
+                if face.iter().all(|p| p.z == 0.0) {
+                    // the inside of a hole, a roof face always goes up to the skeleton
+                    continue;
+                }
+
                 faces.push(face);
             }
         }
     }
 
-    if visited.len() != graph.values().map(|x| x.len()).sum() {
-        return None;
+    if visited.len() != n_edges {
+        return Err(SkeletonError::Inconsistent);
     }
 
-    Some(faces)
+    // a skeleton crossing itself gives faces overlapping each other or spanning multiple edges,
+    // when it doesn't there is one face per edge and they add up to the area of the polygon
+    if faces.len() != contours.iter().map(Vec::len).sum::<usize>() {
+        return Err(SkeletonError::Inconsistent);
+    }
+    let area: f32 = contours.iter().map(|c| -signed_area(c)).sum();
+    let faces_area: f32 = faces
+        .iter()
+        .map(|f| signed_area(&f.iter().map(|p| p.xy()).collect::<Vec<_>>()).abs())
+        .sum();
+    if (area - faces_area).abs() > area * 0.001 {
+        return Err(SkeletonError::Inconsistent);
+    }
+
+    Ok(faces)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::skeleton::{faces_from_skeleton, skeleton};
-    use crate::vec2;
+    use crate::skeleton::{clean_contour, faces_from_skeleton, skeleton, SkeletonError};
+    use crate::tests::{star, XorShift};
+    use crate::{vec2, Polygon, Vec2};
     use ordered_float::OrderedFloat;
 
     #[test]
@@ -1060,10 +834,10 @@ mod tests {
 
         dbg!(&poly);
 
-        let skeleton = skeleton(poly, &[]);
+        let skeleton = skeleton(poly, &[]).unwrap();
         assert!(!skeleton.is_empty());
-        let faces = faces_from_skeleton(poly, &skeleton, false).unwrap();
-        assert_eq!(faces.len(), 6);
+        let faces = faces_from_skeleton(poly, &[], &skeleton, false).unwrap();
+        assert_eq!(faces.len(), 10);
     }
 
     #[test]
@@ -1074,10 +848,10 @@ mod tests {
             vec2(20.0, 10.0),
             vec2(0.0, 10.0),
         ];
-        let skeleton = skeleton(poly, &[]);
+        let skeleton = skeleton(poly, &[]).unwrap();
         assert!(!skeleton.is_empty());
-        let faces = faces_from_skeleton(poly, &skeleton, false).unwrap();
-        assert_eq!(faces.len(), 6);
+        let faces = faces_from_skeleton(poly, &[], &skeleton, false).unwrap();
+        assert_eq!(faces.len(), 4);
     }
 
     #[test]
@@ -1096,8 +870,9 @@ mod tests {
         .copied()
         .rev()
         .collect::<Vec<_>>();
-        let skeleton = skeleton(&poly, &[]);
-        let faces = faces_from_skeleton(&poly, &skeleton, false);
+        let skeleton = skeleton(poly.as_slice(), &[]).unwrap();
+        let faces = faces_from_skeleton(poly.as_slice(), &[], &skeleton, false).unwrap();
+        assert_eq!(faces.len(), poly.len());
     }
 
     #[test]
@@ -1180,7 +955,8 @@ mod tests {
                 vec2(169.0, 124.0),
             ],
             &[],
-        );
+        )
+        .unwrap();
 
         assert!(!skeleton.is_empty());
         skeleton.sort_by_key(|x| OrderedFloat(x.source.x));
@@ -1188,4 +964,175 @@ mod tests {
             println!("{:?}", sub);
         }
     }
+
+    fn with_collinear(poly: &[Vec2]) -> Vec<Vec2> {
+        let mut v = vec![];
+        for (i, &p) in poly.iter().enumerate() {
+            v.push(p);
+            v.push((p + poly[(i + 1) % poly.len()]) * 0.5);
+        }
+        v
+    }
+
+    fn with_near_duplicates(poly: &[Vec2]) -> Vec<Vec2> {
+        poly.iter()
+            .flat_map(|&p| std::iter::once(p).chain(std::iter::once(p + vec2(1e-6, 0.0))))
+            .collect()
+    }
+
+    fn l_shape(w: f32, h: f32, t: f32) -> Vec<Vec2> {
+        vec![
+            vec2(0.0, 0.0),
+            vec2(w, 0.0),
+            vec2(w, t),
+            vec2(t, t),
+            vec2(t, h),
+            vec2(0.0, h),
+        ]
+    }
+
+    fn check(poly: &[Vec2], holes: &[&[Vec2]], n_edges: usize) {
+        let skeleton = skeleton(poly, holes)
+            .unwrap_or_else(|e| panic!("skeleton of {:?} failed: {}", poly, e));
+        assert!(!skeleton.is_empty());
+
+        let outer = Polygon(poly.to_vec());
+        for tree in &skeleton {
+            assert!(tree.height > 0.0);
+            // sources are allowed to lie on the contour within floating point error
+            let inside = outer.contains(tree.source)
+                || outer.project(tree.source).distance(tree.source) < 1e-2;
+            assert!(inside, "{:?} is outside of {:?}", tree.source, poly);
+            for hole in holes {
+                assert!(!Polygon(hole.to_vec()).contains(tree.source));
+            }
+        }
+
+        let faces = faces_from_skeleton(poly, holes, &skeleton, false).unwrap();
+        assert_eq!(faces.len(), n_edges);
+    }
+
+    #[test]
+    fn test_corpus_stars() {
+        let mut rng = XorShift(0x2545_f491);
+        for n in 3..16 {
+            for _ in 0..10 {
+                let poly = star(&mut rng, Vec2::ZERO, n, 20.0..100.0);
+                let n = clean_contour(&poly).len();
+                check(&poly, &[], n);
+                check(&with_collinear(&poly), &[], n);
+                check(&with_near_duplicates(&poly), &[], n);
+
+                let rev: Vec<_> = poly.iter().rev().copied().collect();
+                check(&rev, &[], n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_corpus_holes() {
+        let mut rng = XorShift(0x9e37_79b9);
+        for n in 3..16 {
+            for _ in 0..10 {
+                let poly = star(&mut rng, Vec2::ZERO, n, 20.0..100.0);
+                let n = clean_contour(&poly).len();
+
+                // as big as possible while staying away from the contour
+                let outer = Polygon(poly.clone());
+                let r = outer.project(Vec2::ZERO).magnitude() * 0.5;
+                let square = [vec2(-r, -r), vec2(r, -r), vec2(r, r), vec2(-r, r)];
+                let triangle = [vec2(-r, 0.0), vec2(0.0, -r), vec2(r * 0.7, r * 0.7)];
+                check(&poly, &[&square], n + 4);
+                check(&with_collinear(&poly), &[&triangle], n + 3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_corpus_rectilinear() {
+        // columns of random heights, lots of edges meet at the same time
+        let mut rng = XorShift(0x85eb_ca6b);
+        for k in 1..12 {
+            for _ in 0..20 {
+                let mut poly = vec![vec2(0.0, 0.0), vec2(k as f32, 0.0)];
+                for i in (0..k).rev() {
+                    let h = 1.0 + (rng.next() * 5.0).floor();
+                    poly.push(vec2(i as f32 + 1.0, h));
+                    poly.push(vec2(i as f32, h));
+                }
+                let n = clean_contour(&poly).len();
+                check(&poly, &[], n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_corpus_l_shapes() {
+        for &(w, h, t) in &[(10.0, 10.0, 3.0), (30.0, 8.0, 2.0), (5.0, 40.0, 4.5)] {
+            let poly = l_shape(w, h, t);
+            check(&poly, &[], 6);
+            check(&with_collinear(&poly), &[], 6);
+        }
+    }
+
+    #[test]
+    fn test_hole() {
+        let poly = &[
+            vec2(0.0, 0.0),
+            vec2(30.0, 0.0),
+            vec2(30.0, 20.0),
+            vec2(0.0, 20.0),
+        ];
+        let hole: &[Vec2] = &[
+            vec2(10.0, 5.0),
+            vec2(20.0, 5.0),
+            vec2(20.0, 15.0),
+            vec2(10.0, 15.0),
+        ];
+        check(poly, &[hole], 8);
+
+        let rev: Vec<_> = hole.iter().rev().copied().collect();
+        check(poly, &[&rev], 8);
+    }
+
+    #[test]
+    fn test_degenerate() {
+        let line = &[vec2(0.0, 0.0), vec2(5.0, 0.0), vec2(10.0, 0.0)];
+        assert_eq!(
+            skeleton(line, &[]).unwrap_err(),
+            SkeletonError::DegenerateContour
+        );
+
+        let dup = &[vec2(0.0, 0.0), vec2(0.0, 0.0), vec2(1.0, 1.0)];
+        assert_eq!(
+            skeleton(dup, &[]).unwrap_err(),
+            SkeletonError::DegenerateContour
+        );
+
+        let nan = &[vec2(0.0, 0.0), vec2(f32::NAN, 0.0), vec2(1.0, 1.0)];
+        assert_eq!(skeleton(nan, &[]).unwrap_err(), SkeletonError::NonFinite);
+
+        let bowtie = &[
+            vec2(0.0, 0.0),
+            vec2(10.0, 10.0),
+            vec2(10.0, 0.0),
+            vec2(0.0, 20.0),
+        ];
+        assert_eq!(
+            skeleton(bowtie, &[]).unwrap_err(),
+            SkeletonError::SelfIntersecting
+        );
+
+        let square = &[
+            vec2(0.0, 0.0),
+            vec2(10.0, 0.0),
+            vec2(10.0, 10.0),
+            vec2(0.0, 10.0),
+        ];
+        let far: &[Vec2] = &[vec2(20.0, 20.0), vec2(21.0, 20.0), vec2(21.0, 21.0)];
+        assert_eq!(
+            skeleton(square, &[far]).unwrap_err(),
+            SkeletonError::HoleOutside
+        );
+    }
 }
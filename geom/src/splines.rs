@@ -1,8 +1,21 @@
 use super::Vec2;
 use crate::polyline::PolyLine;
+use crate::Segment;
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 
+/// Nodes and weights of the 8 points Gauss-Legendre quadrature on [-1; 1]
+const GAUSS_LEGENDRE: [(f32, f32); 8] = [
+    (-0.960_289_86, 0.101_228_54),
+    (-0.796_666_5, 0.222_381_03),
+    (-0.525_532_4, 0.313_706_64),
+    (-0.183_434_64, 0.362_683_8),
+    (0.183_434_64, 0.362_683_8),
+    (0.525_532_4, 0.313_706_64),
+    (0.796_666_5, 0.222_381_03),
+    (0.960_289_86, 0.101_228_54),
+];
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Spline {
     pub from: Vec2,
@@ -163,6 +176,127 @@ impl Spline {
         PolyLine::new(self.smart_points(detail, 0.0, 1.0).collect()).length()
     }
 
+    /// Length of the curve between t0 and t1, without approximating it by segments
+    pub fn length_between(&self, t0: f32, t1: f32) -> f32 {
+        // the speed varies a lot on tight curves so the quadrature is done in a few pieces
+        const PIECES: usize = 4;
+        let h = (t1 - t0) / PIECES as f32;
+        (0..PIECES)
+            .map(|i| {
+                let mid = t0 + h * (i as f32 + 0.5);
+                GAUSS_LEGENDRE
+                    .iter()
+                    .map(|&(x, w)| w * self.derivative(mid + h * 0.5 * x).magnitude())
+                    .sum::<f32>()
+                    * h
+                    * 0.5
+            })
+            .sum()
+    }
+
+    pub fn arc_length(&self) -> f32 {
+        self.length_between(0.0, 1.0)
+    }
+
+    /// Inverse of the arc length: the t at which the distance along the curve is d.
+    /// Distances outside of the curve are clamped to its ends.
+    pub fn t_at_distance(&self, d: f32) -> f32 {
+        let total = self.arc_length();
+        if d <= 0.0 || total <= 0.0 {
+            return 0.0;
+        }
+        if d >= total {
+            return 1.0;
+        }
+
+        // newton's method, falling back to bisection when it goes out of the bracket
+        let (mut lo, mut hi) = (0.0, 1.0);
+        let mut t = d / total;
+        for _ in 0..20 {
+            let err = self.length_between(0.0, t) - d;
+            if err.abs() <= total * 1e-5 {
+                break;
+            }
+            if err > 0.0 {
+                hi = t;
+            } else {
+                lo = t;
+            }
+            let next = t - err / self.derivative(t).magnitude();
+            t = if next > lo && next < hi {
+                next
+            } else {
+                (lo + hi) * 0.5
+            };
+        }
+        t
+    }
+
+    pub fn point_at_distance(&self, d: f32) -> Vec2 {
+        self.get(self.t_at_distance(d))
+    }
+
+    /// Signed curvature at t, positive when turning left. The turning radius is its inverse.
+    pub fn curvature(&self, t: f32) -> f32 {
+        let d = self.derivative(t);
+        let speed = d.magnitude();
+        if speed <= f32::EPSILON {
+            return 0.0;
+        }
+        d.cross(self.derivative_2(t)) / (speed * speed * speed)
+    }
+
+    /// Highest absolute curvature along the curve
+    pub fn max_curvature(&self) -> f32 {
+        const SAMPLES: usize = 32;
+        let k = |t: f32| OrderedFloat(self.curvature(t).abs());
+        let h = 1.0 / SAMPLES as f32;
+        let best = (0..=SAMPLES)
+            .map(|i| i as f32 * h)
+            .max_by_key(|&t| k(t))
+            .unwrap(); // Unwrap ok: not empty
+
+        // golden section search around the best sample
+        let phi = 0.5 * (5.0f32.sqrt() - 1.0);
+        let (mut a, mut b) = ((best - h).max(0.0), (best + h).min(1.0));
+        for _ in 0..20 {
+            let c = b - (b - a) * phi;
+            let d = a + (b - a) * phi;
+            if k(c) > k(d) {
+                b = d;
+            } else {
+                a = c;
+            }
+        }
+        k(best).max(k((a + b) * 0.5)).0
+    }
+
+    /// Approximates the curve by a polyline, every point of the curve is within tolerance of it.
+    /// Straight parts use few points while tight curves get a lot of them.
+    pub fn flatten(&self, tolerance: f32) -> PolyLine {
+        let mut points = vec![self.from];
+        self.flatten_into(tolerance, 0, &mut points);
+        PolyLine::new(points)
+    }
+
+    fn flatten_into(&self, tolerance: f32, depth: u32, points: &mut Vec<Vec2>) {
+        // the curve is inside the hull of its control points, so it is close enough to the chord
+        // when they are
+        let chord = Segment::new(self.from, self.to);
+        let c1 = self.from + self.from_derivative;
+        let c2 = self.to - self.to_derivative;
+        if depth >= 16
+            || (chord.project(c1).distance(c1) <= tolerance
+                && chord.project(c2).distance(c2) <= tolerance)
+        {
+            points.push(self.to);
+            return;
+        }
+        let (a, b) = self.split_at(0.5);
+        a.flatten_into(tolerance, depth + 1, points);
+        b.flatten_into(tolerance, depth + 1, points);
+    }
+
     fn step(&self, t: f32, detail: f32) -> f32 {
         let dot = self
             .derivative(t)
@@ -192,3 +326,135 @@ impl<'a> Iterator for SmartPoints<'a> {
         Some(self.t)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::any_vec2;
+    use crate::{vec2, Spline, Vec2};
+    use proptest::prelude::*;
+
+    fn quarter_circle(r: f32) -> Spline {
+        // usual approximation of a circle by bezier curves
+        let k = 0.552_284_8;
+        Spline {
+            from: vec2(r, 0.0),
+            to: vec2(0.0, r),
+            from_derivative: vec2(0.0, k * r),
+            to_derivative: vec2(-k * r, 0.0),
+        }
+    }
+
+    #[test]
+    fn test_arc_length() {
+        let s = quarter_circle(10.0);
+        let expected = std::f32::consts::FRAC_PI_2 * 10.0;
+        assert!((s.arc_length() - expected).abs() < 0.01);
+        assert!((s.arc_length() - s.length(0.01)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_point_at_distance() {
+        // t is far from proportional to the distance when the derivatives are unbalanced
+        let s = Spline {
+            from: Vec2::ZERO,
+            to: vec2(30.0, 0.0),
+            from_derivative: vec2(1.0, 0.0),
+            to_derivative: vec2(25.0, 0.0),
+        };
+        assert!((s.arc_length() - 30.0).abs() < 1e-3);
+        for i in 0..=30 {
+            let d = i as f32;
+            assert!(s.point_at_distance(d).distance(vec2(d, 0.0)) < 1e-3);
+        }
+        assert_eq!(s.point_at_distance(-5.0), s.from);
+        assert_eq!(s.point_at_distance(50.0), s.to);
+
+        let s = quarter_circle(10.0);
+        for i in 0..10 {
+            let d = i as f32 * 1.5;
+            let t = s.t_at_distance(d);
+            assert!((s.length_between(0.0, t) - d).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_curvature() {
+        let s = quarter_circle(10.0);
+        for i in 0..=10 {
+            let k = s.curvature(i as f32 / 10.0);
+            assert!((k - 0.1).abs() < 0.003, "{}", k);
+        }
+        assert!((s.max_curvature() - 0.1).abs() < 0.003);
+
+        let mut rev = s;
+        std::mem::swap(&mut rev.from, &mut rev.to);
+        rev.from_derivative = -s.to_derivative;
+        rev.to_derivative = -s.from_derivative;
+        assert!((rev.curvature(0.5) + 0.1).abs() < 0.003);
+
+        let straight = Spline {
+            from: Vec2::ZERO,
+            to: vec2(10.0, 0.0),
+            from_derivative: vec2(3.0, 0.0),
+            to_derivative: vec2(3.0, 0.0),
+        };
+        assert_eq!(straight.max_curvature(), 0.0);
+    }
+
+    #[test]
+    fn test_flatten() {
+        let s = Spline {
+            from: Vec2::ZERO,
+            to: vec2(100.0, 0.0),
+            from_derivative: vec2(0.0, 80.0),
+            to_derivative: vec2(50.0, -20.0),
+        };
+        let fine = s.flatten(0.01);
+        let coarse = s.flatten(1.0);
+        assert!(coarse.n_points() < fine.n_points());
+        assert_eq!(fine.first(), s.from);
+        assert_eq!(fine.last(), s.to);
+
+        for i in 0..=1000 {
+            let p = s.get(i as f32 / 1000.0);
+            assert!(fine.project_dist(p) <= 0.01 + 1e-4);
+            assert!(coarse.project_dist(p) <= 1.0 + 1e-4);
+        }
+    }
+
+    fn spline() -> impl Strategy<Value = Spline> {
+        (
+            any_vec2(100.0),
+            any_vec2(100.0),
+            any_vec2(50.0),
+            any_vec2(50.0),
+        )
+            .prop_map(|(from, to, from_derivative, to_derivative)| Spline {
+                from,
+                to,
+                from_derivative,
+                to_derivative,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn split_at_continuous(s in spline(), t in 0.01f32..0.99, u in 0.0f32..1.0) {
+            let close = |a: Vec2, b: Vec2| a.distance(b) < 1e-2;
+            let (l, r) = s.split_at(t);
+
+            prop_assert_eq!(l.from, s.from);
+            prop_assert_eq!(r.to, s.to);
+            prop_assert_eq!(l.to, r.from);
+            prop_assert!(close(l.to, s.get(t)));
+
+            // Both halves follow the original curve
+            prop_assert!(close(l.get(u), s.get(u * t)));
+            prop_assert!(close(r.get(u), s.get(t + u * (1.0 - t))));
+
+            // and keep its tangent, up to the reparametrization
+            prop_assert!(close(l.derivative(1.0) / t, s.derivative(t)));
+            prop_assert!(close(r.derivative(0.0) / (1.0 - t), s.derivative(t)));
+        }
+    }
+}
@@ -0,0 +1,25 @@
+--- Same shape as the builtin workplace: a rectangle with two wings.
+--- Only the scripts directly in lua/buildings are loaded, copy it there to use it.
+register_building("Workplace", function(size)
+    local a = rand_in(15.0, 20.0)
+    local b = rand_in(15.0, 20.0)
+
+    local width = math.max(a, b) * (size / 40.0) * 1.5
+    local height = math.min(a, b) * (size / 40.0)
+
+    local p = poly_rect(width, height)
+    local corn_coeff = rand_in(0.2, 0.3)
+
+    p:split_segment(0, corn_coeff)
+    p:split_segment(1, 1.0 - corn_coeff / (1.0 - corn_coeff))
+    local extrude = rand_in(height * 0.3, height * 0.4)
+    p:extrude(2, extrude)
+    p:extrude(0, extrude)
+
+    p:translate(-p:barycenter())
+    local p3 = p:point(3)
+    local p4 = p:point(4)
+    local door = vec2((p3:x() + p4:x()) * 0.5, (p3:y() + p4:y()) * 0.5)
+
+    return { { p, color(0.48, 0.48, 0.5, 1.0) } }, door
+end)
@@ -42,6 +42,15 @@ function Cleanup()
     end
 end
 
+--- Keeps the cars when the scenario is reloaded
+function Persist()
+    return cartest.cars
+end
+
+function Restore(cars)
+    cartest.cars = cars
+end
+
 up = vec2(0.0, 1.0)
 down = vec2(0.0, -1.0)
 left = vec2(-1.0, 0.0)
@@ -0,0 +1,21 @@
+--- In the evening, souls walk to the closest supermarket to hang around.
+--- Only the scripts directly in lua/desires are loaded, copy it there to use it.
+register_desire("leisure", function(soul)
+    local hour = soul:hour()
+    if hour >= 19 and hour < 21 then
+        return 0.3
+    end
+    soul:set("leisure_target", nil)
+    return -1.0
+end, function(soul)
+    local target = soul:get("leisure_target")
+    if target == nil then
+        target = soul:nearest_building("Supermarket")
+        if target == nil then
+            return
+        end
+        soul:set("leisure_target", target)
+    end
+    soul:use_car(false)
+    soul:go_to(target)
+end)
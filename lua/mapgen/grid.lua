@@ -0,0 +1,23 @@
+--- Same layout as the test field: a grid of two-way roads
+register_generator("grid", function(gen)
+    local size = 10
+    local spacing = 100.0
+    local grid = {}
+    for y = 1, size do
+        grid[y] = {}
+        for x = 1, size do
+            grid[y][x] = gen:add_intersection(vec2(x * spacing, 350.0 + y * spacing))
+        end
+    end
+
+    for y = 1, size do
+        for x = 1, size do
+            if x < size then
+                gen:connect(grid[y][x], grid[y][x + 1])
+            end
+            if y < size then
+                gen:connect(grid[y][x], grid[y + 1][x])
+            end
+        end
+    end
+end)
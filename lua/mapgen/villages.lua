@@ -0,0 +1,58 @@
+local function middle(a, b)
+    return vec2((a:x() + b:x()) * 0.5, (a:y() + b:y()) * 0.5)
+end
+
+--- Small villages on the lowlands, each a ring of one-way roads crossed by a main street
+register_generator("villages", function(gen)
+    local n_villages = 6
+    local placed = {}
+    local gates = {}
+    local tries = 0
+
+    while #placed < n_villages and tries < 100 do
+        tries = tries + 1
+        local center = gen:random_vec2(vec2(-1500.0, -1500.0), vec2(1500.0, 1500.0))
+
+        local far_enough = true
+        for _, other in ipairs(placed) do
+            if other:distance(center) < 600.0 then
+                far_enough = false
+            end
+        end
+
+        -- villages are built where the land is low
+        if far_enough and gen:height(center) < 0.3 then
+            placed[#placed + 1] = center
+
+            local radius = gen:random_in(120.0, 200.0)
+            local n = 8
+            local ring = {}
+            local pos = {}
+            for i = 0, n - 1 do
+                local ang = 2.0 * math.pi * i / n
+                pos[#pos + 1] = center + vec2(math.cos(ang) * radius, math.sin(ang) * radius)
+                ring[#ring + 1] = gen:add_intersection(pos[#pos])
+            end
+            local roads = {}
+            for i = 1, n do
+                roads[i] = gen:connect(ring[i], ring[i % n + 1], { one_way = true, parking = false })
+            end
+
+            -- the main street crosses the ring through the middle
+            local opposite = n // 2 + 1
+            local a = gen:split_road(roads[1], middle(pos[1], pos[2]))
+            local b = gen:split_road(roads[opposite], middle(pos[opposite], pos[opposite + 1]))
+            if a ~= nil and b ~= nil then
+                local mid = gen:add_intersection(center)
+                gen:connect(a, mid, { n_lanes = 2 })
+                gen:connect(mid, b, { n_lanes = 2 })
+            end
+            gates[#gates + 1] = ring[3]
+        end
+    end
+
+    -- link each village to the previous one
+    for i = 2, #gates do
+        gen:connect(gates[i - 1], gates[i], { n_lanes = 2, parking = false })
+    end
+end)
@@ -1,24 +1,122 @@
 ---
---- Generated by EmmyLua(https://github.com/EmmyLua)
---- Created by paris.
---- DateTime: 11/08/2020 11:05
+--- EmmyLua stubs of the functions exposed to the scripts, generated from their LuaApi
+--- Run the stdlib_is_generated test with UPDATE_STDLIB=1 to regenerate it
 ---
 
----@class World
-world = world
+---@type World
+world = nil
 
----@class Draw
-draw = draw
+---@type Draw
+draw = nil
 
----@class Vec2
 ---@class Entity
 ---@class Color
+---@class Intersection
+---@class Road
+---@class Lot
+---@class Building
+
+---@class Time
+---@field timestamp number
+---@field delta number
+---@field seconds number
+---@field day number
+---@field hour number
+---@field second number
+
+---@class LanePattern
+---@field n_lanes number
+---@field sidewalks boolean
+---@field parking boolean
+---@field one_way boolean
+
+---@class ScenarioExpected
+---@field time number|nil maximum seconds until Success() returns true
+---@field gridlocks number|nil maximum number of gridlocks
+
+--- Optional table a scenario declares to configure how it is run
+---@class ScenarioConfig
+---@field timeout number|nil seconds after which the scenario fails, 300 by default
+---@field map string|nil "empty" | "testfield" | "paris", the current map is kept if nil
+---@field map_file string|nil path to a saved map, takes precedence over map
+---@field expected ScenarioExpected|nil
+
+--- Hooks a scenario can define, all optional:
+--- Init() after the map is loaded
+--- OnTick(dt) every tick
+--- Draw() every tick, to draw debug shapes
+--- Fail() every tick, the scenario fails when it returns true
+--- Success() every tick, the scenario succeeds when it returns true and the expectations are met
+--- Cleanup() when the scenario ends or is replaced
+--- Persist() when the script changes on disk, returns data given to Restore
+--- Restore(data) instead of Init() when the script is reloaded, only if both are defined
 
 ---@param x number
 ---@param y number
 ---@return Vec2
 function vec2(x, y) end
 
+---@param w number
+---@param h number
+---@return Polygon
+function poly_rect(w, h) end
+
+---@param min number
+---@param max number
+---@return number
+function rand_in(min, max) end
+
+---@class Vec2
+local Vec2 = {}
+
+---@return number
+function Vec2:magnitude() end
+
+--- Returns the unit x vector if the vector is zero
+---@return Vec2
+function Vec2:normalize() end
+
+---@param other Vec2
+---@return number
+function Vec2:distance(other) end
+
+---@return number
+function Vec2:x() end
+
+---@return number
+function Vec2:y() end
+
+---@class Polygon
+local Polygon = {}
+
+---@return number
+function Polygon:len() end
+
+--- 0-indexed, like segments
+---@param i number
+---@return Vec2|nil
+function Polygon:point(i) end
+
+--- Moves the i-th segment outwards by dist
+---@param i number
+---@param dist number
+function Polygon:extrude(i, dist) end
+
+--- Inserts a point on the i-th segment, coeff being between 0 and 1
+---@param i number
+---@param coeff number
+function Polygon:split_segment(i, coeff) end
+
+---@return Vec2
+function Polygon:barycenter() end
+
+---@param v Vec2
+function Polygon:translate(v) end
+
+--- Rotates around the origin by the angle whose cosine and sine are the x and y of cossin
+---@param cossin Vec2
+function Polygon:rotate(cossin) end
+
 ---@param r number
 ---@param g number
 ---@param b number
@@ -26,23 +124,222 @@ function vec2(x, y) end
 ---@return Color
 function color(r, g, b, a) end
 
----@param self Vec2
----@return number
-function magnitude(self) end
+--- Registers a function to be called every tick with the time elapsed since the last one
+---@param f fun(dt: number)
+function on_tick(f) end
+
+---@class Draw
+local Draw = {}
 
----@param draw Draw
 ---@param pos Vec2
 ---@param radius number
-function draw.circle(draw, pos, radius) end
+function Draw:circle(pos, radius) end
 
----@param world World
----@param e Entity
----@return Vec2
-function world.pos(world, e) end
+--- Color of the shapes drawn afterwards, white by default
+---@param col Color
+function Draw:color(col) end
+
+---@class World
+local World = {}
 
----@param world World
 ---@param pos Vec2
 ---@param dir Vec2
 ---@param objective Vec2
 ---@return Entity
-function world.add_car(world, pos, dir, objective) end
\ No newline at end of file
+function World:add_car(pos, dir, objective) end
+
+---@param e Entity
+---@return Vec2|nil
+function World:pos(e) end
+
+---@param e Entity
+function World:remove(e) end
+
+---@return Time
+function World:time() end
+
+--- Capital of the soul in the given commodity, e.g "Bread"
+---@param soul Entity
+---@param commodity string
+---@return number
+function World:capital(soul, commodity) end
+
+---@return Intersection[]
+function World:intersections() end
+
+---@param i Intersection
+---@return Vec2|nil
+function World:intersection_pos(i) end
+
+---@param pos Vec2
+---@return Intersection|nil
+function World:nearest_intersection(pos) end
+
+---@return Road[]
+function World:roads() end
+
+---@param road Road
+---@return Intersection, Intersection
+function World:road_ends(road) end
+
+---@param pos Vec2
+---@param radius number
+---@return Lot[]
+function World:lots_around(pos, radius) end
+
+---@param pos Vec2
+---@param radius number
+---@return Building[]
+function World:buildings_around(pos, radius) end
+
+---@param b Building
+---@return string|nil
+function World:building_kind(b) end
+
+---@param pos Vec2
+---@return Intersection
+function World:add_intersection(pos) end
+
+--- Returns nil if the intersections are the same or do not exist
+---@param src Intersection
+---@param dst Intersection
+---@param pattern LanePattern|nil
+---@return Road|nil
+function World:connect(src, dst, pattern) end
+
+---@param road Road
+---@return boolean
+function World:remove_road(road) end
+
+---@param i Intersection
+function World:remove_intersection(i) end
+
+---@param lot Lot
+---@param kind string "unassigned" | "residential" | "commercial"
+function World:set_lot_kind(lot, kind) end
+
+--- Builds houses on the residential lots
+---@return Building[]
+function World:build_houses() end
+
+---@param house Building
+---@return Entity|nil
+function World:spawn_human(house) end
+
+--- Spawns the company matching the kind of the building
+---@param building Building
+---@return Entity|nil
+function World:spawn_company(building) end
+
+--- Map generators, registered by the scripts of lua/mapgen
+
+--- Registers a generator shown in the Map window, f is called with a MapGen on a cleared map
+---@param name string
+---@param f fun(gen: MapGen)
+function register_generator(name, f) end
+
+---@class MapGen
+local MapGen = {}
+
+---@param pos Vec2
+---@return Intersection
+function MapGen:add_intersection(pos) end
+
+---@param src Intersection
+---@param dst Intersection
+---@param pattern LanePattern|nil
+---@return Road|nil
+function MapGen:connect(src, dst, pattern) end
+
+--- Splits the road in two at pos, returning the new intersection
+---@param road Road
+---@param pos Vec2
+---@return Intersection|nil
+function MapGen:split_road(road, pos) end
+
+---@param i Intersection
+---@return Vec2|nil
+function MapGen:intersection_pos(i) end
+
+--- Terrain height between 0 and 1
+---@param pos Vec2
+---@return number
+function MapGen:height(pos) end
+
+---@return number
+function MapGen:seed() end
+
+--- Seeded random number between 0 and 1
+---@return number
+function MapGen:random() end
+
+---@param min number
+---@param max number
+---@return number
+function MapGen:random_in(min, max) end
+
+--- Seeded random position in the rectangle between min and max
+---@param min Vec2
+---@param max Vec2
+---@return Vec2
+function MapGen:random_vec2(min, max) end
+
+--- Building shapes, registered by the scripts of lua/buildings
+
+--- Replaces the builtin shape of a building kind, f returns the faces of the building centered
+--- on the origin as a list of { Polygon, Color } and the door position
+---@param kind string "House" | "Workplace" | "Supermarket" | "CerealFarm" | ...
+---@param f fun(size: number): table, Vec2
+function register_building(kind, f) end
+
+--- Desires, registered by the scripts of lua/desires
+
+--- Adds a desire to every human. Each tick the desire with the highest score is applied.
+--- The builtin ones score 0.2 for going home, 0.5 for working hours, and grow with hunger.
+---@param name string
+---@param score fun(soul: Soul): number
+---@param apply fun(soul: Soul)
+function register_desire(name, score, apply) end
+
+---@class Soul
+local Soul = {}
+
+---@return Vec2
+function Soul:pos() end
+
+---@return number
+function Soul:hour() end
+
+---@return number
+function Soul:seconds() end
+
+--- Remembered values are kept across ticks and saves
+---@param key string
+---@return number|boolean|string|Building|nil
+function Soul:get(key) end
+
+---@param key string
+---@param value number|boolean|string|Building|nil
+function Soul:set(key, value) end
+
+--- Returns true once the soul arrived
+---@param building Building
+---@return boolean
+function Soul:go_to(building) end
+
+---@param use_car boolean
+function Soul:use_car(use_car) end
+
+--- Puts a buy order on the market, see Soul:bought for the result
+---@param commodity string
+---@param quantity number
+function Soul:buy(commodity, quantity) end
+
+--- Buildings of the sellers of the trades made since last call
+---@param commodity string
+---@return Building[]
+function Soul:bought(commodity) end
+
+---@param kind string
+---@return Building|nil
+function Soul:nearest_building(kind) end
@@ -0,0 +1,67 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use map_model::{
+    CarPath, LaneID, LaneKind, Map, Pathfinder, PedestrianPath, Traversable, TraverseDirection,
+    TraverseKind,
+};
+
+/// Loads the Paris preset, which is read relative to the workspace root
+fn paris() -> Map {
+    std::env::set_current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/.."))
+        .expect("couldn't go to the workspace root");
+    let mut map = Map::empty();
+    map_model::procgen::load_parismap(&mut map);
+    assert!(!map.lanes().is_empty(), "Paris preset is empty");
+    map
+}
+
+/// Up to n (start, end) pairs of lanes of this kind spread over the map that are connected
+fn trips(map: &Map, pf: &impl Pathfinder, kind: LaneKind, n: usize) -> Vec<(Traversable, LaneID)> {
+    let lanes: Vec<LaneID> = map
+        .lanes()
+        .iter()
+        .filter(|(_, l)| l.kind == kind)
+        .map(|(id, _)| id)
+        .collect();
+
+    let stride = (lanes.len() / (4 * n)).max(1);
+    (0..lanes.len())
+        .step_by(stride)
+        .map(|i| {
+            let start = Traversable::new(TraverseKind::Lane(lanes[i]), TraverseDirection::Forward);
+            (start, lanes[(i + lanes.len() / 2) % lanes.len()])
+        })
+        .filter(|&(start, end)| pf.path(map, start, end).is_some())
+        .take(n)
+        .collect()
+}
+
+fn bench_paths(c: &mut Criterion) {
+    let map = paris();
+    let mut group = c.benchmark_group("pathfinding paris");
+    group.sample_size(20);
+
+    let car_trips = trips(&map, &CarPath, LaneKind::Driving, 10);
+    assert!(!car_trips.is_empty(), "no connected driving lanes");
+    group.bench_function("car", |b| {
+        b.iter(|| {
+            for &(start, end) in &car_trips {
+                black_box(CarPath.path(&map, start, end));
+            }
+        })
+    });
+
+    let ped_trips = trips(&map, &PedestrianPath, LaneKind::Walking, 10);
+    assert!(!ped_trips.is_empty(), "no connected walking lanes");
+    group.bench_function("pedestrian", |b| {
+        b.iter(|| {
+            for &(start, end) in &ped_trips {
+                black_box(PedestrianPath.path(&map, start, end));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_paths);
+criterion_main!(benches);
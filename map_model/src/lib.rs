@@ -48,6 +48,7 @@ mod light_policy;
 mod map;
 mod pathfinding;
 mod serializing;
+mod signal_plan;
 mod spatial_map;
 mod traffic_control;
 mod traversable;
@@ -58,6 +59,7 @@ pub use self::pathfinding::*;
 pub use light_policy::*;
 pub use map::*;
 pub use serializing::*;
+pub use signal_plan::*;
 pub use spatial_map::*;
 pub use traffic_control::*;
 pub use traversable::*;
@@ -1,8 +1,9 @@
-use crate::{Intersection, LaneID, Lanes, Roads, TrafficControl, TrafficLightSchedule};
+use crate::{Intersection, LaneID, Lanes, RoadID, Roads, TrafficControl, TrafficLightSchedule};
 use imgui_inspect::{
     imgui::{im_str, Ui},
     InspectArgsDefault, InspectRenderDefault,
 };
+use imgui_inspect_derive::*;
 use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 
@@ -12,6 +13,7 @@ pub enum LightPolicy {
     StopSigns,
     Lights,
     Smart,
+    Manual,
 }
 
 impl Default for LightPolicy {
@@ -20,23 +22,68 @@ impl Default for LightPolicy {
     }
 }
 
+/// Makes the given road turn green at `start` (modulo the cycle length), used to make green waves
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct LightCoordination {
+    pub road: RoadID,
+    pub start: i32,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, Inspect)]
+pub struct LightTiming {
+    /// Green duration of each phase in seconds, orange included
+    #[inspect(min_value = 5.0, max_value = 60.0)]
+    pub cycle_size: usize,
+    #[inspect(min_value = 1.0, max_value = 10.0)]
+    pub orange_length: usize,
+    /// Extend the green while vehicles are detected on the incoming lanes
+    pub actuated: bool,
+    /// Maximum number of seconds a green can be extended by when actuated
+    #[inspect(min_value = 0.0, max_value = 60.0)]
+    pub max_extension: u32,
+    #[inspect(skip = true)]
+    pub coordination: Option<LightCoordination>,
+}
+
+impl Default for LightTiming {
+    fn default() -> Self {
+        Self {
+            cycle_size: 14,
+            orange_length: 4,
+            actuated: false,
+            max_extension: 10,
+            coordination: None,
+        }
+    }
+}
+
+impl LightTiming {
+    /// Length of a full cycle of the lights of an intersection with that many incoming roads
+    pub fn total_length(&self, n_incoming_roads: usize) -> usize {
+        self.cycle_size * ((n_incoming_roads + 1) / 2)
+    }
+}
+
 impl LightPolicy {
     pub fn apply(self, inter: &Intersection, lanes: &mut Lanes, roads: &Roads) {
-        let in_road_lanes: Vec<Vec<LaneID>> = inter
+        let in_road_lanes: Vec<(RoadID, Vec<LaneID>)> = inter
             .roads
             .iter()
             .map(|&x| {
-                roads[x]
-                    .incoming_lanes_to(inter.id)
-                    .iter()
-                    .filter(|(_, kind)| kind.needs_light())
-                    .map(|&(id, _)| id)
-                    .collect::<Vec<_>>()
+                (
+                    x,
+                    roads[x]
+                        .incoming_lanes_to(inter.id)
+                        .iter()
+                        .filter(|(_, kind)| kind.needs_light())
+                        .map(|&(id, _)| id)
+                        .collect::<Vec<_>>(),
+                )
             })
-            .filter(|v| !v.is_empty())
+            .filter(|(_, v)| !v.is_empty())
             .collect();
 
-        for incoming_lanes in &in_road_lanes {
+        for (_, incoming_lanes) in &in_road_lanes {
             for &lane in incoming_lanes {
                 lanes[lane].control = TrafficControl::Always;
             }
@@ -50,6 +97,13 @@ impl LightPolicy {
             LightPolicy::Lights => {
                 self.lights(in_road_lanes, inter, lanes);
             }
+            LightPolicy::Manual => {
+                for (_, incoming_lanes) in in_road_lanes {
+                    for lane in incoming_lanes {
+                        lanes[lane].control = TrafficControl::Plan;
+                    }
+                }
+            }
             LightPolicy::Smart => {
                 if in_road_lanes.len() <= 2 {
                     return;
@@ -64,26 +118,41 @@ impl LightPolicy {
         }
     }
 
-    fn stop_signs(self, in_road_lanes: Vec<Vec<LaneID>>, lanes: &mut Lanes) {
-        for incoming_lanes in in_road_lanes {
+    fn stop_signs(self, in_road_lanes: Vec<(RoadID, Vec<LaneID>)>, lanes: &mut Lanes) {
+        for (_, incoming_lanes) in in_road_lanes {
             for lane in incoming_lanes {
                 lanes[lane].control = TrafficControl::StopSign;
             }
         }
     }
 
-    fn lights(self, in_road_lanes: Vec<Vec<LaneID>>, inter: &Intersection, lanes: &mut Lanes) {
+    fn lights(
+        self,
+        in_road_lanes: Vec<(RoadID, Vec<LaneID>)>,
+        inter: &Intersection,
+        lanes: &mut Lanes,
+    ) {
+        let timing = &inter.light_timing;
         let n_cycles = (in_road_lanes.len() + 1) / 2;
-        let cycle_size = 14;
-        let orange_length = 4;
+        let cycle_size = timing.cycle_size.max(timing.orange_length + 1);
+        let orange_length = timing.orange_length;
 
         let total_length = cycle_size * n_cycles;
 
-        let offset = inter.id.as_ffi();
-        let inter_offset: usize =
-            rand::rngs::SmallRng::seed_from_u64(offset as u64).gen_range(0..total_length);
+        let coordinated = timing.coordination.and_then(|c| {
+            let i = in_road_lanes.iter().position(|(r, _)| *r == c.road)?;
+            // the road's light turns green when (seconds + offset) % total_length == 0
+            let phase_start =
+                cycle_size * (i % n_cycles) + c.start.rem_euclid(total_length as i32) as usize;
+            Some((total_length - phase_start % total_length) % total_length)
+        });
+
+        let inter_offset: usize = coordinated.unwrap_or_else(|| {
+            let offset = inter.id.as_ffi();
+            rand::rngs::SmallRng::seed_from_u64(offset as u64).gen_range(0..total_length)
+        });
 
-        for (i, incoming_lanes) in in_road_lanes.into_iter().enumerate() {
+        for (i, (_, incoming_lanes)) in in_road_lanes.into_iter().enumerate() {
             let light = TrafficControl::Light(TrafficLightSchedule::from_basic(
                 cycle_size - orange_length,
                 orange_length,
@@ -118,6 +187,7 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
             LightPolicy::StopSigns => 1,
             LightPolicy::Lights => 2,
             LightPolicy::Smart => 3,
+            LightPolicy::Manual => 4,
         };
 
         let changed = imgui_inspect::imgui::ComboBox::new(&im_str!("{}", label))
@@ -129,6 +199,7 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
                     &im_str!("Stop signs"),
                     &im_str!("Lights"),
                     &im_str!("Smart"),
+                    &im_str!("Manual"),
                 ],
             );
 
@@ -138,6 +209,7 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
                 1 => **p = LightPolicy::StopSigns,
                 2 => **p = LightPolicy::Lights,
                 3 => **p = LightPolicy::Smart,
+                4 => **p = LightPolicy::Manual,
                 _ => unreachable!(),
             }
         }
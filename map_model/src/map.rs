@@ -1,8 +1,9 @@
-use crate::procgen::Trees;
+use crate::procgen::heightmap::elevation;
+use crate::procgen::{BuildingGen, Trees};
 use crate::{
     Building, BuildingID, BuildingKind, Intersection, IntersectionID, Lane, LaneID, LaneKind,
-    LanePattern, Lot, LotID, LotKind, ParkingSpotID, ParkingSpots, ProjectKind, Road, RoadID,
-    RoadSegmentKind, SpatialMap,
+    LanePattern, LightCoordination, Lot, LotID, LotKind, ParkingSpotID, ParkingSpots, ProjectKind,
+    Road, RoadID, RoadSegmentKind, SpatialMap, TrafficBehavior, TrafficControl, Turn, TurnID,
 };
 use geom::{Intersect, Shape, Vec2};
 use geom::{Spline, OBB};
@@ -10,6 +11,7 @@ use ordered_float::OrderedFloat;
 use rand::prelude::IteratorRandom;
 use rand::Rng;
 use slotmap::DenseSlotMap;
+use std::sync::Arc;
 
 pub type Roads = DenseSlotMap<RoadID, Road>;
 pub type Lanes = DenseSlotMap<LaneID, Lane>;
@@ -33,6 +35,8 @@ pub struct Map {
     pub trees: Trees,
     pub parking: ParkingSpots,
     pub dirty: bool,
+    /// Shapes of the buildings built from now on, the builtin ones are used if None
+    pub(crate) building_gen: Option<Arc<dyn BuildingGen>>,
 }
 
 impl Default for Map {
@@ -53,13 +57,23 @@ impl Map {
             trees: Trees::default(),
             dirty: true,
             spatial_map: SpatialMap::default(),
+            building_gen: None,
         }
     }
 
+    pub fn set_building_gen(&mut self, gen: Option<Arc<dyn BuildingGen>>) {
+        self.building_gen = gen;
+    }
+
+    pub fn building_gen(&self) -> Option<Arc<dyn BuildingGen>> {
+        self.building_gen.clone()
+    }
+
     pub fn update_intersection(&mut self, id: IntersectionID, f: impl Fn(&mut Intersection)) {
         info!("update_intersection {:?}", id);
         let inter = unwrap_or!(self.intersections.get_mut(id), return);
         f(inter);
+        inter.z = elevation(inter.pos);
 
         let inter = &mut self.intersections[id];
         inter.update_traffic_control(&mut self.lanes, &self.roads);
@@ -67,6 +81,90 @@ impl Map {
         self.dirty = true;
     }
 
+    /// Coordinates the lights of the corridor going straight through `start` along `road`,
+    /// so that vehicles driving at `speed` along it get a green wave.
+    pub fn coordinate_corridor(&mut self, start: IntersectionID, road: RoadID, speed: f32) {
+        info!("coordinate_corridor {:?} {:?}", start, road);
+        let timing = unwrap_or!(self.intersections.get(start), return).light_timing;
+
+        for (id, road, dist) in self.corridor(start, road) {
+            let coord = LightCoordination {
+                road,
+                start: (dist / speed) as i32,
+            };
+            self.update_intersection(id, |inter| {
+                inter.light_timing.cycle_size = timing.cycle_size;
+                inter.light_timing.orange_length = timing.orange_length;
+                inter.light_timing.coordination = Some(coord);
+            });
+        }
+    }
+
+    /// Removes the coordination of the whole corridor going through `inter`
+    pub fn clear_corridor(&mut self, inter: IntersectionID) {
+        info!("clear_corridor {:?}", inter);
+        let coord = unwrap_or!(
+            self.intersections
+                .get(inter)
+                .and_then(|i| i.light_timing.coordination),
+            return
+        );
+
+        for (id, _, _) in self.corridor(inter, coord.road) {
+            if self.intersections[id].light_timing.coordination.is_none() {
+                continue;
+            }
+            self.update_intersection(id, |inter| inter.light_timing.coordination = None);
+        }
+    }
+
+    /// The intersections met going straight through `start` along `road` in both directions,
+    /// with the road they are reached from and their signed distance to `start`
+    fn corridor(&self, start: IntersectionID, road: RoadID) -> Vec<(IntersectionID, RoadID, f32)> {
+        let mut corridor = vec![(start, road, 0.0)];
+
+        let other_side = self.straightest_road(start, road);
+        for (first_road, sign) in std::iter::once((road, 1.0)).chain(other_side.map(|r| (r, -1.0)))
+        {
+            let mut inter = start;
+            let mut cur_road = first_road;
+            let mut dist = 0.0;
+            loop {
+                let r = &self.roads[cur_road];
+                dist += r.length;
+                inter = r.other_end(inter);
+                if corridor.iter().any(|(id, _, _)| *id == inter) {
+                    break;
+                }
+                corridor.push((inter, cur_road, sign * dist));
+                cur_road = unwrap_or!(self.straightest_road(inter, cur_road), break);
+            }
+        }
+        corridor
+    }
+
+    /// The road that continues the most straight after `from` through the intersection
+    fn straightest_road(&self, inter: IntersectionID, from: RoadID) -> Option<RoadID> {
+        let inter = self.intersections.get(inter)?;
+        let from_dir = -self.roads[from].orientation_from(inter.id);
+
+        inter
+            .roads
+            .iter()
+            .filter(|&&r| r != from)
+            .map(|&r| {
+                (
+                    r,
+                    from_dir
+                        .angle(self.roads[r].orientation_from(inter.id))
+                        .abs(),
+                )
+            })
+            .filter(|&(_, ang)| ang < 0.5)
+            .min_by_key(|&(_, ang)| OrderedFloat(ang))
+            .map(|(r, _)| r)
+    }
+
     fn invalidate(&mut self, id: IntersectionID) {
         info!("invalidate {:?}", id);
 
@@ -80,6 +178,7 @@ impl Map {
 
             let road = &mut self.roads[x];
             road.gen_pos(&self.intersections, &mut self.lanes, &mut self.parking);
+            self.spatial_map.update(x, road.bbox());
 
             let other_end = &mut self.intersections[self.roads[x].other_end(id)];
             other_end.update_polygon(&self.roads);
@@ -116,7 +215,8 @@ impl Map {
 
         let b = self.buildings.remove(b);
         if let Some(b) = &b {
-            self.spatial_map.remove(b.id)
+            self.spatial_map.remove(b.id);
+            self.parking.remove_lot_spots(b.id);
         }
         self.dirty |= b.is_some();
         b
@@ -143,9 +243,11 @@ impl Map {
                     from_derivative,
                     to_derivative,
                 };
-                let t_approx = s.project_t(pos, 1.0);
+                // t is not proportional to the distance, the split is placed by arc length
+                let flat = s.flatten(0.01);
+                let t = s.t_at_distance(flat.distance_along(flat.project(pos)));
 
-                let (s_from, s_to) = s.split_at(t_approx);
+                let (s_from, s_to) = s.split_at(t);
 
                 self.connect(
                     r.src,
@@ -160,6 +262,27 @@ impl Map {
                     RoadSegmentKind::Curved((s_to.from_derivative, s_to.to_derivative)),
                 );
             }
+            RoadSegmentKind::Clothoid((from_dir, to_dir)) => {
+                // both halves are fitted again from the direction at the split point, which
+                // gives back the two parts of the curve when pos is on it
+                let mid_dir = match r.clothoid() {
+                    Some(c) => c.dir_at(c.project(pos)),
+                    None => pos - r.src_point,
+                };
+
+                self.connect(
+                    r.src,
+                    id,
+                    &pat,
+                    RoadSegmentKind::Clothoid((from_dir, mid_dir)),
+                );
+                self.connect(
+                    id,
+                    r.dst,
+                    &pat,
+                    RoadSegmentKind::Clothoid((mid_dir, to_dir)),
+                );
+            }
         }
 
         id
@@ -234,13 +357,29 @@ impl Map {
             )
         }
 
-        Building::make(
+        let road = &self.roads[road];
+        let id = Building::make(
             &mut self.buildings,
             &mut self.spatial_map,
-            &self.roads[road],
+            road,
             *shape,
             kind,
-        )
+            self.building_gen.as_deref(),
+        );
+
+        if let BuildingKind::ParkingLot = kind {
+            let door_pos = self.buildings[id].door_pos;
+            let lanes = &self.lanes;
+            let access = road
+                .lanes_iter()
+                .filter(|&(_, kind)| kind == LaneKind::Driving)
+                .min_by_key(|&(id, _)| OrderedFloat(lanes[id].dist2_to(door_pos)));
+            if let Some((access, _)) = access {
+                self.parking.generate_lot_spots(id, shape, access);
+            }
+        }
+
+        id
     }
 
     pub fn build_buildings(&mut self) -> impl Iterator<Item = BuildingID> + '_ {
@@ -250,6 +389,7 @@ impl Map {
         let roads = &mut self.roads;
         let buildings = &mut self.buildings;
         let spatial_map = &mut self.spatial_map;
+        let gen = self.building_gen.as_deref();
 
         let mut built = vec![];
 
@@ -279,6 +419,7 @@ impl Map {
                 &roads[parent],
                 lot.shape,
                 kind,
+                gen,
             ));
             false
         });
@@ -326,6 +467,7 @@ impl Map {
         info!("clear");
         let before = std::mem::take(self);
         self.trees = before.trees;
+        self.building_gen = before.building_gen;
     }
 
     pub fn project(&self, pos: Vec2) -> MapProject {
@@ -422,6 +564,67 @@ impl Map {
             .map(|x| x.1)
     }
 
+    /// The behavior of the lane's traffic control for a vehicle that wants to take `turn` at the end
+    /// of it. The turn only matters when the intersection follows a signal plan.
+    pub fn lane_behavior(
+        &self,
+        lane: &Lane,
+        turn: Option<TurnID>,
+        seconds: u32,
+    ) -> TrafficBehavior {
+        if let Some(crosswalk) = turn
+            .and_then(|t| self.intersections.get(t.parent)?.find_turn(t))
+            .filter(|t| t.kind.is_crosswalk())
+        {
+            return self.walk_behavior(crosswalk, seconds);
+        }
+
+        if lane.control.is_plan() {
+            if let Some(inter) = self.intersections.get(lane.dst) {
+                return match turn {
+                    Some(turn) => inter.signal_plan.turn_behavior(turn, seconds),
+                    None => inter.signal_plan.lane_behavior(lane.id, seconds),
+                };
+            }
+        }
+        lane.control.get_behavior(seconds)
+    }
+
+    /// Walk (green) or don't walk (red) signal of a crosswalk. Pedestrians can walk when the
+    /// signalized vehicle turns coming from the crossed road are red.
+    /// Vehicles turning into the crossed road are not counted, they yield to pedestrians like
+    /// they would without lights.
+    pub fn walk_behavior(&self, crosswalk: &Turn, seconds: u32) -> TrafficBehavior {
+        let inter = unwrap_or!(
+            self.intersections.get(crosswalk.id.parent),
+            return TrafficBehavior::GREEN
+        );
+        let road = unwrap_or!(
+            self.lanes.get(crosswalk.id.src),
+            return TrafficBehavior::GREEN
+        )
+        .parent;
+
+        let vehicles_pass = crosswalk.conflicts.iter().any(|&t| {
+            let lane = unwrap_or!(self.lanes.get(t.src), return false);
+            if lane.parent != road {
+                return false;
+            }
+            let behavior = match lane.control {
+                TrafficControl::Light(_) => lane.control.get_behavior(seconds),
+                TrafficControl::Plan => inter.signal_plan.turn_behavior(t, seconds),
+                _ => return false,
+            };
+            !behavior.is_red()
+        });
+
+        if vehicles_pass {
+            TrafficBehavior::RED
+        } else {
+            TrafficBehavior::GREEN
+        }
+    }
+
     pub fn find_road(&self, src: IntersectionID, dst: IntersectionID) -> Option<RoadID> {
         for r in &self.intersections[src].roads {
             let road = &self.roads[*r];
@@ -433,11 +636,33 @@ impl Map {
     }
 
     pub fn nearest_lane(&self, p: Vec2, kind: LaneKind) -> Option<LaneID> {
-        self.lanes
-            .iter()
-            .filter(|(_, x)| x.kind == kind)
-            .min_by_key(|(_, lane)| OrderedFloat(lane.dist2_to(p)))
-            .map(|(id, _)| id)
+        // Lanes can stick out of the road's bbox a bit at sharp elbows
+        const BBOX_SLACK: f32 = 10.0;
+
+        let mut best = None;
+        let mut best_dist2 = std::f32::INFINITY;
+
+        for (obj, dist) in self.spatial_map.query_nearest(p) {
+            if (dist - BBOX_SLACK).max(0.0).powi(2) > best_dist2 {
+                break;
+            }
+            let road = match obj {
+                ProjectKind::Road(id) => unwrap_or!(self.roads.get(id), continue),
+                _ => continue,
+            };
+            for (id, lane_kind) in road.lanes_iter() {
+                if lane_kind != kind {
+                    continue;
+                }
+                let dist2 = unwrap_or!(self.lanes.get(id), continue).dist2_to(p);
+                if dist2 < best_dist2 {
+                    best_dist2 = dist2;
+                    best = Some(id);
+                }
+            }
+        }
+
+        best
     }
 
     pub fn parking_to_drive(&self, spot: ParkingSpotID) -> Option<LaneID> {
@@ -446,6 +671,9 @@ impl Map {
             .lanes
             .get(spot.parent)
             .expect("Parking spot has no parent >:(");
+        if park_lane.kind == LaneKind::Driving {
+            return Some(park_lane.id);
+        }
         let road = self
             .roads
             .get(park_lane.parent)
@@ -459,3 +687,44 @@ impl Map {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{LanePatternBuilder, LightPolicy, Map, RoadSegmentKind, TrafficBehavior};
+    use geom::vec2;
+
+    #[test]
+    fn walk_signal_in_lit_four_way() {
+        let mut map = Map::empty();
+        let center = map.add_intersection(vec2(0.0, 0.0));
+        let pattern = LanePatternBuilder::new().n_lanes(2).build();
+        for &(x, y) in &[(100.0, 0.0), (0.0, 100.0), (-100.0, 0.0), (0.0, -100.0)] {
+            let end = map.add_intersection(vec2(x, y));
+            map.connect(center, end, &pattern, RoadSegmentKind::Straight);
+        }
+        map.update_intersection(center, |inter| inter.light_policy = LightPolicy::Lights);
+
+        let inter = &map.intersections()[center];
+        let period = inter.light_timing.total_length(inter.roads.len()) as u32;
+        let crosswalks: Vec<_> = inter
+            .turns()
+            .iter()
+            .filter(|t| t.kind.is_crosswalk())
+            .collect();
+        assert_eq!(crosswalks.len(), 4);
+
+        for crosswalk in crosswalks {
+            let walks = (0..period).any(|seconds| {
+                matches!(
+                    map.walk_behavior(crosswalk, seconds),
+                    TrafficBehavior::GREEN
+                )
+            });
+            assert!(
+                walks,
+                "no walk signal within a cycle for {:?}",
+                crosswalk.id
+            );
+        }
+    }
+}
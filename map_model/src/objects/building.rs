@@ -1,4 +1,4 @@
-use crate::procgen::ColoredMesh;
+use crate::procgen::{BuildingGen, ColoredMesh};
 use crate::{Buildings, Road, SpatialMap};
 use geom::{Color, Polygon, Vec2, OBB};
 use serde::{Deserialize, Serialize};
@@ -20,6 +20,7 @@ pub enum BuildingKind {
     SlaughterHouse,
     MeatFacility,
     Bakery,
+    ParkingLot,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -38,12 +39,14 @@ impl Building {
         road: &Road,
         obb: OBB,
         kind: BuildingKind,
+        gen: Option<&dyn BuildingGen>,
     ) -> BuildingID {
         let at = obb.center();
         let axis = (obb.corners[1] - obb.corners[0]).normalize();
         let size = obb.corners[0].distance(obb.corners[1]);
 
-        let (mut mesh, mut door_pos) = match kind {
+        let custom = gen.and_then(|gen| gen.gen(kind, size));
+        let (mut mesh, mut door_pos) = custom.unwrap_or_else(|| match kind {
             BuildingKind::House => crate::procgen::gen_exterior_house(size, None),
             BuildingKind::Workplace => crate::procgen::gen_exterior_workplace(size),
             BuildingKind::Supermarket => crate::procgen::gen_exterior_supermarket(size),
@@ -54,7 +57,8 @@ impl Building {
             BuildingKind::VegetableFarm => crate::procgen::gen_exterior_farm(size),
             BuildingKind::SlaughterHouse => (Default::default(), Vec2::y(-size * 0.5)),
             BuildingKind::MeatFacility => (Default::default(), Vec2::y(-size * 0.3)),
-        };
+            BuildingKind::ParkingLot => crate::procgen::gen_exterior_parking_lot(size),
+        });
 
         for (poly, _) in &mut mesh.faces {
             poly.rotate(axis).translate(at);
@@ -1,6 +1,7 @@
+use crate::procgen::heightmap::elevation;
 use crate::{
-    Intersections, LaneID, Lanes, LightPolicy, RoadID, Roads, SpatialMap, TraverseDirection, Turn,
-    TurnID, TurnPolicy,
+    Intersections, LaneID, Lanes, LightPolicy, LightTiming, RoadID, Roads, SignalPlan, SpatialMap,
+    TraverseDirection, Turn, TurnID, TurnPolicy,
 };
 use geom::pseudo_angle;
 use geom::Polygon;
@@ -25,6 +26,9 @@ impl IntersectionID {
 pub struct Intersection {
     pub id: IntersectionID,
     pub pos: Vec2,
+    /// Elevation of the terrain at pos, kept up to date by the map
+    #[serde(skip)]
+    pub z: f32,
 
     turns: Vec<Turn>,
 
@@ -33,6 +37,8 @@ pub struct Intersection {
 
     pub turn_policy: TurnPolicy,
     pub light_policy: LightPolicy,
+    pub light_timing: LightTiming,
+    pub signal_plan: SignalPlan,
 
     pub polygon: Polygon,
 }
@@ -42,10 +48,13 @@ impl Intersection {
         let id = store.insert_with_key(|id| Intersection {
             id,
             pos,
+            z: elevation(pos),
             turns: Default::default(),
             roads: Default::default(),
             turn_policy: Default::default(),
             light_policy: Default::default(),
+            light_timing: Default::default(),
+            signal_plan: Default::default(),
             polygon: Default::default(),
         });
         spatial.insert(id, AABB::new(pos, pos));
@@ -65,6 +74,8 @@ impl Intersection {
     }
 
     pub fn update_turns(&mut self, lanes: &Lanes, roads: &Roads) {
+        let old_turns: Vec<TurnID> = self.turns.iter().map(|t| t.id).collect();
+
         self.turns = self
             .turn_policy
             .generate_turns(self, lanes, roads)
@@ -75,6 +86,44 @@ impl Intersection {
         for turn in self.turns.iter_mut() {
             turn.make_points(lanes);
         }
+
+        self.update_conflicts(lanes);
+
+        if self.signal_plan.regenerate {
+            self.signal_plan = SignalPlan {
+                orange_length: self.signal_plan.orange_length,
+                offset: self.signal_plan.offset,
+                ..SignalPlan::generate(self, lanes)
+            };
+            return;
+        }
+
+        let turns = &self.turns;
+        self.signal_plan
+            .retain_turns(|id| turns.iter().any(|t| t.id == id));
+        self.signal_plan.assign_turns(
+            turns.iter().filter(|t| !old_turns.contains(&t.id)),
+            turns,
+            lanes,
+        );
+    }
+
+    fn update_conflicts(&mut self, lanes: &Lanes) {
+        let n = self.turns.len();
+        let mut conflicts = vec![vec![]; n];
+
+        for i in 0..n {
+            for j in i + 1..n {
+                if self.turns[i].conflicts_with(&self.turns[j], lanes) {
+                    conflicts[i].push(self.turns[j].id);
+                    conflicts[j].push(self.turns[i].id);
+                }
+            }
+        }
+
+        for (turn, c) in self.turns.iter_mut().zip(conflicts) {
+            turn.conflicts = c;
+        }
     }
 
     pub fn update_traffic_control(&self, lanes: &mut Lanes, roads: &Roads) {
@@ -115,6 +164,8 @@ impl Intersection {
 
     pub fn update_polygon(&mut self, roads: &Roads) {
         self.polygon.clear();
+        let pos = self.pos;
+        let mut pieces = vec![];
 
         for (i, &road) in self.roads.iter().enumerate() {
             let road = &roads[road];
@@ -122,8 +173,9 @@ impl Intersection {
 
             let src_orient = road.orientation_from(self.id);
 
-            let left =
-                road.interface_point(self.id) - road.width * 0.5 * src_orient.perpendicular();
+            let interface = road.interface_point(self.id);
+            let side = road.width * 0.5 * src_orient.perpendicular();
+            let left = interface - side;
 
             let dst_orient = next_road.orientation_from(self.id);
             let next_right = next_road.interface_point(self.id)
@@ -146,8 +198,44 @@ impl Intersection {
                 to_derivative: dst_orient * dist,
             };
 
-            self.polygon.extend(spline.smart_points(1.0, 0.0, 1.0));
+            let corner: Vec<Vec2> = spline.smart_points(1.0, 0.0, 1.0).collect();
+            self.polygon.extend(corner.iter().copied());
+
+            // the corner is a fan around pos when it turns around it in one direction,
+            // the triangles of a curl are merged one by one otherwise
+            let turns = |sign: f32| {
+                corner
+                    .windows(2)
+                    .all(|w| (w[0] - pos).perp_dot(w[1] - pos) * sign >= 0.0)
+            };
+            if turns(1.0) || turns(-1.0) {
+                pieces.push(Polygon(std::iter::once(pos).chain(corner).collect()));
+            } else {
+                pieces.extend(corner.windows(2).map(|w| Polygon(vec![pos, w[0], w[1]])));
+            }
+            pieces.push(Polygon(vec![
+                pos - side,
+                left,
+                interface + side,
+                pos + side,
+            ]));
+        }
+
+        // The contour crosses itself when the roads are close to each other, the union of
+        // the road ends and of the corners between them never does.
+        // The contour is kept if a degenerate piece makes the union fail
+        let mut pieces = pieces.into_iter().filter(|p| p.area() > 1e-3);
+        let mut merged = unwrap_or!(pieces.next(), return);
+        for p in pieces {
+            merged = unwrap_or!(
+                merged
+                    .union(&p)
+                    .into_iter()
+                    .max_by_key(|x| OrderedFloat(x.area())),
+                return
+            );
         }
+        self.polygon = merged;
     }
 
     pub fn neighbors<'a>(&'a self, roads: &'a Roads) -> impl Iterator<Item = IntersectionID> + 'a {
@@ -1,4 +1,4 @@
-use crate::procgen::heightmap::height;
+use crate::procgen::heightmap::{height, WATER_HEIGHT};
 use crate::{Buildings, Intersections, Lots, Map, ProjectKind, RoadID, Roads, SpatialMap};
 use geom::OBB;
 use geom::{Intersect, Polygon};
@@ -11,6 +11,8 @@ new_key_type! {
     pub struct LotID;
 }
 
+const MIN_LOT_SIZE: f32 = 20.0;
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum LotKind {
     Unassigned,
@@ -39,26 +41,42 @@ impl Lot {
         axis: Vec2,
         size: f32,
     ) -> Option<LotID> {
-        let shape = OBB::new(at + axis * size * 0.5, axis, size, size);
-
-        if height(at) < 0.12 {
+        if height(at) < WATER_HEIGHT {
             return None;
         }
 
-        for obj in spatial.query(&shape) {
+        // Roads and intersections clip the lot instead of discarding it, it is shrunk to
+        // the depth before the first obstacle
+        let full = OBB::new(at + axis * size * 0.5, axis, size, size);
+        let square = Polygon(full.corners.to_vec());
+        let mut depth = size;
+        for obj in spatial.query(&full) {
             match obj {
                 ProjectKind::Road(r) => {
                     let r = &roads[r];
-                    if r.intersects(&shape) {
-                        return None;
+                    for w in r.generated_points.as_slice().windows(2) {
+                        let n = unwrap_or!((w[1] - w[0]).try_normalize_to(r.width * 0.5), continue)
+                            .perpendicular();
+                        let quad = Polygon(vec![w[0] - n, w[1] - n, w[1] + n, w[0] + n]);
+                        depth = depth.min(clip_depth(&square, &quad, at, axis));
                     }
                 }
                 ProjectKind::Inter(i) => {
-                    let i = &inters[i];
-                    if i.polygon.intersects(&shape) {
-                        return None;
-                    }
+                    depth = depth.min(clip_depth(&square, &inters[i].polygon, at, axis));
                 }
+                _ => {}
+            }
+        }
+
+        if depth < MIN_LOT_SIZE {
+            return None;
+        }
+
+        let size = depth;
+        let shape = OBB::new(at + axis * size * 0.5, axis, size, size);
+
+        for obj in spatial.query(&shape) {
+            match obj {
                 ProjectKind::Lot(h) => {
                     let h = &lots[h];
                     if h.shape.intersects(&shape) {
@@ -75,7 +93,7 @@ impl Lot {
                         return None;
                     }
                 }
-                ProjectKind::Ground => {}
+                _ => {}
             }
         }
 
@@ -183,3 +201,13 @@ impl Lot {
         }
     }
 }
+
+/// Distance along axis from at to the first point of the square covered by the obstacle
+fn clip_depth(square: &Polygon, obstacle: &Polygon, at: Vec2, axis: Vec2) -> f32 {
+    square
+        .intersection(obstacle)
+        .iter()
+        .flat_map(|p| p.iter())
+        .map(|&p| (p - at).dot(axis))
+        .fold(f32::INFINITY, f32::min)
+}
@@ -1,28 +1,57 @@
-use crate::{Lane, LaneID, LaneKind, CROSSWALK_WIDTH};
-use geom::{Transform, Vec2};
+use crate::{BuildingID, Lane, LaneID, LaneKind, CROSSWALK_WIDTH};
+use geom::{Transform, Vec2, OBB};
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 use slotmap::{new_key_type, SecondaryMap, SlotMap};
+use std::sync::atomic::{AtomicU32, Ordering};
 
 new_key_type! {
     pub struct ParkingSpotID;
 }
 
 pub const PARKING_SPOT_LENGTH: f32 = 6.0;
+pub const PARKING_SPOT_WIDTH: f32 = 2.8;
 
 #[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct ParkingSpot {
+    /// The parking lane of the spot, or the driving lane giving access to the lot
     pub parent: LaneID,
     pub trans: Transform,
 }
 
-#[derive(Clone, Default, Serialize, Deserialize)]
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+fn next_generation() -> u32 {
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ParkingSpots {
     spots: SlotMap<ParkingSpotID, ParkingSpot>,
     lane_spots: SecondaryMap<LaneID, Vec<ParkingSpotID>>,
+    lot_spots: SecondaryMap<BuildingID, Vec<ParkingSpotID>>,
+    #[serde(skip, default = "next_generation")]
+    generation: u32,
+}
+
+impl Default for ParkingSpots {
+    fn default() -> Self {
+        Self {
+            spots: Default::default(),
+            lane_spots: Default::default(),
+            lot_spots: Default::default(),
+            generation: next_generation(),
+        }
+    }
 }
 
 impl ParkingSpots {
+    /// Changes every time spots are added or removed, unique among all the maps.
+    /// Lets users cache data computed from the spots.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
     pub fn get(&self, spot: ParkingSpotID) -> Option<&ParkingSpot> {
         self.spots.get(spot)
     }
@@ -32,15 +61,70 @@ impl ParkingSpots {
     }
 
     pub fn remove_spots(&mut self, lane: LaneID) {
+        self.generation = next_generation();
         if let Some(spots) = self.lane_spots.remove(lane) {
             for spot in spots {
                 self.spots.remove(spot);
             }
         }
+
+        // lots accessed from that lane become unreachable
+        let spots = &mut self.spots;
+        for lspots in self.lot_spots.values_mut() {
+            lspots.retain(|&id| {
+                if spots[id].parent == lane {
+                    spots.remove(id);
+                    return false;
+                }
+                true
+            });
+        }
+    }
+
+    pub fn remove_lot_spots(&mut self, building: BuildingID) {
+        self.generation = next_generation();
+        if let Some(spots) = self.lot_spots.remove(building) {
+            for spot in spots {
+                self.spots.remove(spot);
+            }
+        }
+    }
+
+    /// Fills the lot with rows of spots facing away from the road, separated by aisles.
+    /// `access` is the driving lane the cars come from.
+    pub fn generate_lot_spots(&mut self, building: BuildingID, shape: &OBB, access: LaneID) {
+        self.generation = next_generation();
+        self.remove_lot_spots(building);
+
+        let [c0, c1, _, c3] = shape.corners;
+        let along = c1 - c0;
+        let away = c3 - c0;
+        let (width, depth) = (along.magnitude(), away.magnitude());
+        let (along, away) = (along / width, away / depth);
+
+        let margin = 1.0;
+        let aisle = 6.0;
+        let n_cols = ((width - margin * 2.0) / PARKING_SPOT_WIDTH) as i32;
+
+        let mut lspots = vec![];
+        let mut y = aisle + PARKING_SPOT_LENGTH * 0.5;
+        while y + PARKING_SPOT_LENGTH * 0.5 <= depth - margin {
+            for i in 0..n_cols {
+                let x = margin + (i as f32 + 0.5) * PARKING_SPOT_WIDTH;
+                lspots.push(self.spots.insert(ParkingSpot {
+                    parent: access,
+                    trans: Transform::new_cos_sin(c0 + along * x + away * y, away),
+                }));
+            }
+            y += PARKING_SPOT_LENGTH + aisle;
+        }
+
+        self.lot_spots.insert(building, lspots);
     }
 
     pub fn generate_spots(&mut self, lane: &Lane) {
         debug_assert!(matches!(lane.kind, LaneKind::Parking));
+        self.generation = next_generation();
 
         let lane_spots = match self.lane_spots.get_mut(lane.id) {
             Some(x) => x,
@@ -73,8 +157,18 @@ impl ParkingSpots {
     }
 
     pub fn clear(&mut self) {
+        self.generation = next_generation();
         self.spots.clear();
         self.lane_spots.clear();
+        self.lot_spots.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ParkingSpotID, &ParkingSpot)> {
+        self.spots.iter()
+    }
+
+    pub fn lot_spots(&self, building: BuildingID) -> impl Iterator<Item = ParkingSpotID> + '_ {
+        self.lot_spots.get(building).into_iter().flatten().copied()
     }
 
     pub fn spots(&self, lane: LaneID) -> impl Iterator<Item = ParkingSpot> + '_ {
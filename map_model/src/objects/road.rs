@@ -1,12 +1,14 @@
+use crate::procgen::heightmap::elevation;
 use crate::{
     IntersectionID, Intersections, Lane, LaneDirection, LaneID, LaneKind, LanePattern, Lanes,
     LotID, Map, ParkingSpots,
 };
+use geom::Clothoid;
 use geom::PolyLine;
 use geom::Spline;
-use geom::Vec2;
 use geom::AABB;
 use geom::OBB;
+use geom::{Vec2, Vec3};
 use serde::{Deserialize, Serialize};
 use slotmap::new_key_type;
 
@@ -14,10 +16,14 @@ new_key_type! {
     pub struct RoadID;
 }
 
+/// Steepest slope a road can have, as rise over run
+pub const MAX_GRADE: f32 = 0.08;
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum RoadSegmentKind {
     Straight,
-    Curved((Vec2, Vec2)), // The two derivatives for the spline
+    Curved((Vec2, Vec2)),   // The two derivatives for the spline
+    Clothoid((Vec2, Vec2)), // The directions at both ends, the clothoid is fitted between them
 }
 
 impl RoadSegmentKind {
@@ -27,6 +33,10 @@ impl RoadSegmentKind {
             (to - elbow) * std::f32::consts::FRAC_1_SQRT_2,
         ))
     }
+
+    pub fn clothoid_from_elbow(from: Vec2, to: Vec2, elbow: Vec2) -> RoadSegmentKind {
+        RoadSegmentKind::Clothoid((elbow - from, to - elbow))
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -39,8 +49,14 @@ pub struct Road {
     pub dst_point: Vec2,
 
     pub segment: RoadSegmentKind,
+    /// Curve of a clothoid segment, fitted again when the end points move
+    #[serde(skip)]
+    pub(crate) fitted: Option<Clothoid>,
 
     pub(crate) generated_points: PolyLine,
+    /// Elevation of each generated point
+    #[serde(skip)]
+    pub(crate) generated_z: Vec<f32>,
 
     pub length: f32,
     pub width: f32,
@@ -79,11 +95,13 @@ impl Road {
             src_point: intersections[src].pos,
             dst_point: intersections[dst].pos,
             segment,
+            fitted: None,
             width: 0.0,
             length: 1.0,
             lanes_forward: vec![],
             lanes_backward: vec![],
             generated_points: PolyLine::new(vec![Vec2::ZERO]),
+            generated_z: vec![],
             lots: vec![],
         });
         let road = &mut map.roads[id];
@@ -172,6 +190,7 @@ impl Road {
         self.src_point = intersections[self.src].pos;
         self.dst_point = intersections[self.dst].pos;
 
+        self.fit_clothoid();
         self.generate_points();
 
         self.length = match self.segment {
@@ -182,9 +201,15 @@ impl Road {
                 from_derivative,
                 to_derivative,
             }
-            .length(1.0),
+            .arc_length(),
+            RoadSegmentKind::Clothoid(_) => match self.clothoid() {
+                Some(c) => c.length,
+                None => (self.dst_point - self.src_point).magnitude(),
+            },
         };
 
+        self.generate_z(intersections);
+
         let mut dist_from_bottom = 0.0;
         for (id, kind) in self.lanes_iter() {
             let l = &mut lanes[id];
@@ -211,17 +236,95 @@ impl Road {
         &self.generated_points
     }
 
+    pub fn generated_z(&self) -> &[f32] {
+        &self.generated_z
+    }
+
+    pub fn generated_points_3d(&self) -> impl Iterator<Item = Vec3> + '_ {
+        self.generated_points
+            .iter()
+            .zip(&self.generated_z)
+            .map(|(p, &z)| p.z(z))
+    }
+
+    /// Whether a road of that length can join both elevations without being steeper than
+    /// MAX_GRADE
+    pub fn grade_ok(from_z: f32, to_z: f32, length: f32) -> bool {
+        (to_z - from_z).abs() <= MAX_GRADE * length
+    }
+
+    /// Slope of the road at the projection of p, positive when going up from src to dst
+    pub fn grade_at(&self, p: Vec2) -> f32 {
+        let (_, seg) = self.generated_points.project_segment(p);
+        if seg == 0 || seg >= self.generated_z.len() {
+            return 0.0;
+        }
+        let run = self.generated_points[seg].distance(self.generated_points[seg - 1]);
+        if run <= 0.0 {
+            return 0.0;
+        }
+        (self.generated_z[seg] - self.generated_z[seg - 1]) / run
+    }
+
+    /// Follows the terrain along the generated points, flattened where needed so that
+    /// the road joins both intersections without being steeper than MAX_GRADE.
+    pub(crate) fn generate_z(&mut self, intersections: &Intersections) {
+        let src_z = intersections[self.src].z;
+        let dst_z = intersections[self.dst].z;
+
+        let mut dists = Vec::with_capacity(self.generated_points.n_points());
+        let mut d = self.interface_from(self.src).max(0.0);
+        let mut last = self.generated_points.first();
+        for &p in self.generated_points.iter() {
+            d += p.distance(last);
+            dists.push(d);
+            last = p;
+        }
+        let total = d + self.interface_from(self.dst).max(0.0);
+
+        // Roads built before the check, or from presets, become a straight ramp
+        let grade = MAX_GRADE.max((dst_z - src_z).abs() / total.max(1e-3));
+
+        // Going forward keeps the slope under the grade starting from src,
+        // then clamping to what dst can reach keeps it without breaking that
+        let mut prev = (0.0, src_z);
+        self.generated_z.clear();
+        for (&p, &d) in self.generated_points.iter().zip(&dists) {
+            let slack = grade * (d - prev.0);
+            let z = elevation(p).max(prev.1 - slack).min(prev.1 + slack);
+            self.generated_z.push(z);
+            prev = (d, z);
+        }
+
+        for (z, &d) in self.generated_z.iter_mut().zip(&dists) {
+            let slack = grade * (total - d).max(0.0);
+            *z = z.max(dst_z - slack).min(dst_z + slack);
+        }
+    }
+
     fn generate_points(&mut self) {
         let (src_dir, dst_dir) = self.basic_orientations();
 
         let from = self.src_point + src_dir * self.interface_from(self.src);
         let to = self.dst_point + dst_dir * self.interface_from(self.dst);
 
+        let clothoid = self.clothoid();
         match &self.segment {
             RoadSegmentKind::Straight => {
                 self.generated_points.clear_push(from);
                 self.generated_points.push(to);
             }
+            RoadSegmentKind::Clothoid(_) => match clothoid {
+                Some(c) => {
+                    let start = self.interface_from(self.src);
+                    let end = c.length - self.interface_from(self.dst);
+                    self.generated_points = c.sub(start, end).flatten(0.1);
+                }
+                None => {
+                    self.generated_points.clear_push(from);
+                    self.generated_points.push(to);
+                }
+            },
             &RoadSegmentKind::Curved((from_derivative, to_derivative)) => {
                 let s = Spline {
                     from: self.src_point,
@@ -296,13 +399,29 @@ impl Road {
         }
     }
 
+    /// The curve of a clothoid road, None for the other kinds or when it cannot be fitted
+    pub fn clothoid(&self) -> Option<Clothoid> {
+        self.fitted
+    }
+
+    pub(crate) fn fit_clothoid(&mut self) {
+        self.fitted = match self.segment {
+            RoadSegmentKind::Clothoid((from_dir, to_dir)) => {
+                Clothoid::fit(self.src_point, from_dir, self.dst_point, to_dir)
+            }
+            _ => None,
+        };
+    }
+
     pub fn basic_orientations(&self) -> (Vec2, Vec2) {
         match &self.segment {
             RoadSegmentKind::Straight => {
                 let d = (self.dst_point - self.src_point).normalize();
                 (d, -d)
             }
-            RoadSegmentKind::Curved((s, d)) => (s.normalize(), -d.normalize()),
+            RoadSegmentKind::Curved((s, d)) | RoadSegmentKind::Clothoid((s, d)) => {
+                (s.normalize(), -d.normalize())
+            }
         }
     }
 
@@ -2,6 +2,7 @@ use crate::{IntersectionID, LaneID, Lanes};
 use geom::PolyLine;
 use geom::Spline;
 use geom::Vec2;
+use geom::{Intersect, Segment};
 use serde::{Deserialize, Serialize};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash)]
@@ -36,11 +37,39 @@ impl TurnKind {
     }
 }
 
+/// Ordered by priority: a straight turn has right of way over a right turn, which has right of way
+/// over a left turn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TurnDirection {
+    UTurn,
+    Left,
+    Right,
+    Straight,
+}
+
+impl TurnDirection {
+    pub fn from_angle(ang: f32) -> Self {
+        if ang.abs() < 0.5 {
+            TurnDirection::Straight
+        } else if ang.abs() > 2.6 {
+            TurnDirection::UTurn
+        } else if ang > 0.0 {
+            TurnDirection::Left
+        } else {
+            TurnDirection::Right
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Turn {
     pub id: TurnID,
     pub points: PolyLine,
     pub kind: TurnKind,
+    pub direction: TurnDirection,
+
+    /// Turns of the same intersection whose path crosses or merges with this one
+    pub conflicts: Vec<TurnID>,
 }
 
 const TURN_ANG_ADD: f32 = 0.29;
@@ -54,6 +83,8 @@ impl Turn {
             id,
             points: PolyLine::new(vec![Vec2::ZERO; N_SPLINE + 2]),
             kind,
+            direction: TurnDirection::Straight,
+            conflicts: vec![],
         }
     }
 
@@ -67,6 +98,7 @@ impl Turn {
         self.points.clear_push(pos_src);
 
         if self.kind.is_crosswalk() {
+            self.direction = TurnDirection::Straight;
             self.points.push(pos_dst);
             return;
         }
@@ -75,6 +107,7 @@ impl Turn {
         let dst_dir = dst_lane.orientation_from(self.id.parent);
 
         let ang = src_dir.angle(dst_dir);
+        self.direction = TurnDirection::from_angle(ang);
 
         let dist =
             (pos_dst - pos_src).magnitude() * (TURN_ANG_ADD + ang.abs() * TURN_ANG_MUL) * TURN_MUL;
@@ -92,4 +125,61 @@ impl Turn {
         self.points
             .extend(spline.smart_points(0.3, 0.0, 1.0).skip(1));
     }
+
+    /// Two driving turns conflict if they lead to the same lane or if their paths cross.
+    /// Turns coming from the same lane never conflict, vehicles just follow each other.
+    /// A crosswalk conflicts with the driving turns coming from or going to the road it crosses,
+    /// but only the ones coming from it hold the walk signal, see `Map::walk_behavior`.
+    pub fn conflicts_with(&self, other: &Turn, lanes: &Lanes) -> bool {
+        match (self.kind, other.kind) {
+            (TurnKind::Crosswalk, TurnKind::Driving) => return other.uses_road_of(self, lanes),
+            (TurnKind::Driving, TurnKind::Crosswalk) => return self.uses_road_of(other, lanes),
+            (TurnKind::Driving, TurnKind::Driving) => {}
+            _ => return false,
+        }
+        if self.id == other.id || self.id.src == other.id.src {
+            return false;
+        }
+        if self.id.dst == other.id.dst {
+            return true;
+        }
+        if !self.points.bbox().intersects(&other.points.bbox()) {
+            return false;
+        }
+
+        self.points.as_slice().windows(2).any(|w1| {
+            let s1 = Segment::new(w1[0], w1[1]);
+            other
+                .points
+                .as_slice()
+                .windows(2)
+                .any(|w2| s1.intersection_point(&Segment::new(w2[0], w2[1])).is_some())
+        })
+    }
+
+    fn uses_road_of(&self, crosswalk: &Turn, lanes: &Lanes) -> bool {
+        let road = unwrap_or!(lanes.get(crosswalk.id.src), return false).parent;
+        [self.id.src, self.id.dst]
+            .iter()
+            .any(|&l| lanes.get(l).map_or(false, |l| l.parent == road))
+    }
+
+    /// Whether a vehicle on this turn must give way to a vehicle on the other turn.
+    /// Stop-signed lanes yield to the others, then straighter turns have priority.
+    /// When neither yields, the first to arrive goes first.
+    pub fn yields_to(&self, other: &Turn, lanes: &Lanes) -> bool {
+        let is_stop = |t: &Turn| {
+            lanes
+                .get(t.id.src)
+                .map_or(false, |l| l.control.is_stop_sign())
+        };
+
+        let my_stop = is_stop(self);
+        let his_stop = is_stop(other);
+        if my_stop != his_stop {
+            return my_stop;
+        }
+
+        self.direction < other.direction
+    }
 }
@@ -1,10 +1,10 @@
+use crate::BuildingKind;
 use geom::skeleton::{faces_from_skeleton, skeleton};
 use geom::{vec2, vec3, Color, Intersect, LinearColor, Polygon, Segment, Shape, Vec2, AABB};
 use ordered_float::OrderedFloat;
 use rand::prelude::SmallRng;
 use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
-use std::panic::catch_unwind;
 
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct ColoredMesh {
@@ -27,6 +27,13 @@ impl ColoredMesh {
     }
 }
 
+/// Building shapes provided from outside of the map model, e.g. by mods
+pub trait BuildingGen: Send + Sync {
+    /// Returns the mesh and door position of a building of that size centered on the origin,
+    /// or None to use the builtin shape
+    fn gen(&self, kind: BuildingKind, size: f32) -> Option<(ColoredMesh, Vec2)>;
+}
+
 pub fn gen_exterior_workplace(size: f32) -> (ColoredMesh, Vec2) {
     let a = rand_in(15.0, 20.0);
     let b = rand_in(15.0, 20.0);
@@ -69,26 +76,31 @@ pub fn gen_exterior_house(size: f32, seed: Option<u64>) -> (ColoredMesh, Vec2) {
         for _ in 0..rng.gen_range(1.0..5.0) as usize {
             let seg = rng.gen_range(0.0..p.len() as f32) as usize;
 
-            let origlen = p.segment(seg).vec().magnitude();
-            if origlen < 8.0 {
+            let s = p.segment(seg);
+            let len = s.vec().magnitude();
+            if len < 8.0 {
                 continue;
             }
 
-            let l = rng.gen_range(-0.2..0.5);
-            let r = rng.gen_range(l + 0.4..l + 1.0);
-            if r <= 1.0 {
-                p.split_segment(seg, r);
-            }
-
-            let newlen = p.segment(seg).vec().magnitude();
-
-            if l >= 0.0 {
-                p.split_segment(seg, l * origlen / newlen);
-                p.extrude(seg + 1, rng.gen_range(1.0..8.0));
-            } else {
-                p.extrude(seg, rng.gen_range(1.0..8.0));
-            }
-
+            let l = rng.gen_range(-0.2..0.5f32);
+            let r = rng.gen_range(l + 0.4..l + 1.0).min(1.0);
+            let l = l.max(0.0);
+            let depth = rng.gen_range(1.0..8.0);
+
+            // the wing overlaps the body a bit so that they are merged into one footprint,
+            // the body is counter-clockwise so the perpendicular points outwards
+            let dir = s.vec() / len;
+            let out = dir.perpendicular();
+            let a = s.src + dir * len * l;
+            let b = s.src + dir * len * r;
+            let wing = Polygon(vec![a - out, b - out, b + out * depth, a + out * depth]);
+
+            p = unwrap_or!(
+                p.union(&wing)
+                    .into_iter()
+                    .max_by_key(|x| OrderedFloat(x.area())),
+                continue
+            );
             p.simplify();
         }
 
@@ -104,26 +116,13 @@ pub fn gen_exterior_house(size: f32, seed: Option<u64>) -> (ColoredMesh, Vec2) {
 
         let merge_triangles = rng.gen();
 
-        // silence panics
-        let hook = std::panic::take_hook();
-        std::panic::set_hook(Box::new(|_| ()));
-        // have to catch because the algorithm for skeleton might fail and is quite complicated
-        let (skeleton, faces) = unwrap_or!(
-            catch_unwind(|| {
-                let skeleton = skeleton(p.as_slice(), &[]);
-                let faces = faces_from_skeleton(p.as_slice(), &skeleton, merge_triangles)?;
-                Some((skeleton, faces))
-            })
-            .ok()
-            .flatten(),
-            {
-                std::panic::set_hook(hook);
-                continue 'retry;
-            }
+        // the skeleton fails on some degenerate shapes, another one is generated then
+        let skeleton = unwrap_or!(skeleton(p.as_slice(), &[]).ok(), continue 'retry);
+        let faces = unwrap_or!(
+            faces_from_skeleton(p.as_slice(), &[], &skeleton, merge_triangles).ok(),
+            continue 'retry
         );
 
-        std::panic::set_hook(hook);
-
         if faces.len() < 2 {
             continue 'retry;
         }
@@ -213,6 +212,18 @@ pub fn gen_exterior_supermarket(size: f32) -> (ColoredMesh, Vec2) {
     )
 }
 
+pub fn gen_exterior_parking_lot(size: f32) -> (ColoredMesh, Vec2) {
+    (
+        ColoredMesh {
+            faces: vec![(
+                Polygon::centered_rect(size, size),
+                Color::new(0.35, 0.35, 0.37, 1.0).into(),
+            )],
+        },
+        Vec2::y(-size * 0.5),
+    )
+}
+
 ///  XXXXX   
 ///  XXXXX   
 ///    XXX   
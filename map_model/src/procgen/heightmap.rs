@@ -77,6 +77,12 @@ fn fnoise(ampl: f32, in_wv: Vec2) -> f32 {
     noise
 }
 
+/// Height below which the terrain is under water
+pub const WATER_HEIGHT: f32 = 0.12;
+
+/// Meters of elevation per unit of height
+const ELEVATION_SCALE: f32 = 400.0;
+
 pub fn height(mut p: Vec2) -> f32 {
     p -= vec2(-2000.0, 2000.0);
 
@@ -84,9 +90,14 @@ pub fn height(mut p: Vec2) -> f32 {
     noise.max(0.0).min(1.0)
 }
 
+/// Elevation of the terrain in meters, the water level being at zero
+pub fn elevation(p: Vec2) -> f32 {
+    (height(p) - WATER_HEIGHT) * ELEVATION_SCALE
+}
+
 pub fn tree_density(mut p: Vec2) -> f32 {
     let h = height(p);
     p -= vec2(-2000.0, 2000.0);
 
-    (simplex_noise(p * 0.00003) * 2.0 + 0.5).max(0.0) * (h - 0.12)
+    (simplex_noise(p * 0.00003) * 2.0 + 0.5).max(0.0) * (h - WATER_HEIGHT)
 }
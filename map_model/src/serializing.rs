@@ -1,3 +1,4 @@
+use crate::procgen::heightmap::elevation;
 use crate::procgen::Trees;
 use crate::{Buildings, Intersections, Lanes, Lots, Map, ParkingSpots, Roads, SpatialMap};
 use geom::Shape;
@@ -35,8 +36,13 @@ impl From<&Map> for SerializedMap {
 impl From<SerializedMap> for Map {
     fn from(mut sel: SerializedMap) -> Self {
         for inter in sel.intersections.values_mut() {
+            inter.z = elevation(inter.pos);
             inter.update_polygon(&sel.roads);
         }
+        for road in sel.roads.values_mut() {
+            road.fit_clothoid();
+            road.generate_z(&sel.intersections);
+        }
 
         let spatial_map = mk_spatial_map(&sel);
         Map {
@@ -49,6 +55,7 @@ impl From<SerializedMap> for Map {
             parking: sel.parking,
             trees: sel.trees,
             dirty: true,
+            building_gen: None,
         }
     }
 }
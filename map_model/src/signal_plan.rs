@@ -0,0 +1,288 @@
+use crate::{
+    Intersection, LaneID, Lanes, RoadID, TrafficBehavior, Turn, TurnDirection, TurnID, TurnKind,
+};
+use imgui_inspect::{
+    imgui::{im_str, Ui},
+    InspectArgsDefault, InspectRenderDefault,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignalPhase {
+    /// Turns that are green during this phase
+    pub turns: Vec<TurnID>,
+    /// Green duration in seconds, orange excluded
+    pub duration: u32,
+}
+
+/// A fixed cycle of phases, each one granting green to a set of turns.
+/// Used by intersections with the `LightPolicy::Manual` policy.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignalPlan {
+    pub phases: Vec<SignalPhase>,
+    pub orange_length: u32,
+    pub offset: u32,
+    /// Set by the editor to replace the phases with generated ones on the next turn update
+    #[serde(skip)]
+    pub regenerate: bool,
+}
+
+impl Default for SignalPlan {
+    fn default() -> Self {
+        Self {
+            phases: vec![],
+            orange_length: 4,
+            offset: 0,
+            regenerate: false,
+        }
+    }
+}
+
+impl SignalPlan {
+    /// Generates a plan where each pair of opposite roads gets a phase for going straight and
+    /// turning right, followed by a protected phase for turning left.
+    pub fn generate(inter: &Intersection, lanes: &Lanes) -> Self {
+        let in_roads: Vec<RoadID> = inter
+            .roads
+            .iter()
+            .copied()
+            .filter(|&r| {
+                inter
+                    .turns()
+                    .iter()
+                    .any(|t| lanes.get(t.id.src).map_or(false, |l| l.parent == r))
+            })
+            .collect();
+
+        let n_groups = (in_roads.len() + 1) / 2;
+        let mut phases = vec![];
+
+        for group in 0..n_groups {
+            let in_group = |t: TurnID| {
+                lanes.get(t.src).map_or(false, |l| {
+                    in_roads
+                        .iter()
+                        .position(|&r| r == l.parent)
+                        .map_or(false, |i| i % n_groups == group)
+                })
+            };
+
+            let (left, through): (Vec<_>, Vec<_>) = inter
+                .turns()
+                .iter()
+                .filter(|t| matches!(t.kind, TurnKind::Driving) && in_group(t.id))
+                .partition(|t| matches!(t.direction, TurnDirection::Left | TurnDirection::UTurn));
+
+            if !through.is_empty() {
+                phases.push(SignalPhase {
+                    turns: through.into_iter().map(|t| t.id).collect(),
+                    duration: 10,
+                });
+            }
+            if !left.is_empty() {
+                phases.push(SignalPhase {
+                    turns: left.into_iter().map(|t| t.id).collect(),
+                    duration: 6,
+                });
+            }
+        }
+
+        Self {
+            phases,
+            ..Default::default()
+        }
+    }
+
+    pub fn period(&self) -> u32 {
+        self.phases
+            .iter()
+            .map(|p| p.duration + self.orange_length)
+            .sum()
+    }
+
+    /// Index of the current phase and the seconds elapsed since it started
+    pub fn current_phase(&self, seconds: u32) -> Option<(usize, u32)> {
+        let period = self.period();
+        if period == 0 {
+            return None;
+        }
+
+        let mut t = (seconds + self.offset) % period;
+        for (i, phase) in self.phases.iter().enumerate() {
+            let length = phase.duration + self.orange_length;
+            if t < length {
+                return Some((i, t));
+            }
+            t -= length;
+        }
+        None
+    }
+
+    pub fn turn_behavior(&self, turn: TurnID, seconds: u32) -> TrafficBehavior {
+        let (i, t) = unwrap_or!(self.current_phase(seconds), return TrafficBehavior::GREEN);
+        let phase = &self.phases[i];
+        if !phase.turns.contains(&turn) {
+            return TrafficBehavior::RED;
+        }
+        if t < phase.duration {
+            return TrafficBehavior::GREEN;
+        }
+
+        // no need to go orange if the next phase also grants it
+        let next = &self.phases[(i + 1) % self.phases.len()];
+        if next.turns.contains(&turn) {
+            TrafficBehavior::GREEN
+        } else {
+            TrafficBehavior::ORANGE
+        }
+    }
+
+    /// The most permissive behavior among the turns starting from that lane
+    pub fn lane_behavior(&self, lane: LaneID, seconds: u32) -> TrafficBehavior {
+        let mut best = TrafficBehavior::RED;
+        for &turn in self.phases.iter().flat_map(|p| &p.turns) {
+            if turn.src != lane {
+                continue;
+            }
+            match self.turn_behavior(turn, seconds) {
+                TrafficBehavior::GREEN => return TrafficBehavior::GREEN,
+                TrafficBehavior::ORANGE => best = TrafficBehavior::ORANGE,
+                _ => {}
+            }
+        }
+        best
+    }
+
+    pub fn toggle_turn(&mut self, phase: usize, turn: TurnID) {
+        let phase = unwrap_or!(self.phases.get_mut(phase), return);
+        if let Some(i) = phase.turns.iter().position(|&t| t == turn) {
+            phase.turns.swap_remove(i);
+        } else {
+            phase.turns.push(turn);
+        }
+    }
+
+    /// Removes the turns that do not exist anymore
+    pub fn retain_turns(&mut self, mut f: impl FnMut(TurnID) -> bool) {
+        for phase in &mut self.phases {
+            phase.turns.retain(|&t| f(t));
+        }
+    }
+
+    /// Adds new turns to the phase granting the most turns of the same road going the same way,
+    /// or else the most turns of the same road.
+    /// Turns from a road that has no turn in any phase stay unassigned.
+    pub fn assign_turns<'a>(
+        &mut self,
+        new_turns: impl Iterator<Item = &'a Turn>,
+        turns: &[Turn],
+        lanes: &Lanes,
+    ) {
+        let road_of = |t: TurnID| lanes.get(t.src).map(|l| l.parent);
+        let is_left = |t: &Turn| matches!(t.direction, TurnDirection::Left | TurnDirection::UTurn);
+
+        for turn in new_turns.filter(|t| matches!(t.kind, TurnKind::Driving)) {
+            let road = road_of(turn.id);
+            // (turns of the road going the same way, turns of the road)
+            let score = |phase: &SignalPhase| {
+                let same_road = phase.turns.iter().filter(|&&t| road_of(t) == road);
+                let same_way = same_road
+                    .clone()
+                    .filter_map(|&t| turns.iter().find(|x| x.id == t))
+                    .filter(|x| is_left(*x) == is_left(turn))
+                    .count();
+                (same_way, same_road.count())
+            };
+
+            let best = self
+                .phases
+                .iter()
+                .enumerate()
+                .map(|(i, p)| (i, score(p)))
+                .filter(|&(_, (_, same_road))| same_road > 0)
+                .max_by_key(|&(_, score)| score);
+            if let Some((i, _)) = best {
+                self.phases[i].turns.push(turn.id);
+            }
+        }
+    }
+
+    /// Driving turns that are not in any phase, they are always red
+    pub fn unassigned_turns<'a>(&'a self, turns: &'a [Turn]) -> impl Iterator<Item = &'a Turn> {
+        turns.iter().filter(move |t| {
+            matches!(t.kind, TurnKind::Driving)
+                && !self.phases.iter().any(|p| p.turns.contains(&t.id))
+        })
+    }
+}
+
+impl InspectRenderDefault<SignalPlan> for SignalPlan {
+    fn render(_: &[&SignalPlan], _: &'static str, _: &Ui, _: &InspectArgsDefault) {
+        unimplemented!()
+    }
+
+    fn render_mut(
+        data: &mut [&mut SignalPlan],
+        label: &'static str,
+        ui: &Ui,
+        _: &InspectArgsDefault,
+    ) -> bool {
+        if data.len() != 1 {
+            unimplemented!()
+        }
+        let plan = &mut data[0];
+        let mut changed = false;
+
+        if !imgui_inspect::imgui::CollapsingHeader::new(&im_str!("{}", label)).build(ui) {
+            return false;
+        }
+        ui.indent();
+
+        let mut orange = plan.orange_length as i32;
+        if ui.input_int(im_str!("orange"), &mut orange).build() {
+            plan.orange_length = orange.max(0) as u32;
+            changed = true;
+        }
+        let mut offset = plan.offset as i32;
+        if ui.input_int(im_str!("offset"), &mut offset).build() {
+            plan.offset = offset.max(0) as u32;
+            changed = true;
+        }
+
+        let mut to_remove = None;
+        for (i, phase) in plan.phases.iter_mut().enumerate() {
+            let id = ui.push_id(i as i32);
+            ui.text(im_str!("Phase {}: {} turns", i, phase.turns.len()));
+            let mut duration = phase.duration as i32;
+            if ui.input_int(im_str!("duration"), &mut duration).build() {
+                phase.duration = duration.max(1) as u32;
+                changed = true;
+            }
+            if ui.small_button(im_str!("Remove phase")) {
+                to_remove = Some(i);
+            }
+            id.pop(ui);
+        }
+
+        if let Some(i) = to_remove {
+            plan.phases.remove(i);
+            changed = true;
+        }
+
+        if ui.small_button(im_str!("Add phase")) {
+            plan.phases.push(SignalPhase {
+                turns: vec![],
+                duration: 10,
+            });
+            changed = true;
+        }
+
+        if ui.small_button(im_str!("Regenerate")) {
+            plan.regenerate = true;
+            changed = true;
+        }
+
+        ui.unindent();
+        changed
+    }
+}
@@ -46,7 +46,8 @@ pub struct SpatialMap {
 impl Default for SpatialMap {
     fn default() -> Self {
         Self {
-            grid: ShapeGrid::new(50),
+            // Long curved roads span hundreds of meters, keep them out of the small cells
+            grid: ShapeGrid::new_multilevel(50, 5),
             ids: Default::default(),
         }
     }
@@ -91,6 +92,13 @@ impl SpatialMap {
         self.query(Circle { center, radius })
     }
 
+    /// Objects by increasing distance from their bbox to pos, along with that distance
+    pub fn query_nearest(&self, pos: Vec2) -> impl Iterator<Item = (ProjectKind, f32)> + '_ {
+        self.grid
+            .query_nearest(pos)
+            .filter_map(move |(h, dist)| Some((*self.grid.get(h)?.1, dist)))
+    }
+
     pub fn query<'a>(
         &'a self,
         r: impl Intersect<AABB> + Clone + 'a,
@@ -40,6 +40,9 @@ pub enum TrafficControl {
     Always,
     Light(TrafficLightSchedule),
     StopSign,
+    /// Follows the signal plan of the intersection, the behavior depends on the turn taken.
+    /// See `Map::lane_behavior`
+    Plan,
 }
 
 impl TrafficControl {
@@ -55,6 +58,14 @@ impl TrafficControl {
         matches!(self, TrafficControl::Light(_))
     }
 
+    pub fn is_plan(&self) -> bool {
+        matches!(self, TrafficControl::Plan)
+    }
+
+    pub fn is_green(&self, seconds: u32) -> bool {
+        matches!(self.get_behavior(seconds), TrafficBehavior::GREEN)
+    }
+
     pub fn get_behavior(&self, seconds: u32) -> TrafficBehavior {
         match self {
             TrafficControl::Always => TrafficBehavior::GREEN,
@@ -69,6 +80,7 @@ impl TrafficControl {
                 }
             }
             TrafficControl::StopSign => TrafficBehavior::STOP,
+            TrafficControl::Plan => TrafficBehavior::GREEN,
         }
     }
 }
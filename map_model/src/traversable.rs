@@ -1,5 +1,5 @@
 use crate::{IntersectionID, LaneID, Lanes, Map, TurnID};
-use geom::PolyLine;
+use geom::{PolyLine, Vec2};
 use imgui_inspect::imgui;
 use imgui_inspect_derive::*;
 use serde::{Deserialize, Serialize};
@@ -49,11 +49,33 @@ impl Traversable {
         }
     }
 
-    pub fn can_pass(&self, time: u32, lanes: &Lanes) -> bool {
+    /// Slope at the projection of p when going along the traversable, turns being flat
+    pub fn grade_at(&self, m: &Map, p: Vec2) -> f32 {
         match self.kind {
             TraverseKind::Lane(id) => {
-                let l = unwrap_or!(lanes.get(id), return true);
-                !l.control.get_behavior(time).is_red()
+                let lane = unwrap_or!(m.lanes.get(id), return 0.0);
+                let road = unwrap_or!(m.roads.get(lane.parent), return 0.0);
+                let grade = road.grade_at(p);
+                if (lane.src == road.src) == (self.dir == TraverseDirection::Forward) {
+                    grade
+                } else {
+                    -grade
+                }
+            }
+            TraverseKind::Turn(_) => 0.0,
+        }
+    }
+
+    /// Whether a vehicle can go from this traversable to the next one
+    pub fn can_pass(&self, time: u32, next: Option<&Traversable>, map: &Map) -> bool {
+        match self.kind {
+            TraverseKind::Lane(id) => {
+                let l = unwrap_or!(map.lanes.get(id), return true);
+                let turn = match next.map(|x| x.kind) {
+                    Some(TraverseKind::Turn(t)) => Some(t),
+                    _ => None,
+                };
+                !map.lane_behavior(l, turn, time).is_red()
             }
             TraverseKind::Turn(_) => true,
         }
@@ -0,0 +1,98 @@
+use mlua::{Lua, Value};
+use std::fmt::Write;
+
+/// Signature of a function exposed to lua, from which its EmmyLua stub is generated
+pub struct LuaFn {
+    pub name: &'static str,
+    /// Lines written above the stub, may be empty
+    pub doc: &'static str,
+    /// Names and EmmyLua types of the parameters, the type can be followed by a description
+    pub params: &'static [(&'static str, &'static str)],
+    /// EmmyLua type of the returned values, empty if nothing is returned
+    pub ret: &'static str,
+}
+
+/// Functions exposed to lua together, as globals or as the methods of a userdata.
+/// Registering them through `name` keeps `lua/stdlib.lua` in sync with what scripts can call.
+pub struct LuaApi {
+    /// Lines written above the functions, may be empty
+    pub doc: &'static str,
+    /// EmmyLua class of the userdata, None for globals
+    pub class: Option<&'static str>,
+    pub functions: &'static [LuaFn],
+}
+
+impl LuaApi {
+    /// Name to register a documented function with.
+    /// Panics if it isn't documented, so a function cannot be missing from the stubs.
+    pub fn name(&self, name: &'static str) -> &'static str {
+        assert!(
+            self.functions.iter().any(|f| f.name == name),
+            "{} is not documented in its LuaApi",
+            name
+        );
+        name
+    }
+
+    /// Functions of the api that `v` doesn't have, `v` being the globals if the api has no class
+    pub fn missing<'lua>(&self, l: &'lua Lua, v: Value<'lua>) -> Vec<&'static str> {
+        self.functions
+            .iter()
+            .map(|f| f.name)
+            .filter(|name| {
+                let found = l
+                    .load("local v, name = ...\nreturn v[name] ~= nil")
+                    .call::<_, bool>((v.clone(), *name));
+                !found.unwrap_or(false)
+            })
+            .collect()
+    }
+
+    /// Appends the EmmyLua stubs of the api
+    pub fn write_stubs(&self, out: &mut String) {
+        if !self.doc.is_empty() {
+            write_doc(out, self.doc);
+            out.push('\n');
+        }
+        if let Some(class) = self.class {
+            let _ = writeln!(out, "---@class {}\nlocal {} = {{}}\n", class, class);
+        }
+
+        for f in self.functions {
+            write_doc(out, f.doc);
+            for (name, ty) in f.params {
+                let _ = writeln!(out, "---@param {} {}", name, ty);
+            }
+            if !f.ret.is_empty() {
+                let _ = writeln!(out, "---@return {}", f.ret);
+            }
+
+            let params: Vec<&str> = f.params.iter().map(|(name, _)| *name).collect();
+            let params = params.join(", ");
+            let _ = match self.class {
+                Some(class) => writeln!(out, "function {}:{}({}) end\n", class, f.name, params),
+                None => writeln!(out, "function {}({}) end\n", f.name, params),
+            };
+        }
+    }
+}
+
+fn write_doc(out: &mut String, doc: &str) {
+    for line in doc.lines() {
+        let _ = writeln!(out, "--- {}", line);
+    }
+}
+
+/// EmmyLua stubs of the apis, below a header declaring what isn't generated
+pub fn stubs(header: &str, apis: &[&LuaApi]) -> String {
+    let mut out = header.to_string();
+    for api in apis {
+        out.push('\n');
+        api.write_stubs(&mut out);
+        // a single blank line between two apis
+        while out.ends_with("\n\n") {
+            out.pop();
+        }
+    }
+    out
+}
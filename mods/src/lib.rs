@@ -1,13 +1,32 @@
-use mlua::{FromLuaMulti, Lua, TableExt};
+use mlua::{FromLuaMulti, Function, HookTriggers, Lua, StdLib, Table, TableExt, ToLuaMulti, Value};
 use std::fmt::Display;
-use std::fs::File;
-use std::io::Read;
 
 pub use mlua;
 
+mod doc;
 mod stdlib;
-use std::path::Path;
+mod watcher;
+pub use doc::*;
+use std::path::{Path, PathBuf};
 pub use stdlib::*;
+pub use watcher::*;
+
+/// Maximum memory a lua VM can allocate, in bytes
+pub const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Maximum number of instructions a single call from rust into lua can execute
+pub const INSTRUCTION_LIMIT: u32 = 10_000_000;
+
+/// The instruction budget is checked every that many instructions
+const INSTRUCTION_CHECK_PERIOD: u32 = 1000;
+
+const BUDGET_KEY: &str = "instruction_budget";
+
+/// Where `require` looks for modules, `?` is replaced by the module name
+const MODULE_PATH: &str = "lua/?.lua;?.lua";
+
+const LOADED_KEY: &str = "loaded_modules";
+const REQUIRED_FILES_KEY: &str = "required_files";
 
 trait ResultExt<T> {
     fn ok_print(self) -> Option<T>;
@@ -18,7 +37,14 @@ impl<T, E: Display> ResultExt<T> for Result<T, E> {
     }
 }
 
+/// Gives the VM a new instruction budget, must be done before each call into lua
+fn reset_budget(l: &Lua) {
+    l.set_named_registry_value(BUDGET_KEY, INSTRUCTION_LIMIT / INSTRUCTION_CHECK_PERIOD)
+        .ok_print();
+}
+
 pub fn call_f<'a, R: FromLuaMulti<'a>>(l: &'a Lua, f: &str) -> Option<R> {
+    reset_budget(l);
     l.globals().call_function(f, ()).ok_print()
 }
 
@@ -26,19 +52,277 @@ pub fn eval_f(l: &Lua, f: &str) -> Option<()> {
     call_f(l, f)
 }
 
-pub fn load<P: AsRef<Path>>(name: P) -> Option<Lua> {
+/// Returns the global function with that name, or None if the script doesn't define it
+pub fn get_f<'a>(l: &'a Lua, f: &str) -> Option<Function<'a>> {
+    l.globals().get::<_, Option<Function>>(f).ok().flatten()
+}
+
+/// Calls a lua function obtained from the VM, for example a callback registered by a script
+pub fn try_call<'a, A: ToLuaMulti<'a>, R: FromLuaMulti<'a>>(
+    l: &'a Lua,
+    f: &Function<'a>,
+    args: A,
+) -> mlua::Result<R> {
+    reset_budget(l);
+    f.call(args)
+}
+
+pub fn call<'a, A: ToLuaMulti<'a>, R: FromLuaMulti<'a>>(
+    l: &'a Lua,
+    f: &Function<'a>,
+    args: A,
+) -> Option<R> {
+    try_call(l, f, args).ok_print()
+}
+
+/// Creates a VM with only the libraries that cannot reach outside of it.
+/// `require` is restricted to lua files below the working directory and every call is limited in
+/// instructions and memory.
+pub fn sandboxed() -> Option<Lua> {
+    sandboxed_with(MODULE_PATH.to_string())
+}
+
+fn sandboxed_with(module_path: String) -> Option<Lua> {
+    let lua = Lua::new_with(
+        StdLib::COROUTINE | StdLib::TABLE | StdLib::STRING | StdLib::UTF8 | StdLib::MATH,
+    );
+
+    lua.set_memory_limit(MEMORY_LIMIT).ok_print()?;
+    lua.set_hook(
+        HookTriggers {
+            every_nth_instruction: Some(INSTRUCTION_CHECK_PERIOD),
+            ..Default::default()
+        },
+        |l, _| {
+            let budget: u32 = l.named_registry_value(BUDGET_KEY)?;
+            if budget == 0 {
+                return Err(mlua::Error::RuntimeError(
+                    "instruction limit reached".to_string(),
+                ));
+            }
+            l.set_named_registry_value(BUDGET_KEY, budget - 1)
+        },
+    );
+    reset_budget(&lua);
+
+    let require = restricted_require(&lua, module_path).ok_print()?;
+    lua.globals().set("require", require).ok_print()?;
+
+    lua.load(
+        r#"
+        dofile = nil
+        loadfile = nil
+        local unsafe_load = load
+        load = function(chunk, name, _, env)
+            return unsafe_load(chunk, name, "t", env)
+        end
+        "#,
+    )
+    .exec()
+    .ok_print()?;
+
+    Some(lua)
+}
+
+/// `require` looking for modules only in `module_path`, which scripts cannot see nor change since
+/// the `package` library is not loaded
+fn restricted_require(lua: &Lua, module_path: String) -> mlua::Result<Function> {
+    lua.set_named_registry_value(LOADED_KEY, lua.create_table()?)?;
+    lua.set_named_registry_value(REQUIRED_FILES_KEY, lua.create_table()?)?;
+
+    lua.create_function(move |l, name: String| {
+        // dots are path separators, so ".." or a leading one would leave the lua directory
+        if name.starts_with('.')
+            || name.contains("..")
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(mlua::Error::RuntimeError(format!(
+                "invalid module name {}",
+                name
+            )));
+        }
+
+        let loaded: Table = l.named_registry_value(LOADED_KEY)?;
+        let module: Value = loaded.get(name.as_str())?;
+        if !matches!(module, Value::Nil) {
+            return Ok(module);
+        }
+
+        let file = name.replace('.', "/");
+        let path = module_path
+            .split(';')
+            .map(|x| x.replace('?', &file))
+            .find(|x| Path::new(x).is_file())
+            .ok_or_else(|| mlua::Error::RuntimeError(format!("module {} not found", name)))?;
+        let data = std::fs::read_to_string(&path).map_err(|err| {
+            mlua::Error::RuntimeError(format!("could not open module {}: {}", name, err))
+        })?;
+
+        let module: Value = l
+            .load(&data)
+            .set_name(&path)?
+            .call((name.as_str(), path.as_str()))?;
+        let module = match module {
+            Value::Nil => Value::Boolean(true),
+            x => x,
+        };
+        loaded.set(name.as_str(), module.clone())?;
+
+        let files: Table = l.named_registry_value(REQUIRED_FILES_KEY)?;
+        files.set(files.raw_len() + 1, path)?;
+
+        Ok(module)
+    })
+}
+
+/// Runs a script in an existing VM, e.g. to load several mods side by side
+pub fn exec_file<P: AsRef<Path>>(l: &Lua, name: P) -> mlua::Result<()> {
     let name = name.as_ref();
-    let mut data_file = File::open(name)
-        .map_err(|err| log::error!("Could not open `{:?}`, {}", name, err))
-        .ok()?;
-
-    let mut data = String::new();
-    data_file.read_to_string(&mut data).ok()?;
-    let lua = unsafe { Lua::unsafe_new() };
-    lua.load(r#"package.path = "lua/?.lua;?.lua""#)
-        .eval()
-        .ok_print()?;
+    let data = std::fs::read_to_string(name)
+        .map_err(|err| mlua::Error::RuntimeError(format!("could not open {:?}: {}", name, err)))?;
+
+    reset_budget(l);
+    l.load(&data)
+        .set_name(&*name.to_string_lossy())?
+        .eval::<()>()
+}
+
+/// Runs every script of a directory in the VM, reporting the ones that fail without stopping.
+/// Returns the files to watch for reloading: the scripts and the modules they required.
+pub fn exec_dir(
+    l: &Lua,
+    dir: impl AsRef<Path>,
+    mut on_error: impl FnMut(&Path, mlua::Error),
+) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.extension().map_or(false, |x| x == "lua"))
+        .collect();
+    files.sort();
+
+    for file in &files {
+        if let Err(e) = exec_file(l, file) {
+            on_error(file, e);
+        }
+    }
+
+    files.extend(required_files(l));
+    files
+        .into_iter()
+        .map(|x| x.canonicalize().unwrap_or(x))
+        .collect()
+}
+
+/// Whether a script must be reloaded: one of the changed files is in its directory or among the
+/// files returned by `exec_dir`
+pub fn is_affected(changed: &[PathBuf], dir: impl AsRef<Path>, files: &[PathBuf]) -> bool {
+    changed.iter().any(|x| {
+        x.starts_with(dir.as_ref()) || x.canonicalize().map_or(false, |x| files.contains(&x))
+    })
+}
+
+/// Creates a sandboxed VM with the standard library
+pub fn try_new() -> mlua::Result<Lua> {
+    try_new_with(MODULE_PATH.to_string())
+}
+
+fn try_new_with(module_path: String) -> mlua::Result<Lua> {
+    let lua = sandboxed_with(module_path)
+        .ok_or_else(|| mlua::Error::RuntimeError("could not create the lua VM".to_string()))?;
     add_std(&lua);
-    lua.load(&data).eval().ok_print()?;
-    Some(lua)
+    Ok(lua)
+}
+
+/// Loads a script in a sandboxed VM, returning why it failed so it can be shown to the player.
+/// Modules are required from the `lua` directory the script is in, wherever the game runs from.
+pub fn try_load<P: AsRef<Path>>(name: P) -> mlua::Result<Lua> {
+    let name = name.as_ref();
+    let module_path = match name.ancestors().find(|x| x.ends_with("lua")) {
+        Some(root) => format!("{}/?.lua;{}", root.to_string_lossy(), MODULE_PATH),
+        None => MODULE_PATH.to_string(),
+    };
+    let lua = try_new_with(module_path)?;
+    exec_file(&lua, name)?;
+    Ok(lua)
+}
+
+pub fn load<P: AsRef<Path>>(name: P) -> Option<Lua> {
+    try_load(name).ok_print()
+}
+
+/// Files of the modules loaded with `require`, a script must be reloaded when one of them changes
+pub fn required_files(l: &Lua) -> Vec<PathBuf> {
+    let files: Option<Vec<String>> = l.named_registry_value(REQUIRED_FILES_KEY).ok_print();
+    files.into_iter().flatten().map(PathBuf::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reset_budget, sandboxed, try_call, try_load, MEMORY_LIMIT};
+    use mlua::Function;
+
+    fn run_err(code: &str) -> String {
+        let lua = sandboxed().unwrap();
+        reset_budget(&lua);
+        lua.load(code).exec().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_no_unsafe_libs() {
+        let lua = sandboxed().unwrap();
+        let absent: bool = lua
+            .load("return io == nil and os == nil and debug == nil and package == nil")
+            .eval()
+            .unwrap();
+        assert!(absent);
+    }
+
+    #[test]
+    fn test_instruction_limit() {
+        let lua = sandboxed().unwrap();
+        let f: Function = lua
+            .load("return function() while true do end end")
+            .eval()
+            .unwrap();
+        let err = try_call::<_, ()>(&lua, &f, ()).unwrap_err();
+        assert!(err.to_string().contains("instruction limit reached"));
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let err = run_err(&format!("local x = string.rep('x', {})", MEMORY_LIMIT + 1));
+        assert!(err.contains("memory"), "{}", err);
+    }
+
+    #[test]
+    fn test_require_outside_rejected() {
+        assert!(run_err(r#"require("../x")"#).contains("invalid module name"));
+        assert!(run_err(r#"require(".x")"#).contains("invalid module name"));
+    }
+
+    #[test]
+    fn test_require_path_cannot_change() {
+        // the tests run in the crate directory, where Cargo.toml exists
+        let err = run_err(r#"package = { path = "?.toml" } require("Cargo")"#);
+        assert!(err.contains("module Cargo not found"), "{}", err);
+        let err = run_err(r#"require.path = "?.toml""#);
+        assert!(err.contains("index"), "{}", err);
+    }
+
+    #[test]
+    fn test_require_next_to_script() {
+        let dir = std::env::temp_dir().join("mods_test_require").join("lua");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("answer.lua"), "return 42").unwrap();
+        std::fs::write(dir.join("main.lua"), "x = require('answer')").unwrap();
+
+        let lua = try_load(dir.join("main.lua")).unwrap();
+        assert_eq!(lua.globals().get::<_, i32>("x").unwrap(), 42);
+        assert_eq!(super::required_files(&lua), vec![dir.join("answer.lua")]);
+    }
 }
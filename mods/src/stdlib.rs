@@ -1,3 +1,4 @@
+use crate::{LuaApi, LuaFn};
 use geom::Polygon;
 use geom::Vec2;
 use mlua::prelude::LuaResult;
@@ -6,22 +7,84 @@ use mlua::{FromLuaMulti, Lua, MetaMethod, ToLuaMulti, UserData, UserDataMethods}
 #[derive(Clone)]
 pub struct LuaPolygon(pub Polygon);
 
+pub const POLYGON_API: LuaApi = LuaApi {
+    doc: "",
+    class: Some("Polygon"),
+    functions: &[
+        LuaFn {
+            name: "len",
+            doc: "",
+            params: &[],
+            ret: "number",
+        },
+        LuaFn {
+            name: "point",
+            doc: "0-indexed, like segments",
+            params: &[("i", "number")],
+            ret: "Vec2|nil",
+        },
+        LuaFn {
+            name: "extrude",
+            doc: "Moves the i-th segment outwards by dist",
+            params: &[("i", "number"), ("dist", "number")],
+            ret: "",
+        },
+        LuaFn {
+            name: "split_segment",
+            doc: "Inserts a point on the i-th segment, coeff being between 0 and 1",
+            params: &[("i", "number"), ("coeff", "number")],
+            ret: "",
+        },
+        LuaFn {
+            name: "barycenter",
+            doc: "",
+            params: &[],
+            ret: "Vec2",
+        },
+        LuaFn {
+            name: "translate",
+            doc: "",
+            params: &[("v", "Vec2")],
+            ret: "",
+        },
+        LuaFn {
+            name: "rotate",
+            doc: "Rotates around the origin by the angle whose cosine and sine are the x and y \
+                  of cossin",
+            params: &[("cossin", "Vec2")],
+            ret: "",
+        },
+    ],
+};
+
 impl UserData for LuaPolygon {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_method_mut("extrude", |_, p, (seg, dist): (i32, f32)| {
-            p.0.extrude(seg as usize, dist);
-            Ok(())
+        methods.add_method_mut(
+            POLYGON_API.name("extrude"),
+            |_, p, (seg, dist): (i32, f32)| {
+                p.0.extrude(seg as usize, dist);
+                Ok(())
+            },
+        );
+        methods.add_method_mut(
+            POLYGON_API.name("split_segment"),
+            |_, p, (seg, coeff): (i32, f32)| {
+                p.0.split_segment(seg as usize, coeff);
+                Ok(())
+            },
+        );
+        methods.add_method(POLYGON_API.name("len"), |_, p, ()| Ok(p.0.len()));
+        methods.add_method(POLYGON_API.name("point"), |_, p, i: i32| {
+            Ok(p.0.as_slice().get(i as usize).map(|&x| LuaVec2(x)))
         });
-        methods.add_method_mut("split_segment", |_, p, (seg, coeff): (i32, f32)| {
-            p.0.split_segment(seg as usize, coeff);
-            Ok(())
+        methods.add_method_mut(POLYGON_API.name("barycenter"), |_, p, (): ()| {
+            Ok(LuaVec2(p.0.barycenter()))
         });
-        methods.add_method_mut("barycenter", |_, p, (): ()| Ok(LuaVec2(p.0.barycenter())));
-        methods.add_method_mut("translate", |_, p, vec: LuaVec2| {
+        methods.add_method_mut(POLYGON_API.name("translate"), |_, p, vec: LuaVec2| {
             p.0.translate(vec.0);
             Ok(())
         });
-        methods.add_method_mut("rotate", |_, p, cossin: LuaVec2| {
+        methods.add_method_mut(POLYGON_API.name("rotate"), |_, p, cossin: LuaVec2| {
             p.0.rotate(cossin.0);
             Ok(())
         });
@@ -31,15 +94,56 @@ impl UserData for LuaPolygon {
 #[derive(Copy, Clone)]
 pub struct LuaVec2(pub Vec2);
 
+pub const VEC2_API: LuaApi = LuaApi {
+    doc: "",
+    class: Some("Vec2"),
+    functions: &[
+        LuaFn {
+            name: "magnitude",
+            doc: "",
+            params: &[],
+            ret: "number",
+        },
+        LuaFn {
+            name: "normalize",
+            doc: "Returns the unit x vector if the vector is zero",
+            params: &[],
+            ret: "Vec2",
+        },
+        LuaFn {
+            name: "distance",
+            doc: "",
+            params: &[("other", "Vec2")],
+            ret: "number",
+        },
+        LuaFn {
+            name: "x",
+            doc: "",
+            params: &[],
+            ret: "number",
+        },
+        LuaFn {
+            name: "y",
+            doc: "",
+            params: &[],
+            ret: "number",
+        },
+    ],
+};
+
 impl UserData for LuaVec2 {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_method("magnitude", |_, vec, ()| Ok(vec.0.magnitude()));
-        methods.add_method("normalize", |_, vec, ()| {
+        methods.add_method(VEC2_API.name("magnitude"), |_, vec, ()| {
+            Ok(vec.0.magnitude())
+        });
+        methods.add_method(VEC2_API.name("normalize"), |_, vec, ()| {
             Ok(LuaVec2(vec.0.try_normalize().unwrap_or(Vec2::UNIT_X)))
         });
-        methods.add_method("distance", |_, vec, v: LuaVec2| Ok(vec.0.distance(v.0)));
-        methods.add_method("x", |_, vec, ()| Ok(vec.0.x));
-        methods.add_method("y", |_, vec, ()| Ok(vec.0.y));
+        methods.add_method(VEC2_API.name("distance"), |_, vec, v: LuaVec2| {
+            Ok(vec.0.distance(v.0))
+        });
+        methods.add_method(VEC2_API.name("x"), |_, vec, ()| Ok(vec.0.x));
+        methods.add_method(VEC2_API.name("y"), |_, vec, ()| Ok(vec.0.y));
 
         methods.add_meta_function(MetaMethod::Unm, |_, vec1: LuaVec2| Ok(LuaVec2(-vec1.0)));
 
@@ -93,8 +197,53 @@ fn vec2(_: &Lua, (x, y): (f32, f32)) -> LuaResult<LuaVec2> {
     Ok(LuaVec2(Vec2 { x, y }))
 }
 
+/// Globals added by `add_std`
+pub const STD_API: LuaApi = LuaApi {
+    doc: "",
+    class: None,
+    functions: &[
+        LuaFn {
+            name: "vec2",
+            doc: "",
+            params: &[("x", "number"), ("y", "number")],
+            ret: "Vec2",
+        },
+        LuaFn {
+            name: "poly_rect",
+            doc: "",
+            params: &[("w", "number"), ("h", "number")],
+            ret: "Polygon",
+        },
+        LuaFn {
+            name: "rand_in",
+            doc: "",
+            params: &[("min", "number"), ("max", "number")],
+            ret: "number",
+        },
+    ],
+};
+
 pub fn add_std(lua: &Lua) {
-    add_fn(lua, "poly_rect", poly_rect);
-    add_fn(lua, "rand_in", rand_in);
-    add_fn(lua, "vec2", vec2);
+    add_fn(lua, STD_API.name("poly_rect"), poly_rect);
+    add_fn(lua, STD_API.name("rand_in"), rand_in);
+    add_fn(lua, STD_API.name("vec2"), vec2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LuaPolygon, LuaVec2, POLYGON_API, STD_API, VEC2_API};
+    use geom::{Polygon, Vec2};
+    use mlua::{ToLua, Value};
+
+    #[test]
+    fn test_documented_functions_exist() {
+        let l = crate::try_new().unwrap();
+        let vec = LuaVec2(Vec2::ZERO).to_lua(&l).unwrap();
+        let poly = LuaPolygon(Polygon::rect(1.0, 1.0)).to_lua(&l).unwrap();
+
+        let none: Vec<&str> = vec![];
+        assert_eq!(STD_API.missing(&l, Value::Table(l.globals())), none);
+        assert_eq!(VEC2_API.missing(&l, vec), none);
+        assert_eq!(POLYGON_API.missing(&l, poly), none);
+    }
 }
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The lua directory is scanned at most that often
+const POLL_PERIOD: Duration = Duration::from_millis(500);
+
+/// Watches the scripts of a directory by polling their modification time
+pub struct LuaWatcher {
+    root: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+}
+
+impl LuaWatcher {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let mut me = Self {
+            root: root.into(),
+            modified: HashMap::new(),
+            last_poll: Instant::now(),
+        };
+        me.poll();
+        me
+    }
+
+    /// Returns the scripts that were created or modified since the last call
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < POLL_PERIOD {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+        self.poll()
+    }
+
+    fn poll(&mut self) -> Vec<PathBuf> {
+        let mut files = vec![];
+        list_scripts(&self.root, &mut files);
+
+        let mut changed = vec![];
+        for (path, time) in files {
+            if self.modified.insert(path.clone(), time) != Some(time) {
+                changed.push(path);
+            }
+        }
+        changed
+    }
+}
+
+fn list_scripts(dir: &Path, files: &mut Vec<(PathBuf, SystemTime)>) {
+    for entry in std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x.ok())
+    {
+        let path = entry.path();
+        if path.is_dir() {
+            list_scripts(&path, files);
+            continue;
+        }
+        if path.extension().map_or(true, |x| x != "lua") {
+            continue;
+        }
+        if let Some(time) = entry.metadata().ok().and_then(|x| x.modified().ok()) {
+            files.push((path, time));
+        }
+    }
+}
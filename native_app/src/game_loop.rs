@@ -1,5 +1,6 @@
 use crate::audio::GameAudio;
 use crate::context::Context;
+use crate::gui::windows::console::ConsoleRequests;
 use crate::gui::windows::debug::DebugObjs;
 use crate::gui::{FollowEntity, Gui, Settings, UiTextures};
 use crate::rendering::imgui_wrapper::ImguiWrapper;
@@ -8,16 +9,23 @@ use crate::rendering::{
 };
 use common::GameTime;
 use egregoria::engine_interaction::{KeyboardInfo, MouseInfo, RenderStats};
+use egregoria::map_dynamic::SignalActuation;
 use egregoria::rendering::immediate::{ImmediateDraw, ImmediateOrder, ImmediateSound, OrderKind};
+use egregoria::scenarios::building_shapes::BuildingShapes;
+use egregoria::scenarios::console::LuaConsole;
+use egregoria::scenarios::map_generators::MapGenerators;
+use egregoria::scenarios::scenario_runner::reload_scenario;
 use egregoria::souls::add_souls_to_empty_buildings;
+use egregoria::souls::desire::LuaDesires;
 use egregoria::{load_from_disk, Egregoria};
 use geom::Camera;
 use geom::{vec3, LinearColor, Vec2};
 use map_model::Map;
+use mods::LuaWatcher;
 use std::borrow::Cow;
 use std::time::Instant;
 use wgpu_engine::lighting::{LightInstance, LightRender};
-use wgpu_engine::{FrameContext, GfxContext, GuiRenderContext};
+use wgpu_engine::{FrameContext, GfxContext, GuiRenderContext, Mesh};
 use winit::dpi::PhysicalSize;
 use winit::window::{Fullscreen, Window};
 
@@ -36,6 +44,8 @@ pub struct State {
     pub light: LightRender,
 
     all_audio: GameAudio,
+
+    lua_watcher: LuaWatcher,
 }
 
 impl State {
@@ -62,6 +72,13 @@ impl State {
 
         load_from_disk(&mut goria);
 
+        let mut console = goria.write::<LuaConsole>();
+        goria
+            .write::<BuildingShapes>()
+            .reload(&mut console, &mut goria.write::<Map>());
+        goria.write::<LuaDesires>().reload(&mut console);
+        drop(console);
+
         let gui: Gui = common::saveload::load_json("gui").unwrap_or_default();
 
         goria.insert(camera.camera);
@@ -77,6 +94,7 @@ impl State {
             gui,
             all_audio: GameAudio::new(&mut ctx.audio),
             light: LightRender::new(&mut ctx.gfx),
+            lua_watcher: LuaWatcher::new("lua"),
         };
         me.manage_settings(ctx, me.gui.settings);
         me
@@ -93,6 +111,8 @@ impl State {
 
         self.manage_settings(ctx, self.gui.settings);
 
+        self.manage_console_requests();
+
         self.manage_time(delta, &mut ctx.gfx);
 
         self.manage_io(ctx);
@@ -112,6 +132,23 @@ impl State {
                 self.camera.unproject(ctx.input.mouse.screen);
         }
 
+        let changed = self.lua_watcher.changed();
+        if !changed.is_empty() {
+            reload_scenario(&mut self.goria, &changed);
+            let mut console = self.goria.write::<LuaConsole>();
+            self.goria
+                .write::<MapGenerators>()
+                .reload_if_affected(&changed, &mut console);
+            self.goria.write::<BuildingShapes>().reload_if_affected(
+                &changed,
+                &mut console,
+                &mut self.goria.write::<Map>(),
+            );
+            self.goria
+                .write::<LuaDesires>()
+                .reload_if_affected(&changed, &mut console);
+        }
+
         self.goria.run();
 
         add_souls_to_empty_buildings(&mut self.goria);
@@ -134,8 +171,13 @@ impl State {
         let mut tess = self.camera.culled_tesselator();
 
         let time: GameTime = *self.goria.read::<GameTime>();
-        self.road_renderer
-            .render(&mut self.goria.write::<Map>(), time.seconds, &mut tess, ctx);
+        self.road_renderer.render(
+            &mut self.goria.write::<Map>(),
+            &self.goria.read::<SignalActuation>(),
+            time.seconds,
+            &mut tess,
+            ctx,
+        );
 
         self.instanced_renderer.render(&mut self.goria, ctx);
 
@@ -201,7 +243,7 @@ impl State {
             immediate.orders.clear();
         }
 
-        if let Some(x) = tess.meshbuilder.build(ctx.gfx) {
+        if let Some(x) = Mesh::new(ctx.gfx, &tess.meshbuilder) {
             ctx.draw(x)
         }
 
@@ -295,6 +337,18 @@ impl State {
         gfx.set_time(time.timestamp as f32);
     }
 
+    fn manage_console_requests(&mut self) {
+        let mut requests = self.goria.write::<ConsoleRequests>();
+        if let Some(warp) = requests.time_warp.take() {
+            self.gui.settings.time_warp = warp;
+        }
+        if let Some(pos) = requests.camera_pos.take() {
+            self.goria.write::<FollowEntity>().0.take();
+            self.camera.camera.position.x = pos.x;
+            self.camera.camera.position.y = pos.y;
+        }
+    }
+
     fn manage_entity_follow(&mut self) {
         if !self.goria.read::<MouseInfo>().just_pressed.is_empty() {
             self.goria.write::<FollowEntity>().0.take();
@@ -1,7 +1,7 @@
 use egregoria::engine_interaction::{KeyCode, KeyboardInfo};
 use imgui::TextureId;
 use legion::{system, Entity};
-use roadbuild::RoadBuildResource;
+use roadbuild::{CurveKind, RoadBuildResource};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use wgpu_engine::GfxContext;
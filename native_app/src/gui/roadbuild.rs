@@ -4,9 +4,12 @@ use egregoria::rendering::immediate::{ImmediateDraw, ImmediateSound};
 use geom::Color;
 use geom::Spline;
 use geom::Vec2;
+use geom::{Clothoid, PolyLine};
 use legion::system;
+use map_model::procgen::heightmap::elevation;
 use map_model::{
-    IntersectionID, LanePattern, LanePatternBuilder, Map, MapProject, ProjectKind, RoadSegmentKind,
+    IntersectionID, LanePattern, LanePatternBuilder, Map, MapProject, ProjectKind, Road,
+    RoadSegmentKind,
 };
 
 const MAX_TURN_ANGLE: f32 = 30.0 * std::f32::consts::PI / 180.0;
@@ -24,11 +27,69 @@ impl Default for BuildState {
     }
 }
 
+/// Curve joining both ends of a curved road through the interpolation point
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CurveKind {
+    Spline,
+    Clothoid,
+}
+
+impl Default for CurveKind {
+    fn default() -> Self {
+        CurveKind::Spline
+    }
+}
+
+impl CurveKind {
+    fn spline(from: Vec2, to: Vec2, elbow: Vec2) -> Spline {
+        Spline {
+            from,
+            to,
+            from_derivative: (elbow - from) * std::f32::consts::FRAC_1_SQRT_2,
+            to_derivative: (to - elbow) * std::f32::consts::FRAC_1_SQRT_2,
+        }
+    }
+
+    fn clothoid(from: Vec2, to: Vec2, elbow: Vec2) -> Option<Clothoid> {
+        Clothoid::fit(from, elbow - from, to, to - elbow)
+    }
+
+    fn segment(self, from: Vec2, to: Vec2, elbow: Vec2) -> RoadSegmentKind {
+        match self {
+            CurveKind::Spline => RoadSegmentKind::from_elbow(from, to, elbow),
+            CurveKind::Clothoid => RoadSegmentKind::clothoid_from_elbow(from, to, elbow),
+        }
+    }
+
+    /// Whether a road that wide would fold on itself, or cannot be made at all
+    fn is_steep(self, from: Vec2, to: Vec2, elbow: Vec2, thickness: f32) -> bool {
+        match self {
+            CurveKind::Spline => Self::spline(from, to, elbow).is_steep(thickness),
+            CurveKind::Clothoid => Self::clothoid(from, to, elbow)
+                .map(|c| c.is_steep(thickness))
+                .unwrap_or(true),
+        }
+    }
+
+    fn points(self, from: Vec2, to: Vec2, elbow: Vec2) -> Vec<Vec2> {
+        match self {
+            CurveKind::Spline => Self::spline(from, to, elbow)
+                .smart_points(1.0, 0.0, 1.0)
+                .collect(),
+            CurveKind::Clothoid => Self::clothoid(from, to, elbow)
+                .map(|c| c.flatten(0.1))
+                .map(PolyLine::into_vec)
+                .unwrap_or_else(|| vec![from, to]),
+        }
+    }
+}
+
 register_resource_noserialize!(RoadBuildResource);
 #[derive(Default)]
 pub struct RoadBuildResource {
     build_state: BuildState,
     pub pattern_builder: LanePatternBuilder,
+    pub curve: CurveKind,
 }
 
 use common::AudioKind;
@@ -84,22 +145,35 @@ pub fn roadbuild(
     let is_valid = match (state.build_state, cur_proj.kind) {
         (Hover, Building(_)) => false,
         (Start(selected_proj), _) => {
+            // Placing the interpolation point, the curve can be long enough to climb the slope
+            let picks_elbow = matches!((*tool, cur_proj.kind), (Tool::RoadbuildCurved, Ground));
+
             compatible(map, cur_proj.kind, selected_proj.kind)
                 && check_angle(map, selected_proj, cur_proj.pos)
                 && check_angle(map, cur_proj, selected_proj.pos)
+                && (picks_elbow
+                    || check_grade(
+                        map,
+                        selected_proj,
+                        cur_proj,
+                        selected_proj.pos.distance(cur_proj.pos),
+                    ))
         }
         (Interpolation(interpoint, selected_proj), _) => {
-            let sp = Spline {
-                from: selected_proj.pos,
-                to: cur_proj.pos,
-                from_derivative: (interpoint - selected_proj.pos) * std::f32::consts::FRAC_1_SQRT_2,
-                to_derivative: (cur_proj.pos - interpoint) * std::f32::consts::FRAC_1_SQRT_2,
-            };
+            let points = state
+                .curve
+                .points(selected_proj.pos, cur_proj.pos, interpoint);
 
             compatible(map, cur_proj.kind, selected_proj.kind)
                 && check_angle(map, selected_proj, interpoint)
                 && check_angle(map, cur_proj, interpoint)
-                && !sp.is_steep(state.pattern_builder.width())
+                && !state.curve.is_steep(
+                    selected_proj.pos,
+                    cur_proj.pos,
+                    interpoint,
+                    state.pattern_builder.width(),
+                )
+                && check_grade(map, selected_proj, cur_proj, PolyLine::new(points).length())
         }
         _ => true,
     };
@@ -132,6 +206,7 @@ pub fn roadbuild(
                     cur_proj,
                     None,
                     &state.pattern_builder.build(),
+                    state.curve,
                 );
 
                 let hover = MapProject {
@@ -150,6 +225,7 @@ pub fn roadbuild(
                     cur_proj,
                     Some(interpoint),
                     &state.pattern_builder.build(),
+                    state.curve,
                 );
 
                 let hover = MapProject {
@@ -170,11 +246,12 @@ fn make_connection(
     to: MapProject,
     interpoint: Option<Vec2>,
     pattern: &LanePattern,
+    curve: CurveKind,
 ) -> IntersectionID {
     use ProjectKind::*;
 
     let connection_segment = match interpoint {
-        Some(x) => RoadSegmentKind::from_elbow(from.pos, to.pos, x),
+        Some(x) => curve.segment(from.pos, to.pos, x),
         None => RoadSegmentKind::Straight,
     };
 
@@ -220,6 +297,15 @@ fn check_angle(map: &Map, from: MapProject, to: Vec2) -> bool {
     }
 }
 
+/// Whether a road of that length between both ends stays under the maximum grade
+fn check_grade(map: &Map, from: MapProject, to: MapProject, length: f32) -> bool {
+    let z = |proj: MapProject| match proj.kind {
+        Inter(i) => map.intersections()[i].z,
+        _ => elevation(proj.pos),
+    };
+    Road::grade_ok(z(from), z(to), length)
+}
+
 fn compatible(map: &Map, x: ProjectKind, y: ProjectKind) -> bool {
     use ProjectKind::*;
     match (x, y) {
@@ -278,22 +364,13 @@ impl RoadBuildResource {
                 immdraw.line(proj_pos, x.pos, patwidth).color(col).z(Z_TOOL);
             }
             BuildState::Interpolation(p, x) => {
-                let sp = Spline {
-                    from: x.pos,
-                    to: proj_pos,
-                    from_derivative: (p - x.pos) * std::f32::consts::FRAC_1_SQRT_2,
-                    to_derivative: (proj_pos - p) * std::f32::consts::FRAC_1_SQRT_2,
-                };
-                let points: Vec<_> = sp.smart_points(1.0, 0.0, 1.0).collect();
+                let points = self.curve.points(x.pos, proj_pos, p);
 
                 immdraw.polyline(points, patwidth).color(col).z(Z_TOOL);
 
+                immdraw.circle(x.pos, patwidth * 0.5).color(col).z(Z_TOOL);
                 immdraw
-                    .circle(sp.get(0.0), patwidth * 0.5)
-                    .color(col)
-                    .z(Z_TOOL);
-                immdraw
-                    .circle(sp.get(1.0), patwidth * 0.5)
+                    .circle(proj_pos, patwidth * 0.5)
                     .color(col)
                     .z(Z_TOOL);
             }
@@ -1,14 +1,16 @@
 use crate::gui::{InspectedEntity, Tool, Z_TOOL};
 use egregoria::engine_interaction::{MouseButton, MouseInfo};
 use egregoria::rendering::immediate::ImmediateDraw;
+use egregoria::vehicles::VehicleKind;
 use geom::Color;
 use imgui_inspect_derive::*;
 use legion::systems::CommandBuffer;
 use legion::world::SubWorld;
 use legion::Entity;
 use legion::{system, IntoQuery};
-use map_model::{IntersectionID, LightPolicy, TurnPolicy};
+use map_model::{IntersectionID, LightPolicy, LightTiming, SignalPlan, TurnKind, TurnPolicy};
 use map_model::{Map, ProjectKind};
+use ordered_float::OrderedFloat;
 
 #[derive(Clone, Inspect)]
 pub struct IntersectionComponent {
@@ -16,6 +18,12 @@ pub struct IntersectionComponent {
     pub id: IntersectionID,
     pub turn_policy: TurnPolicy,
     pub light_policy: LightPolicy,
+    pub light_timing: LightTiming,
+    /// Coordinate the lights along the widest road going through this intersection
+    pub green_wave: bool,
+    pub signal_plan: SignalPlan,
+    /// Phase of the signal plan shown on the map, click on turns to add or remove them
+    pub edited_phase: usize,
 }
 
 register_resource_noserialize!(RoadEditorResource);
@@ -54,13 +62,73 @@ pub fn roadeditor(
         .color(Color::BLUE)
         .z(Z_TOOL);
 
+    let selected = state
+        .inspect_e
+        .and_then(|e| <&IntersectionComponent>::query().get(sw, e).ok())
+        .filter(|c| matches!(c.light_policy, LightPolicy::Manual))
+        .cloned();
+
+    // Preview the edited phase of the signal plan, turns that are in no phase are shown in red
+    if let Some(ref comp) = selected {
+        if let Some(inter) = map.intersections().get(comp.id) {
+            let phase = comp.signal_plan.phases.get(comp.edited_phase);
+            let unassigned: Vec<_> = comp
+                .signal_plan
+                .unassigned_turns(inter.turns())
+                .map(|t| t.id)
+                .collect();
+            for turn in inter.turns() {
+                if !matches!(turn.kind, TurnKind::Driving) {
+                    continue;
+                }
+                let green = phase.map_or(false, |p| p.turns.contains(&turn.id));
+                let color = if green {
+                    Color::GREEN
+                } else if unassigned.contains(&turn.id) {
+                    Color::RED
+                } else {
+                    Color::gray(0.6).a(0.5)
+                };
+                imm_draw
+                    .polyline(turn.points.as_slice(), if green { 0.6 } else { 0.2 })
+                    .color(color)
+                    .z(Z_TOOL);
+            }
+        }
+    }
+
     if mouseinfo.just_pressed.contains(&MouseButton::Left) {
         if let ProjectKind::Inter(id) = cur_proj.kind {
+            let clicked_turn = selected.as_ref().filter(|c| c.id == id).and_then(|_| {
+                map.intersections()[id]
+                    .turns()
+                    .iter()
+                    .filter(|t| matches!(t.kind, TurnKind::Driving))
+                    .map(|t| (t.id, t.points.project_dist(mouseinfo.unprojected)))
+                    .filter(|&(_, d)| d < 1.5)
+                    .min_by_key(|&(_, d)| OrderedFloat(d))
+                    .map(|(t, _)| t)
+            });
+
+            if let (Some(mut comp), Some(turn)) = (selected, clicked_turn) {
+                comp.signal_plan.toggle_turn(comp.edited_phase, turn);
+                let plan = comp.signal_plan.clone();
+                map.update_intersection(id, |inter| inter.signal_plan = plan.clone());
+                if let Some(e) = state.inspect_e {
+                    buf.add_component(e, comp);
+                }
+                return;
+            }
+
             let inter = &map.intersections()[id];
             state.inspect_e = Some(buf.push((IntersectionComponent {
                 id,
                 turn_policy: inter.turn_policy,
                 light_policy: inter.light_policy,
+                light_timing: inter.light_timing,
+                green_wave: inter.light_timing.coordination.is_some(),
+                signal_plan: inter.signal_plan.clone(),
+                edited_phase: 0,
             },)));
             inspected.e = state.inspect_e;
         }
@@ -69,10 +137,44 @@ pub fn roadeditor(
     if let Some(insp) = state.inspect_e {
         if inspected.e == Some(insp) && inspected.dirty {
             let selected_interc = <&IntersectionComponent>::query().get(sw, insp).unwrap();
-            map.update_intersection(selected_interc.id, |inter| {
+            let id = selected_interc.id;
+            let was_coordinated = map.intersections()[id].light_timing.coordination.is_some();
+            let was_manual = matches!(map.intersections()[id].light_policy, LightPolicy::Manual);
+            if was_coordinated && !selected_interc.green_wave {
+                map.clear_corridor(id);
+            }
+
+            map.update_intersection(id, |inter| {
                 inter.turn_policy = selected_interc.turn_policy;
                 inter.light_policy = selected_interc.light_policy;
+                let coordination = inter.light_timing.coordination;
+                inter.light_timing = selected_interc.light_timing;
+                inter.light_timing.coordination = coordination;
+                inter.signal_plan = selected_interc.signal_plan.clone();
+                // Switching to a manual plan starts from a generated one
+                if !was_manual
+                    && matches!(inter.light_policy, LightPolicy::Manual)
+                    && inter.signal_plan.phases.is_empty()
+                {
+                    inter.signal_plan.regenerate = true;
+                }
             });
+
+            if selected_interc.green_wave && !was_coordinated {
+                let main_road = map.intersections()[id]
+                    .roads
+                    .iter()
+                    .copied()
+                    .max_by_key(|&r| OrderedFloat(map.roads()[r].width));
+                if let Some(road) = main_road {
+                    map.coordinate_corridor(id, road, VehicleKind::Car.cruising_speed());
+                }
+            }
+
+            // The plan might have been generated, pruned or extended by the map
+            let mut synced = selected_interc.clone();
+            synced.signal_plan = map.intersections()[id].signal_plan.clone();
+            buf.add_component(insp, synced);
         }
     }
 }
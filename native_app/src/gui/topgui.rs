@@ -2,14 +2,14 @@ use crate::gui::lotbrush::LotBrushResource;
 use crate::gui::settings::Settings;
 use crate::gui::specialbuilding::SpecialBuildingResource;
 use crate::gui::windows::ImguiWindows;
-use crate::gui::{InspectedEntity, RoadBuildResource, Tool, UiTex, UiTextures};
+use crate::gui::{CurveKind, InspectedEntity, RoadBuildResource, Tool, UiTex, UiTextures};
 use common::GameTime;
 use egregoria::engine_interaction::{KeyCode, KeyboardInfo};
 use egregoria::Egregoria;
 use imgui::{im_str, StyleColor, StyleVar};
 use imgui::{Ui, Window};
 use imgui_inspect::{InspectArgsStruct, InspectRenderStruct};
-use map_model::{LanePatternBuilder, LotKind};
+use map_model::{BuildingKind, LanePatternBuilder, LotKind};
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 
@@ -165,6 +165,13 @@ impl Gui {
                     }
 
                     goria.write::<RoadBuildResource>().pattern_builder = pattern;
+
+                    if matches!(*goria.read::<Tool>(), Tool::RoadbuildCurved) {
+                        let mut state = goria.write::<RoadBuildResource>();
+                        ui.text("Curve");
+                        ui.radio_button(im_str!("Spline"), &mut state.curve, CurveKind::Spline);
+                        ui.radio_button(im_str!("Clothoid"), &mut state.curve, CurveKind::Clothoid);
+                    }
                 });
         }
 
@@ -252,6 +259,18 @@ impl Gui {
                         tok.pop(ui);
                     }
 
+                    let tok = ui.push_style_var(StyleVar::Alpha(
+                        if matches!(cur_kind, BuildingKind::ParkingLot) {
+                            1.0
+                        } else {
+                            0.5
+                        },
+                    ));
+                    if ui.button(im_str!("Parking lot"), [building_select_w, 35.0]) {
+                        cur_build.opt = Some((BuildingKind::ParkingLot, 40.0));
+                    }
+                    tok.pop(ui);
+
                     let bdescrpt_w = 180.0;
 
                     if let Some(descr) = picked_descr {
@@ -0,0 +1,185 @@
+use super::{ConsoleCommand, ConsoleRequests};
+use crate::gui::windows::debug::DebugObjs;
+use crate::gui::UiTextures;
+use egregoria::scenarios::building_shapes::BuildingShapes;
+use egregoria::scenarios::console::LuaConsole;
+use egregoria::souls::desire::LuaDesires;
+use egregoria::vehicles::{spawn_parked_vehicle, VehicleKind};
+use egregoria::Egregoria;
+use geom::{vec2, Camera};
+use legion::systems::Resource;
+use map_model::Map;
+use mods::mlua::{Function, Lua, MultiValue};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+fn parse<T: FromStr>(args: &[&str], i: usize, what: &str) -> Result<T, String> {
+    let arg = args.get(i).ok_or_else(|| format!("missing {}", what))?;
+    arg.parse()
+        .map_err(|_| format!("{} is not a valid {}", arg, what))
+}
+
+register_command!(
+    "help",
+    "[command]",
+    "lists the commands or describes one",
+    help
+);
+fn help(_: &mut Egregoria, args: &[&str]) -> Result<String, String> {
+    if let Some(name) = args.first() {
+        let cmd = super::find_command(name).ok_or_else(|| format!("unknown command {}", name))?;
+        return Ok(format!("{} {}: {}", cmd.name, cmd.usage, cmd.help));
+    }
+    let mut cmds: Vec<_> = inventory::iter::<ConsoleCommand>.into_iter().collect();
+    cmds.sort_unstable_by_key(|c| c.name);
+    let mut s = String::from("clear: empties the console\n");
+    for cmd in cmds {
+        s += &format!("{} {}: {}\n", cmd.name, cmd.usage, cmd.help);
+    }
+    Ok(s)
+}
+
+register_command!(
+    "spawn",
+    "car|truck|bus [n]",
+    "parks n vehicles near the center of the screen",
+    spawn
+);
+fn spawn(goria: &mut Egregoria, args: &[&str]) -> Result<String, String> {
+    let kind = match args.first() {
+        Some(&"car") => VehicleKind::Car,
+        Some(&"truck") => VehicleKind::Truck,
+        Some(&"bus") => VehicleKind::Bus,
+        Some(x) => return Err(format!("cannot spawn {}", x)),
+        None => return Err("missing what to spawn".to_string()),
+    };
+    let n: u32 = if args.len() > 1 {
+        parse(args, 1, "count")?
+    } else {
+        1
+    };
+
+    let pos = goria.read::<Camera>().position.xy();
+    let spawned = (0..n)
+        .filter_map(|_| spawn_parked_vehicle(goria, kind, pos))
+        .count();
+    if spawned == 0 {
+        return Err("no free parking spot around".to_string());
+    }
+    Ok(format!("spawned {} {:?}", spawned, kind))
+}
+
+register_command!("tp", "x y", "moves the camera to that position", tp);
+fn tp(goria: &mut Egregoria, args: &[&str]) -> Result<String, String> {
+    let pos = vec2(parse(args, 0, "x")?, parse(args, 1, "y")?);
+    goria.write::<ConsoleRequests>().camera_pos = Some(pos);
+    Ok(String::new())
+}
+
+register_command!(
+    "warp",
+    "factor",
+    "sets the time warp, 0 pauses the game",
+    warp
+);
+fn warp(goria: &mut Egregoria, args: &[&str]) -> Result<String, String> {
+    let warp: f32 = parse(args, 0, "factor")?;
+    if !(0.0..=1000.0).contains(&warp) {
+        return Err("the time warp must be between 0 and 1000".to_string());
+    }
+    goria.write::<ConsoleRequests>().time_warp = Some(warp);
+    Ok(format!("time warp set to {}", warp))
+}
+
+register_command!("save", "", "saves the world to disk", save);
+fn save(goria: &mut Egregoria, _: &[&str]) -> Result<String, String> {
+    egregoria::save_to_disk(goria);
+    Ok("saved".to_string())
+}
+
+register_command!(
+    "load",
+    "",
+    "replaces the world by the last one saved to disk",
+    load
+);
+fn load(goria: &mut Egregoria, _: &[&str]) -> Result<String, String> {
+    // the runtime resources (collision world, reservations...) point into the old world, so the
+    // save is loaded into a fresh one like at startup and only the ui and scripts are kept
+    let mut fresh = Egregoria::init();
+    if let Some(map) = goria.take::<Map>() {
+        // keeps the building generator from the scripts, the map itself is replaced by the save
+        fresh.insert(map);
+    }
+    egregoria::load_from_disk(&mut fresh);
+
+    move_resource::<UiTextures>(goria, &mut fresh);
+    move_resource::<Camera>(goria, &mut fresh);
+    move_resource::<LuaConsole>(goria, &mut fresh);
+    move_resource::<BuildingShapes>(goria, &mut fresh);
+    move_resource::<LuaDesires>(goria, &mut fresh);
+    move_resource::<DebugObjs>(goria, &mut fresh);
+    move_resource::<ConsoleLua>(goria, &mut fresh);
+    move_resource::<ConsoleRequests>(goria, &mut fresh);
+
+    *goria = fresh;
+    Ok(format!("loaded {} entities", goria.world.len()))
+}
+
+fn move_resource<T: Resource + Send + Sync>(from: &mut Egregoria, to: &mut Egregoria) {
+    if let Some(res) = from.take::<T>() {
+        to.insert(res);
+    }
+}
+
+register_resource_noserialize!(ConsoleLua);
+/// The VM evaluating the snippets typed in the console, kept so globals survive between snippets
+#[derive(Default)]
+struct ConsoleLua(Option<Mutex<Lua>>);
+
+fn new_vm() -> Result<Lua, String> {
+    let l = mods::try_new().map_err(|e| e.to_string())?;
+    egregoria::scenarios::add_egregoria_lua_stdlib(&l);
+    Ok(l)
+}
+
+fn eval(l: &Lua, src: &str) -> Result<String, String> {
+    // like the standalone interpreter, try the snippet as an expression first
+    let f = l
+        .load(&format!("return {}", src))
+        .set_name("console")
+        .and_then(|x| x.into_function())
+        .or_else(|_| l.load(src).set_name("console")?.into_function())
+        .map_err(|e| e.to_string())?;
+
+    let values: MultiValue = mods::try_call(l, &f, ()).map_err(|e| e.to_string())?;
+    let tostring: Function = l.globals().get("tostring").map_err(|e| e.to_string())?;
+    let values = values
+        .into_iter()
+        .map(|v| tostring.call::<_, String>(v))
+        .collect::<mods::mlua::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+    Ok(values.join("\t"))
+}
+
+register_command!(
+    "lua",
+    "snippet",
+    "evaluates lua with the running world exposed as `world`",
+    lua
+);
+fn lua(goria: &mut Egregoria, args: &[&str]) -> Result<String, String> {
+    let src = args.first().copied().unwrap_or("");
+    if src.is_empty() {
+        return Err("missing snippet".to_string());
+    }
+
+    let l = match goria.write::<ConsoleLua>().0.take() {
+        Some(l) => l.into_inner().unwrap(),
+        None => new_vm()?,
+    };
+    let r = egregoria::scenarios::with_world(&l, goria, |l| eval(l, src))
+        .unwrap_or_else(|| Err("could not expose the world".to_string()));
+    goria.write::<ConsoleLua>().0 = Some(Mutex::new(l));
+    r
+}
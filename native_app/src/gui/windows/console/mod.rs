@@ -0,0 +1,265 @@
+use crate::gui::windows::ImguiWindow;
+use egregoria::Egregoria;
+use geom::Vec2;
+use imgui::{
+    im_str, FocusedWidget, HistoryDirection, ImString, InputTextCallback, InputTextCallbackHandler,
+    TextCallbackData, Ui,
+};
+use std::collections::VecDeque;
+
+/// Registers a console command, `$f` is called with the arguments following the command name
+macro_rules! register_command {
+    ($name: literal, $usage: literal, $help: literal, $f: expr) => {
+        inventory::submit! {
+            $crate::gui::windows::console::ConsoleCommand {
+                name: $name,
+                usage: $usage,
+                help: $help,
+                run: $f,
+            }
+        }
+    };
+}
+
+mod commands;
+
+/// Older output lines are dropped past that many
+const MAX_OUTPUT: usize = 300;
+
+/// Older commands are forgotten past that many
+const MAX_HISTORY: usize = 100;
+
+pub struct ConsoleCommand {
+    pub name: &'static str,
+    /// Arguments of the command, shown by `help`
+    pub usage: &'static str,
+    pub help: &'static str,
+    /// Returns the text to print, or why the command failed
+    pub run: fn(&mut Egregoria, &[&str]) -> Result<String, String>,
+}
+inventory::collect!(ConsoleCommand);
+
+fn find_command(name: &str) -> Option<&'static ConsoleCommand> {
+    inventory::iter::<ConsoleCommand>
+        .into_iter()
+        .find(|c| c.name == name)
+}
+
+register_resource_noserialize!(ConsoleRequests);
+/// Changes asked by the console to state that lives outside of the world,
+/// applied by the game loop on the next frame
+#[derive(Default)]
+pub struct ConsoleRequests {
+    pub time_warp: Option<f32>,
+    pub camera_pos: Option<Vec2>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum OutputKind {
+    Input,
+    Result,
+    Error,
+}
+
+pub struct DevConsole {
+    input: ImString,
+    output: VecDeque<(OutputKind, String)>,
+    history: Vec<String>,
+    /// Index in the history of the command being shown in the input, None when typing a new one
+    history_pos: Option<usize>,
+    scroll_to_bottom: bool,
+}
+
+impl Default for DevConsole {
+    fn default() -> Self {
+        Self {
+            input: ImString::with_capacity(256),
+            output: VecDeque::new(),
+            history: vec![],
+            history_pos: None,
+            scroll_to_bottom: false,
+        }
+    }
+}
+
+impl DevConsole {
+    fn print(&mut self, kind: OutputKind, text: impl Into<String>) {
+        for line in text.into().lines() {
+            if self.output.len() == MAX_OUTPUT {
+                self.output.pop_front();
+            }
+            self.output.push_back((kind, line.to_string()));
+        }
+        self.scroll_to_bottom = true;
+    }
+
+    fn exec(&mut self, goria: &mut Egregoria, line: &str) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        self.print(OutputKind::Input, format!("> {}", line));
+
+        self.history.retain(|x| x != line);
+        self.history.push(line.to_string());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.history_pos = None;
+
+        let (name, rest) = split_command(line);
+        if name == "clear" {
+            self.output.clear();
+            return;
+        }
+        let cmd = match find_command(name) {
+            Some(x) => x,
+            None => {
+                self.print(
+                    OutputKind::Error,
+                    format!("unknown command {}, try help", name),
+                );
+                return;
+            }
+        };
+
+        // lua snippets are passed as is, the other commands get their arguments split
+        let args: Vec<&str> = if name == "lua" {
+            vec![rest]
+        } else {
+            rest.split_whitespace().collect()
+        };
+        match (cmd.run)(goria, &args) {
+            Ok(x) if x.is_empty() => {}
+            Ok(x) => self.print(OutputKind::Result, x),
+            Err(e) => self.print(OutputKind::Error, e),
+        }
+    }
+}
+
+fn split_command(line: &str) -> (&str, &str) {
+    match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim_start()),
+        None => (line, ""),
+    }
+}
+
+/// Names of the commands starting with the prefix, sorted
+fn complete(prefix: &str) -> Vec<&'static str> {
+    let mut v: Vec<_> = inventory::iter::<ConsoleCommand>
+        .into_iter()
+        .map(|c| c.name)
+        .chain(std::iter::once("clear"))
+        .filter(|x| x.starts_with(prefix))
+        .collect();
+    v.sort_unstable();
+    v
+}
+
+fn common_prefix<'a>(words: &[&'a str]) -> &'a str {
+    let first = unwrap_or!(words.first(), return "");
+    let mut len = first.len();
+    for w in &words[1..] {
+        len = first
+            .bytes()
+            .zip(w.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count();
+    }
+    &first[..len]
+}
+
+struct InputCallbacks<'a> {
+    history: &'a [String],
+    history_pos: &'a mut Option<usize>,
+    /// Candidates to show when the completion is ambiguous
+    candidates: &'a mut Vec<&'static str>,
+}
+
+impl<'a> InputTextCallbackHandler for InputCallbacks<'a> {
+    fn on_completion(&mut self, mut data: TextCallbackData<'_>) {
+        let text = data.str().to_string();
+        if text.contains(char::is_whitespace) {
+            return;
+        }
+        let matches = complete(&text);
+        let prefix = common_prefix(&matches);
+        if matches.len() == 1 {
+            data.clear();
+            data.push_str(prefix);
+            data.push_str(" ");
+            return;
+        }
+        if prefix.len() > text.len() {
+            data.clear();
+            data.push_str(prefix);
+        }
+        *self.candidates = matches;
+    }
+
+    fn on_history(&mut self, dir: HistoryDirection, mut data: TextCallbackData<'_>) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() - 1;
+        *self.history_pos = match (dir, *self.history_pos) {
+            (HistoryDirection::Up, None) => Some(last),
+            (HistoryDirection::Up, Some(i)) => Some(i.saturating_sub(1)),
+            (HistoryDirection::Down, Some(i)) if i < last => Some(i + 1),
+            (HistoryDirection::Down, _) => None,
+        };
+        data.clear();
+        if let Some(i) = *self.history_pos {
+            data.push_str(&self.history[i]);
+        }
+    }
+}
+
+impl ImguiWindow for DevConsole {
+    fn render(&mut self, ui: &Ui, goria: &mut Egregoria) {
+        let footer = ui.frame_height_with_spacing() + 4.0;
+        imgui::ChildWindow::new(im_str!("console output"))
+            .size([0.0, -footer])
+            .build(ui, || {
+                for (kind, line) in &self.output {
+                    let color = match kind {
+                        OutputKind::Input => [0.6, 0.8, 1.0, 1.0],
+                        OutputKind::Result => [1.0, 1.0, 1.0, 1.0],
+                        OutputKind::Error => [1.0, 0.4, 0.4, 1.0],
+                    };
+                    ui.text_colored(color, im_str!("{}", line));
+                }
+                if self.scroll_to_bottom {
+                    ui.set_scroll_here_y_with_ratio(1.0);
+                    self.scroll_to_bottom = false;
+                }
+            });
+        ui.separator();
+
+        let mut candidates = vec![];
+        let entered = ui
+            .input_text(im_str!("##console input"), &mut self.input)
+            .enter_returns_true(true)
+            .callback(
+                InputTextCallback::COMPLETION | InputTextCallback::HISTORY,
+                InputCallbacks {
+                    history: &self.history,
+                    history_pos: &mut self.history_pos,
+                    candidates: &mut candidates,
+                },
+            )
+            .build();
+
+        if !candidates.is_empty() {
+            self.print(OutputKind::Result, candidates.join(" "));
+        }
+
+        if entered {
+            let line = self.input.to_str().to_string();
+            self.input.clear();
+            self.exec(goria, &line);
+            ui.set_keyboard_focus_here(FocusedWidget::Previous);
+        }
+    }
+}
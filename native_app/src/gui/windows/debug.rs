@@ -3,7 +3,7 @@
 use crate::gui::InspectedEntity;
 use common::{GameTime, SECONDS_PER_DAY};
 use egregoria::engine_interaction::{MouseInfo, RenderStats};
-use egregoria::map_dynamic::Itinerary;
+use egregoria::map_dynamic::{Itinerary, ParkingManagement};
 use egregoria::physics::CollisionWorld;
 use egregoria::utils::frame_log::FrameLog;
 use egregoria::Egregoria;
@@ -33,6 +33,7 @@ impl Default for DebugObjs {
             (false, "Debug splines", debug_spline),
             (false, "Debug turns", debug_turns),
             (false, "Debug road points", debug_road_points),
+            (false, "Show parking occupancy", show_parking_occupancy),
             (false, "Show grid", show_grid),
         ])
     }
@@ -128,6 +129,13 @@ pub fn debug_spline(tess: &mut Tesselator, world: &Egregoria) -> Option<()> {
                 },
             );
         }
+        if let Some(c) = road.clothoid() {
+            tess.set_color(Color::RED);
+            tess.draw_polyline(c.flatten(0.05).as_slice(), 1.0, 2.0);
+            tess.set_color(Color::PURPLE);
+            tess.draw_circle(c.from, 1.0, 1.0);
+            tess.draw_circle(c.end(), 1.0, 1.0);
+        }
     }
 
     Some(())
@@ -142,6 +150,18 @@ pub fn debug_road_points(tess: &mut Tesselator, world: &Egregoria) -> Option<()>
     Some(())
 }
 
+pub fn show_parking_occupancy(tess: &mut Tesselator, world: &Egregoria) -> Option<()> {
+    let map = world.read::<Map>();
+    let pm = world.read::<ParkingManagement>();
+    for (&id, occ) in pm.occupancy(&map).iter() {
+        let road = unwrap_or!(map.roads().get(id), continue);
+        let r = occ.ratio();
+        tess.set_color(Color::new(r, 1.0 - r, 0.0, 0.6));
+        tess.draw_polyline(road.generated_points().as_slice(), 1.0, road.width);
+    }
+    Some(())
+}
+
 pub fn debug_turns(tess: &mut Tesselator, world: &Egregoria) -> Option<()> {
     let map = world.read::<Map>();
     let lanes = map.lanes();
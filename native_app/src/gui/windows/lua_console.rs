@@ -0,0 +1,25 @@
+use egregoria::scenarios::console::{ConsoleLevel, LuaConsole};
+use egregoria::Egregoria;
+use imgui::{im_str, Ui};
+
+pub fn lua_console(ui: &Ui, goria: &mut Egregoria) {
+    let mut console = goria.write::<LuaConsole>();
+    if ui.small_button(im_str!("clear")) {
+        console.clear();
+    }
+    ui.same_line(0.0);
+    ui.text(im_str!("{} errors", console.errors));
+    ui.separator();
+
+    for line in console.lines() {
+        let text = im_str!("[{}] {}", line.source, line.msg);
+        match line.level {
+            ConsoleLevel::Info => ui.text_wrapped(&text),
+            ConsoleLevel::Error => {
+                let tok = ui.push_style_color(imgui::StyleColor::Text, [1.0, 0.4, 0.4, 1.0]);
+                ui.text_wrapped(&text);
+                tok.pop(ui);
+            }
+        }
+    }
+}
@@ -1,8 +1,11 @@
-use egregoria::map_dynamic::BuildingInfos;
+use egregoria::map_dynamic::{BuildingInfos, ParkingManagement, ParkingPricing};
 use egregoria::pedestrians::Pedestrian;
+use egregoria::scenarios::console::LuaConsole;
+use egregoria::scenarios::map_generators::MapGenerators;
 use egregoria::vehicles::Vehicle;
 use egregoria::Egregoria;
 use imgui::{im_str, Ui};
+use imgui_inspect::{InspectArgsDefault, InspectRenderDefault};
 use legion::IntoQuery;
 use map_model::Map;
 
@@ -30,6 +33,26 @@ pub fn map(ui: &Ui, goria: &mut Egregoria) {
         map.clear();
     }
 
+    let mut generators = goria.write::<MapGenerators>();
+    let mut console = goria.write::<LuaConsole>();
+    if !generators.is_loaded() {
+        generators.reload(&mut console);
+    }
+    ui.separator();
+    ui.text(im_str!("Lua generators"));
+    ui.input_int(im_str!("seed"), &mut generators.seed).build();
+    for name in generators.names() {
+        if ui.small_button(&im_str!("generate {}", name)) {
+            generators.generate(name, &mut map, &mut console);
+        }
+    }
+    if ui.small_button(im_str!("reload generators")) {
+        generators.reload(&mut console);
+    }
+    drop(console);
+    drop(generators);
+    ui.separator();
+
     ui.text(im_str!(
         "{} pedestrians",
         <&Pedestrian>::query().iter(&goria.world).count()
@@ -38,4 +61,18 @@ pub fn map(ui: &Ui, goria: &mut Egregoria) {
         "{} vehicles",
         <&Vehicle>::query().iter(&goria.world).count()
     ));
+
+    let mut pm = goria.write::<ParkingManagement>();
+    <ParkingPricing as InspectRenderDefault<ParkingPricing>>::render_mut(
+        &mut [&mut pm.pricing],
+        "parking pricing",
+        ui,
+        &InspectArgsDefault::default(),
+    );
+    let (reserved, total) = pm
+        .occupancy(&map)
+        .values()
+        .fold((0, 0), |(r, t), occ| (r + occ.reserved, t + occ.total));
+    ui.text(im_str!("{}/{} parking spots taken", reserved, total));
+    ui.text(im_str!("parking revenue: {}", pm.revenue()));
 }
@@ -1,5 +1,7 @@
 mod config;
+pub mod console;
 pub mod debug;
+mod lua_console;
 mod map;
 mod scenarios;
 
@@ -47,6 +49,12 @@ impl Default for ImguiWindows {
         );
         s.insert(imgui::im_str!("Config"), config::config, false);
         s.insert(imgui::im_str!("Debug"), debug::debug, false);
+        s.insert(imgui::im_str!("Lua"), lua_console::lua_console, false);
+        s.insert(
+            imgui::im_str!("Console"),
+            console::DevConsole::default(),
+            false,
+        );
         s
     }
 }
@@ -1,4 +1,5 @@
 use crate::gui::windows::ImguiWindow;
+use egregoria::scenarios::scenario_runner::{ScenarioResult, ScenarioStatus};
 use egregoria::Egregoria;
 use imgui::{im_str, Ui};
 
@@ -39,5 +40,21 @@ impl ImguiWindow for Scenarios {
         if ui.small_button(im_str!("reload scenario list")) {
             self.available_scenarios = available_scenarios();
         }
+
+        let result = goria.read::<ScenarioResult>();
+        if result.status == ScenarioStatus::NotStarted {
+            return;
+        }
+        ui.separator();
+        ui.text(im_str!("{}", result.name));
+        match result.status {
+            ScenarioStatus::Failure(ref reason) => {
+                ui.text_colored([1.0, 0.3, 0.3, 1.0], im_str!("failed: {}", reason))
+            }
+            ScenarioStatus::Success => ui.text_colored([0.3, 1.0, 0.3, 1.0], im_str!("success")),
+            _ => ui.text(im_str!("running")),
+        }
+        ui.text(im_str!("elapsed: {:.1}s", result.elapsed));
+        ui.text(im_str!("gridlocks: {}", result.gridlocks));
     }
 }
@@ -25,6 +25,20 @@ fn main() {
     log::set_max_level(LevelFilter::Debug);
     log_panics::init();
 
+    // egregoria --snapshot <file.svg|file.png> [width] renders the last save without a window
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|x| x == "--snapshot") {
+        let path = unwrap_or!(args.get(i + 1), {
+            log::error!("usage: --snapshot <file.svg|file.png> [width]");
+            std::process::exit(2)
+        });
+        let width = args.get(i + 2).and_then(|x| x.parse().ok()).unwrap_or(2048);
+        if snapshot::snapshot_save(path, width).is_none() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let mut ctx = Context::new();
 
     let state = game_loop::State::new(&mut ctx);
@@ -1,10 +1,9 @@
+use egregoria::map_dynamic::SignalActuation;
 use egregoria::utils::Restrict;
 use flat_spatial::storage::Storage;
 use geom::{lerp, vec2, Color, LinearColor, AABB};
-use map_model::{
-    BuildingKind, Lane, LaneKind, LotKind, Map, ProjectKind, TrafficBehavior, TurnKind,
-    CROSSWALK_WIDTH,
-};
+use map_model::{BuildingKind, Lane, Map, ProjectKind, TrafficBehavior, TurnKind, CROSSWALK_WIDTH};
+use snapshot::{tesselate_map, Z_ARROW, Z_CROSSWALK, Z_HOUSE, Z_SIGNAL, Z_TREE};
 use std::collections::HashMap;
 use std::ops::Mul;
 use wgpu_engine::{
@@ -41,17 +40,6 @@ pub struct RoadRenderer {
     last_config: usize,
 }
 
-const Z_LOT: f32 = 0.3;
-const Z_INTER_BG: f32 = 0.208;
-const Z_LANE_BG: f32 = 0.21;
-const Z_LANE: f32 = 0.22;
-const Z_SIDEWALK: f32 = 0.23;
-const Z_ARROW: f32 = 0.24;
-const Z_CROSSWALK: f32 = 0.25;
-const Z_HOUSE: f32 = 0.28;
-const Z_SIGNAL: f32 = 0.29;
-const Z_TREE: f32 = 0.5;
-
 impl RoadRenderer {
     pub fn new(gfx: &mut GfxContext) -> Self {
         let arrow_builder = SpriteBatchBuilder::from_path(gfx, "assets/arrow_one_way.png");
@@ -102,105 +90,8 @@ impl RoadRenderer {
     }
 
     fn map_mesh(&self, map: &Map, mut tess: Tesselator, gfx: &GfxContext) -> Option<Mesh> {
-        let low_col: LinearColor = common::config().road_low_col.into();
-        let mid_col: LinearColor = common::config().road_mid_col.into();
-        let hig_col: LinearColor = common::config().road_hig_col.into();
-        let line_col: LinearColor = common::config().road_line_col.into();
-
-        let inters = map.intersections();
-        let lanes = map.lanes();
-
-        for l in lanes.values() {
-            tess.set_color(line_col);
-
-            let or_src = l.orientation_from(l.src);
-            let or_dst = -l.orientation_from(l.dst);
-
-            tess.draw_polyline_with_dir(
-                l.points.as_slice(),
-                or_src,
-                or_dst,
-                Z_LANE_BG,
-                l.width + 0.5,
-            );
-
-            tess.set_color(match l.kind {
-                LaneKind::Walking => hig_col,
-                LaneKind::Parking => low_col,
-                _ => mid_col,
-            });
-            let z = match l.kind {
-                LaneKind::Walking => Z_SIDEWALK,
-                _ => Z_LANE,
-            };
-
-            tess.draw_polyline_with_dir(l.points.as_slice(), or_src, or_dst, z, l.width - 0.5);
-        }
-
-        // Intersections
-        let mut p = Vec::with_capacity(8);
-        for inter in inters.values() {
-            if inter.roads.is_empty() {
-                tess.set_color(line_col);
-                tess.draw_circle(inter.pos, Z_LANE_BG, 5.5);
-
-                tess.set_color(mid_col);
-                tess.draw_circle(inter.pos, Z_LANE, 5.0);
-                continue;
-            }
-
-            tess.set_color(mid_col);
-            tess.draw_filled_polygon(inter.polygon.as_slice(), Z_INTER_BG);
-
-            // Walking corners
-            for turn in inter
-                .turns()
-                .iter()
-                .filter(|turn| matches!(turn.kind, TurnKind::WalkingCorner))
-            {
-                tess.set_color(line_col);
-                let id = turn.id;
-
-                let w = lanes[id.src].width;
-
-                let first_dir = -lanes[id.src].orientation_from(id.parent);
-                let last_dir = lanes[id.dst].orientation_from(id.parent);
-
-                p.clear();
-                p.extend_from_slice(turn.points.as_slice());
-
-                tess.draw_polyline_with_dir(&p, first_dir, last_dir, Z_LANE_BG, w + 0.5);
-
-                tess.set_color(hig_col);
-
-                p.clear();
-                p.extend_from_slice(turn.points.as_slice());
-
-                let z = Z_SIDEWALK;
-
-                tess.draw_polyline_with_dir(&p, first_dir, last_dir, z, w - 0.5);
-            }
-        }
-
-        // Buildings mesh
-        for building in map.buildings().values() {
-            for (p, col) in &building.mesh.faces {
-                tess.set_color(*col);
-                tess.draw_filled_polygon(p.as_slice(), Z_HOUSE);
-            }
-        }
-
-        // Lots
-        for lot in map.lots().values() {
-            let col = match lot.kind {
-                LotKind::Unassigned => common::config().lot_unassigned_col,
-                LotKind::Residential => common::config().lot_residential_col,
-                LotKind::Commercial => common::config().lot_commercial_col,
-            };
-            tess.set_color(col);
-            tess.draw_filled_polygon(&lot.shape.corners, Z_LOT);
-        }
-        tess.meshbuilder.build(gfx)
+        tesselate_map(map, &mut tess);
+        Mesh::new(gfx, &tess.meshbuilder)
     }
 
     fn buildings_sprites(&mut self, map: &Map, gfx: &GfxContext) -> MultiSpriteBatch {
@@ -231,7 +122,13 @@ impl RoadRenderer {
             .collect()
     }
 
-    fn render_lane_signals(n: &Lane, sr: &mut Tesselator, time: u32) {
+    fn render_lane_signals(
+        n: &Lane,
+        map: &Map,
+        signals: &SignalActuation,
+        sr: &mut Tesselator,
+        time: u32,
+    ) {
         if n.control.is_always() {
             return;
         }
@@ -260,13 +157,15 @@ impl RoadRenderer {
         for i in -1..2 {
             sr.draw_circle(r_center + i as f32 * dir_perp * size, Z_SIGNAL, size * 0.5);
         }
-        sr.set_color(match n.control.get_behavior(time) {
+        let behavior = map.lane_behavior(n, None, signals.seconds(n.dst, time));
+
+        sr.set_color(match behavior {
             TrafficBehavior::RED | TrafficBehavior::STOP => LinearColor::RED,
             TrafficBehavior::ORANGE => LinearColor::ORANGE,
             TrafficBehavior::GREEN => LinearColor::GREEN,
         });
 
-        let offset = match n.control.get_behavior(time) {
+        let offset = match behavior {
             TrafficBehavior::RED => -size,
             TrafficBehavior::ORANGE => 0.0,
             TrafficBehavior::GREEN => size,
@@ -276,7 +175,7 @@ impl RoadRenderer {
         sr.draw_circle(r_center + offset * dir_perp, Z_SIGNAL, size * 0.5);
     }
 
-    fn signals_render(map: &Map, time: u32, sr: &mut Tesselator) {
+    fn signals_render(map: &Map, signals: &SignalActuation, time: u32, sr: &mut Tesselator) {
         match sr.cull_rect {
             Some(rect) => {
                 if rect.w().max(rect.h()) > 1500.0 {
@@ -292,12 +191,12 @@ impl RoadRenderer {
                     .flat_map(|id| map.roads()[id].lanes_iter())
                     .map(|(id, _)| &map.lanes()[id])
                 {
-                    Self::render_lane_signals(n, sr, time);
+                    Self::render_lane_signals(n, map, signals, sr, time);
                 }
             }
             None => {
                 for n in map.lanes().values() {
-                    Self::render_lane_signals(n, sr, time);
+                    Self::render_lane_signals(n, map, signals, sr, time);
                 }
             }
         }
@@ -438,6 +337,7 @@ impl RoadRenderer {
     pub fn render(
         &mut self,
         map: &mut Map,
+        signals: &SignalActuation,
         time: u32,
         tess: &mut Tesselator,
         ctx: &mut FrameContext,
@@ -483,7 +383,7 @@ impl RoadRenderer {
             ctx.draw(x);
         }
 
-        Self::signals_render(map, time, tess);
+        Self::signals_render(map, signals, time, tess);
 
         self.last_cam = screen;
     }
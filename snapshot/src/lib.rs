@@ -0,0 +1,10 @@
+//! Pictures of the map rendered on the cpu, so they can be made where the game can't open a window.
+
+#[macro_use]
+extern crate common;
+
+mod map_mesh;
+mod snapshot;
+
+pub use map_mesh::*;
+pub use snapshot::*;
@@ -0,0 +1,116 @@
+use geom::LinearColor;
+use map_model::{LaneKind, LotKind, Map, TurnKind};
+use tesselator::Tesselator;
+
+pub const Z_LOT: f32 = 0.3;
+pub const Z_INTER_BG: f32 = 0.208;
+pub const Z_LANE_BG: f32 = 0.21;
+pub const Z_LANE: f32 = 0.22;
+pub const Z_SIDEWALK: f32 = 0.23;
+pub const Z_ARROW: f32 = 0.24;
+pub const Z_CROSSWALK: f32 = 0.25;
+pub const Z_HOUSE: f32 = 0.28;
+pub const Z_SIGNAL: f32 = 0.29;
+pub const Z_TREE: f32 = 0.5;
+
+/// Lanes, intersections, buildings and lots, everything of the map that doesn't need a texture
+pub fn tesselate_map(map: &Map, tess: &mut Tesselator) {
+    let low_col: LinearColor = common::config().road_low_col.into();
+    let mid_col: LinearColor = common::config().road_mid_col.into();
+    let hig_col: LinearColor = common::config().road_hig_col.into();
+    let line_col: LinearColor = common::config().road_line_col.into();
+
+    let inters = map.intersections();
+    let lanes = map.lanes();
+
+    for l in lanes.values() {
+        tess.set_color(line_col);
+
+        let or_src = l.orientation_from(l.src);
+        let or_dst = -l.orientation_from(l.dst);
+
+        tess.draw_polyline_with_dir(
+            l.points.as_slice(),
+            or_src,
+            or_dst,
+            Z_LANE_BG,
+            l.width + 0.5,
+        );
+
+        tess.set_color(match l.kind {
+            LaneKind::Walking => hig_col,
+            LaneKind::Parking => low_col,
+            _ => mid_col,
+        });
+        let z = match l.kind {
+            LaneKind::Walking => Z_SIDEWALK,
+            _ => Z_LANE,
+        };
+
+        tess.draw_polyline_with_dir(l.points.as_slice(), or_src, or_dst, z, l.width - 0.5);
+    }
+
+    // Intersections
+    let mut p = Vec::with_capacity(8);
+    for inter in inters.values() {
+        if inter.roads.is_empty() {
+            tess.set_color(line_col);
+            tess.draw_circle(inter.pos, Z_LANE_BG, 5.5);
+
+            tess.set_color(mid_col);
+            tess.draw_circle(inter.pos, Z_LANE, 5.0);
+            continue;
+        }
+
+        tess.set_color(mid_col);
+        tess.draw_filled_polygon(inter.polygon.as_slice(), Z_INTER_BG);
+
+        // Walking corners
+        for turn in inter
+            .turns()
+            .iter()
+            .filter(|turn| matches!(turn.kind, TurnKind::WalkingCorner))
+        {
+            tess.set_color(line_col);
+            let id = turn.id;
+
+            let w = lanes[id.src].width;
+
+            let first_dir = -lanes[id.src].orientation_from(id.parent);
+            let last_dir = lanes[id.dst].orientation_from(id.parent);
+
+            p.clear();
+            p.extend_from_slice(turn.points.as_slice());
+
+            tess.draw_polyline_with_dir(&p, first_dir, last_dir, Z_LANE_BG, w + 0.5);
+
+            tess.set_color(hig_col);
+
+            p.clear();
+            p.extend_from_slice(turn.points.as_slice());
+
+            let z = Z_SIDEWALK;
+
+            tess.draw_polyline_with_dir(&p, first_dir, last_dir, z, w - 0.5);
+        }
+    }
+
+    // Buildings mesh
+    for building in map.buildings().values() {
+        for (p, col) in &building.mesh.faces {
+            tess.set_color(*col);
+            tess.draw_filled_polygon(p.as_slice(), Z_HOUSE);
+        }
+    }
+
+    // Lots
+    for lot in map.lots().values() {
+        let col = match lot.kind {
+            LotKind::Unassigned => common::config().lot_unassigned_col,
+            LotKind::Residential => common::config().lot_residential_col,
+            LotKind::Commercial => common::config().lot_commercial_col,
+        };
+        tess.set_color(col);
+        tess.draw_filled_polygon(&lot.shape.corners, Z_LOT);
+    }
+}
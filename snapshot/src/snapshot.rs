@@ -0,0 +1,369 @@
+use crate::{tesselate_map, Z_TREE};
+use egregoria::pedestrians::Pedestrian;
+use egregoria::rendering::assets::AssetRender;
+use egregoria::Egregoria;
+use geom::{vec2, Color, LinearColor, Transform, Vec2, AABB};
+use legion::query::*;
+use map_model::Map;
+use std::fmt::Write;
+use std::path::Path;
+use tesselator::Tesselator;
+
+const Z_AGENT: f32 = 0.6;
+
+/// Margin around the map in meters
+const MARGIN: f32 = 20.0;
+
+/// Something moving on the map, drawn as a rectangle
+pub struct Agent {
+    pub pos: Vec2,
+    pub dir: Vec2,
+    pub length: f32,
+    pub col: LinearColor,
+}
+
+/// Vehicles and pedestrians of the world
+pub fn world_agents(goria: &Egregoria) -> Vec<Agent> {
+    let vehicles = <(&Transform, &AssetRender)>::query()
+        .iter(&goria.world)
+        .filter(|(_, ar)| !ar.hide)
+        .map(|(trans, ar)| Agent {
+            pos: trans.position(),
+            dir: trans.direction(),
+            length: ar.scale,
+            col: ar.tint.into(),
+        });
+
+    let pedestrians = <(&Transform, &Pedestrian)>::query()
+        .iter(&goria.world)
+        .map(|(trans, _)| Agent {
+            pos: trans.position(),
+            dir: trans.direction(),
+            length: 0.8,
+            col: LinearColor::gray(0.1),
+        });
+
+    vehicles.chain(pedestrians).collect()
+}
+
+struct Triangle {
+    p: [Vec2; 3],
+    z: f32,
+    col: LinearColor,
+}
+
+/// A picture of the map rendered on the cpu, using the same mesh as the game's renderer.
+/// Textured objects are approximated: trees are disks and agents are rectangles.
+pub struct Snapshot {
+    tess: Tesselator,
+}
+
+impl Snapshot {
+    pub fn new(map: &Map) -> Self {
+        let mut tess = Tesselator::new(None, 15.0);
+        tesselate_map(map, &mut tess);
+
+        let tree_col = LinearColor::from(common::config().tree_col);
+        for h in map.trees.grid.handles() {
+            let (pos, t) = unwrap_or!(map.trees.grid.get(h), continue);
+            tess.set_color(t.col * tree_col);
+            tess.draw_circle(pos, Z_TREE, t.size * 0.4);
+        }
+
+        Self { tess }
+    }
+
+    pub fn add_agents(&mut self, agents: impl IntoIterator<Item = Agent>) {
+        for agent in agents {
+            self.tess.set_color(agent.col);
+            self.tess.draw_rect_cos_sin(
+                agent.pos,
+                Z_AGENT,
+                agent.length,
+                agent.length * 0.5,
+                agent.dir,
+            );
+        }
+    }
+
+    /// Triangles in painting order, the mesh is drawn by increasing z
+    fn triangles(&self) -> Vec<Triangle> {
+        let mb = &self.tess.meshbuilder;
+        let mut tris: Vec<Triangle> = mb
+            .indices
+            .chunks_exact(3)
+            .map(|t| {
+                let v = |i: u32| {
+                    let p = mb.vertices[i as usize].position;
+                    vec2(p[0], p[1])
+                };
+                let first = &mb.vertices[t[0] as usize];
+                let [r, g, b, a] = first.color;
+                Triangle {
+                    p: [v(t[0]), v(t[1]), v(t[2])],
+                    z: first.position[2],
+                    col: LinearColor::new(r, g, b, a),
+                }
+            })
+            .collect();
+        tris.sort_by(|a, b| a.z.partial_cmp(&b.z).unwrap_or(std::cmp::Ordering::Equal));
+        tris
+    }
+
+    /// Area covered by the picture in world coordinates
+    pub fn bounds(&self) -> AABB {
+        let mut it = self
+            .tess
+            .meshbuilder
+            .vertices
+            .iter()
+            .map(|v| vec2(v.position[0], v.position[1]));
+        let first = unwrap_or!(it.next(), return AABB::new(Vec2::ZERO, Vec2::splat(1.0)));
+        it.fold(AABB::new(first, first), |b, p| {
+            AABB::new(b.ll.min(p), b.ur.max(p))
+        })
+        .expand(MARGIN)
+    }
+
+    /// Height in pixels of a picture of that width, keeping the aspect ratio of the map
+    pub fn height(&self, width: u32) -> u32 {
+        let b = self.bounds();
+        ((width as f32 * b.h() / b.w()).ceil() as u32).max(1)
+    }
+
+    pub fn to_svg(&self, width: u32) -> String {
+        let b = self.bounds();
+        let mut s = String::new();
+        let _ = writeln!(
+            s,
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" "#,
+                r#"width="{}" height="{}" viewBox="{} {} {} {}">"#
+            ),
+            width,
+            self.height(width),
+            b.ll.x,
+            -b.ur.y,
+            b.w(),
+            b.h()
+        );
+        let _ = writeln!(
+            s,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            b.ll.x,
+            -b.ur.y,
+            b.w(),
+            b.h(),
+            svg_color(common::config().grass_col.into()).0
+        );
+
+        // the world's y axis goes up while svg's goes down
+        s += "<g transform=\"scale(1,-1)\">\n";
+
+        // consecutive triangles of the same layer and color are merged so they don't show seams
+        let mut current: Option<(f32, [f32; 4])> = None;
+        for t in self.triangles() {
+            let key = (t.z, [t.col.r, t.col.g, t.col.b, t.col.a]);
+            if current != Some(key) {
+                if current.is_some() {
+                    s += "\"/>\n";
+                }
+                let (fill, opacity) = svg_color(t.col);
+                let _ = write!(s, r#"<path fill="{}" fill-opacity="{}" d=""#, fill, opacity);
+                current = Some(key);
+            }
+            let [p0, p1, p2] = t.p;
+            let _ = write!(
+                s,
+                "M{:.2} {:.2}L{:.2} {:.2}L{:.2} {:.2}Z",
+                p0.x, p0.y, p1.x, p1.y, p2.x, p2.y
+            );
+        }
+        if current.is_some() {
+            s += "\"/>\n";
+        }
+        s += "</g>\n</svg>\n";
+        s
+    }
+
+    /// Rasterizes the picture in software, sampling each pixel at its center
+    pub fn to_png(&self, width: u32) -> image::RgbaImage {
+        let b = self.bounds();
+        let height = self.height(width);
+        let scale = width as f32 / b.w();
+        let bg: LinearColor = common::config().grass_col.into();
+
+        let mut img = Raster {
+            w: width as usize,
+            h: height as usize,
+            pixels: vec![bg; width as usize * height as usize],
+        };
+
+        for t in self.triangles() {
+            let to_px = |p: Vec2| vec2((p.x - b.ll.x) * scale, (b.ur.y - p.y) * scale);
+            img.fill_triangle([to_px(t.p[0]), to_px(t.p[1]), to_px(t.p[2])], t.col);
+        }
+
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            let c = Color::from(img.pixels[y as usize * img.w + x as usize]);
+            let to_u8 = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+            image::Rgba([to_u8(c.r), to_u8(c.g), to_u8(c.b), 255])
+        })
+    }
+
+    /// Writes the picture as an svg or a png depending on the extension of the path
+    pub fn save(&self, path: impl AsRef<Path>, width: u32) -> Option<()> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .map(|x| x.to_string_lossy().to_ascii_lowercase());
+        let r = match ext.as_deref() {
+            Some("svg") => std::fs::write(path, self.to_svg(width)).map_err(|e| e.to_string()),
+            Some("png") => self.to_png(width).save(path).map_err(|e| e.to_string()),
+            _ => Err("the snapshot must be an svg or a png".to_string()),
+        };
+        match r {
+            Ok(()) => {
+                log::info!("saved snapshot to {}", path.display());
+                Some(())
+            }
+            Err(e) => {
+                log::error!("could not save snapshot to {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Returns the fill and the fill-opacity attributes of a color
+fn svg_color(col: LinearColor) -> (String, f32) {
+    let c = Color::from(col);
+    let to_u8 = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+    (
+        format!("#{:02x}{:02x}{:02x}", to_u8(c.r), to_u8(c.g), to_u8(c.b)),
+        col.a,
+    )
+}
+
+/// Pixels are blended in linear space and only converted to srgb once done
+struct Raster {
+    w: usize,
+    h: usize,
+    pixels: Vec<LinearColor>,
+}
+
+impl Raster {
+    fn fill_triangle(&mut self, [a, b, c]: [Vec2; 3], col: LinearColor) {
+        let edge = |p0: Vec2, p1: Vec2, p: Vec2| {
+            (p1.x - p0.x) * (p.y - p0.y) - (p1.y - p0.y) * (p.x - p0.x)
+        };
+        let area = edge(a, b, c);
+        if area == 0.0 {
+            return;
+        }
+        let sign = area.signum();
+
+        let ll = a.min(b).min(c);
+        let ur = a.max(b).max(c);
+        let x0 = ll.x.floor().max(0.0) as usize;
+        let y0 = ll.y.floor().max(0.0) as usize;
+        let x1 = (ur.x.ceil().max(0.0) as usize).min(self.w);
+        let y1 = (ur.y.ceil().max(0.0) as usize).min(self.h);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = vec2(x as f32 + 0.5, y as f32 + 0.5);
+                if edge(b, c, p) * sign < 0.0
+                    || edge(c, a, p) * sign < 0.0
+                    || edge(a, b, p) * sign < 0.0
+                {
+                    continue;
+                }
+                let dst = &mut self.pixels[y * self.w + x];
+                *dst = LinearColor::new(
+                    col.r * col.a + dst.r * (1.0 - col.a),
+                    col.g * col.a + dst.g * (1.0 - col.a),
+                    col.b * col.a + dst.b * (1.0 - col.a),
+                    1.0,
+                );
+            }
+        }
+    }
+}
+
+/// Loads the last save and writes a snapshot of its map and agents, without opening a window
+pub fn snapshot_save(path: &str, width: u32) -> Option<()> {
+    let mut goria = Egregoria::init();
+    egregoria::load_from_disk(&mut goria);
+
+    let mut snapshot = Snapshot::new(&goria.read::<Map>());
+    snapshot.add_agents(world_agents(&goria));
+    snapshot.save(path, width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{svg_color, Agent, Snapshot};
+    use geom::{vec2, Color, LinearColor, Vec2};
+    use map_model::{LanePatternBuilder, Map, RoadSegmentKind};
+
+    const WIDTH: u32 = 240;
+
+    fn to_u8(c: Color) -> [u8; 4] {
+        let to_u8 = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+        [to_u8(c.r), to_u8(c.g), to_u8(c.b), 255]
+    }
+
+    /// A single road going east with a red car in its middle
+    fn tiny_snapshot() -> Snapshot {
+        let mut map = Map::empty();
+        let a = map.add_intersection(vec2(0.0, 0.0));
+        let b = map.add_intersection(vec2(200.0, 0.0));
+        let pattern = LanePatternBuilder::new().build();
+        map.connect(a, b, &pattern, RoadSegmentKind::Straight);
+
+        let mut snapshot = Snapshot::new(&map);
+        snapshot.add_agents(vec![Agent {
+            pos: vec2(100.0, 0.0),
+            dir: Vec2::UNIT_X,
+            length: 10.0,
+            col: LinearColor::RED,
+        }]);
+        snapshot
+    }
+
+    #[test]
+    fn svg_has_background_and_layers() {
+        let snapshot = tiny_snapshot();
+        let svg = snapshot.to_svg(WIDTH);
+        let grass = svg_color(common::config().grass_col.into()).0;
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(&format!(r#"width="{}""#, WIDTH)));
+        assert!(svg.contains(&format!(r#"fill="{}""#, grass)));
+        assert!(svg.contains(r##"<path fill="#ff0000""##));
+    }
+
+    #[test]
+    fn png_pixels() {
+        let snapshot = tiny_snapshot();
+        let img = snapshot.to_png(WIDTH);
+        assert_eq!(img.width(), WIDTH);
+        assert_eq!(img.height(), snapshot.height(WIDTH));
+
+        let b = snapshot.bounds();
+        let scale = WIDTH as f32 / b.w();
+        let pixel = |p: Vec2| {
+            let x = ((p.x - b.ll.x) * scale) as u32;
+            let y = ((b.ur.y - p.y) * scale) as u32;
+            img.get_pixel(x, y).0
+        };
+        let grass = to_u8(Color::from(LinearColor::from(common::config().grass_col)));
+
+        assert_eq!(img.get_pixel(0, 0).0, grass);
+        assert_eq!(pixel(vec2(100.0, 0.0)), [255, 0, 0, 255]);
+        assert_ne!(pixel(vec2(50.0, 0.0)), grass);
+        assert_eq!(pixel(vec2(100.0, b.ur.y - 1.0)), grass);
+    }
+}
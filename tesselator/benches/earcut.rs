@@ -0,0 +1,41 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use tesselator::earcut::earcut;
+
+/// Flat list of coordinates of a polygon with n points, concave if `spikes` is true
+fn polygon(n: usize, spikes: bool) -> Vec<f32> {
+    (0..n)
+        .flat_map(|i| {
+            let angle = i as f32 * std::f32::consts::PI * 2.0 / n as f32;
+            let r = if spikes && i % 2 == 1 { 60.0 } else { 100.0 };
+            vec![angle.cos() * r, angle.sin() * r]
+        })
+        .collect()
+}
+
+fn bench_earcut(c: &mut Criterion) {
+    let mut group = c.benchmark_group("earcut");
+    // Below 40 points the z-order hashing isn't used
+    for &n in &[8, 32, 128, 1024] {
+        let convex = polygon(n, false);
+        group.bench_with_input(BenchmarkId::new("convex", n), &convex, |b, data| {
+            b.iter(|| {
+                let mut count = 0;
+                earcut(data, |_, _, _| count += 1);
+                count
+            })
+        });
+
+        let star = polygon(n, true);
+        group.bench_with_input(BenchmarkId::new("star", n), &star, |b, data| {
+            b.iter(|| {
+                let mut count = 0;
+                earcut(data, |_, _, _| count += 1);
+                count
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_earcut);
+criterion_main!(benches);
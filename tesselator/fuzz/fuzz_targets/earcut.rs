@@ -0,0 +1,17 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use tesselator::earcut::earcut;
+
+fuzz_target!(|points: Vec<(i16, i16)>| {
+    let data: Vec<f32> = points
+        .iter()
+        .flat_map(|&(x, y)| vec![x as f32 * 0.1, y as f32 * 0.1])
+        .collect();
+
+    let mut triangles = 0;
+    earcut(&data, |a, b, c| {
+        assert!(a < points.len() && b < points.len() && c < points.len());
+        triangles += 1;
+    });
+    assert!(triangles <= points.len().saturating_sub(2));
+});
@@ -0,0 +1,18 @@
+//! Geometry of the meshes drawn by the renderer, kept free of any gpu dependency
+//! so the map can also be drawn on the cpu.
+
+macro_rules! unwrap_or {
+    ($e: expr, $t: expr) => {
+        match $e {
+            Some(x) => x,
+            None => $t,
+        }
+    };
+}
+
+pub mod earcut;
+mod mesh;
+mod tesselator;
+
+pub use mesh::*;
+pub use tesselator::*;
@@ -0,0 +1,44 @@
+pub type IndexType = u32;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ColoredVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for ColoredVertex {}
+unsafe impl bytemuck::Zeroable for ColoredVertex {}
+
+#[derive(Default)]
+pub struct MeshBuilder {
+    pub vertices: Vec<ColoredVertex>,
+    pub indices: Vec<IndexType>,
+}
+
+impl MeshBuilder {
+    pub fn new() -> Self {
+        Self {
+            vertices: vec![],
+            indices: vec![],
+        }
+    }
+
+    pub fn extend(&mut self, vertices: &[ColoredVertex], indices: &[IndexType]) -> &mut Self {
+        let offset = self.vertices.len() as IndexType;
+        self.vertices.extend_from_slice(vertices);
+        self.indices.extend(indices.iter().map(|x| x + offset));
+        self
+    }
+
+    #[inline(always)]
+    pub fn extend_with(&mut self, f: impl Fn(&mut Vec<ColoredVertex>, &mut dyn FnMut(IndexType))) {
+        let offset = self.vertices.len() as IndexType;
+        let vertices = &mut self.vertices;
+        let indices = &mut self.indices;
+        let mut x = move |index: IndexType| {
+            indices.push(index + offset);
+        };
+        f(vertices, &mut x);
+    }
+}
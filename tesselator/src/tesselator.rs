@@ -1,4 +1,4 @@
-use crate::geometry::earcut::earcut;
+use crate::earcut::earcut;
 use crate::{ColoredVertex, MeshBuilder};
 use geom::{vec2, Intersect, LinearColor, Segment, Vec2, AABB};
 
@@ -1,55 +1,32 @@
-use crate::{compile_shader, ColoredVertex, Drawable, GfxContext, IndexType, VBDesc};
+use crate::{compile_shader, ColoredVertex, Drawable, GfxContext, MeshBuilder, VBDesc};
 use std::rc::Rc;
 use wgpu::util::DeviceExt;
 use wgpu::{IndexFormat, RenderPass, RenderPipeline};
 
-#[derive(Default)]
-pub struct MeshBuilder {
-    pub vertices: Vec<ColoredVertex>,
-    pub indices: Vec<IndexType>,
+#[derive(Clone)]
+pub struct Mesh {
+    pub vertex_buffer: Rc<wgpu::Buffer>,
+    pub index_buffer: Rc<wgpu::Buffer>,
+    pub n_indices: u32,
+    pub alpha_blend: bool,
 }
 
-impl MeshBuilder {
-    pub fn new() -> Self {
-        Self {
-            vertices: vec![],
-            indices: vec![],
-        }
-    }
-
-    pub fn extend(&mut self, vertices: &[ColoredVertex], indices: &[IndexType]) -> &mut Self {
-        let offset = self.vertices.len() as IndexType;
-        self.vertices.extend_from_slice(vertices);
-        self.indices.extend(indices.iter().map(|x| x + offset));
-        self
-    }
-
-    #[inline(always)]
-    pub fn extend_with(&mut self, f: impl Fn(&mut Vec<ColoredVertex>, &mut dyn FnMut(IndexType))) {
-        let offset = self.vertices.len() as IndexType;
-        let vertices = &mut self.vertices;
-        let indices = &mut self.indices;
-        let mut x = move |index: IndexType| {
-            indices.push(index + offset);
-        };
-        f(vertices, &mut x);
-    }
-
-    pub fn build(self, ctx: &GfxContext) -> Option<Mesh> {
-        if self.vertices.is_empty() {
+impl Mesh {
+    pub fn new(ctx: &GfxContext, mb: &MeshBuilder) -> Option<Self> {
+        if mb.vertices.is_empty() {
             return None;
         }
         let vertex_buffer = Rc::new(ctx.device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: None,
-                contents: bytemuck::cast_slice(&self.vertices),
+                contents: bytemuck::cast_slice(&mb.vertices),
                 usage: wgpu::BufferUsage::VERTEX,
             },
         ));
         let index_buffer = Rc::new(ctx.device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: None,
-                contents: bytemuck::cast_slice(&self.indices),
+                contents: bytemuck::cast_slice(&mb.indices),
                 usage: wgpu::BufferUsage::INDEX,
             },
         ));
@@ -57,20 +34,12 @@ impl MeshBuilder {
         Some(Mesh {
             vertex_buffer,
             index_buffer,
-            n_indices: self.indices.len() as u32,
+            n_indices: mb.indices.len() as u32,
             alpha_blend: false,
         })
     }
 }
 
-#[derive(Clone)]
-pub struct Mesh {
-    pub vertex_buffer: Rc<wgpu::Buffer>,
-    pub index_buffer: Rc<wgpu::Buffer>,
-    pub n_indices: u32,
-    pub alpha_blend: bool,
-}
-
 impl Drawable for Mesh {
     fn create_pipeline(gfx: &GfxContext) -> RenderPipeline {
         let vert = compile_shader("assets/shaders/mesh_shader.vert", None);
@@ -23,8 +23,6 @@ pub trait Shaders: 'static {
     fn frag_shader() -> CompiledShader;
 }
 
-pub type IndexType = u32;
-
 pub trait Drawable {
     fn create_pipeline(gfx: &GfxContext) -> RenderPipeline
     where
@@ -1,4 +0,0 @@
-pub mod earcut;
-mod tesselator;
-
-pub use tesselator::*;
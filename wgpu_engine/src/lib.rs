@@ -1,11 +1,7 @@
-#[macro_use]
-extern crate common;
-
 #[macro_use]
 pub mod u8slice;
 
 mod draweables;
-mod geometry;
 mod gfx;
 pub mod lighting;
 mod shader;
@@ -14,7 +10,6 @@ mod uniform;
 mod vertex_types;
 
 pub use draweables::*;
-pub use geometry::*;
 pub use gfx::*;
 pub use shader::*;
 pub use texture::*;
@@ -22,6 +17,7 @@ pub use u8slice::*;
 pub use uniform::*;
 pub use vertex_types::*;
 
+pub use tesselator::{earcut, ColoredVertex, IndexType, MeshBuilder, Tesselator};
 pub use wgpu;
 
 trait VBDesc {
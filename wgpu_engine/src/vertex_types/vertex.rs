@@ -1,14 +1,11 @@
-use crate::VBDesc;
+use crate::{ColoredVertex, ToU8Slice, VBDesc};
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-pub struct ColoredVertex {
-    pub position: [f32; 3],
-    pub color: [f32; 4],
+impl ToU8Slice for ColoredVertex {
+    fn cast_slice(self_slice: &[Self]) -> &[u8] {
+        bytemuck::cast_slice(self_slice)
+    }
 }
 
-u8slice_impl!(ColoredVertex);
-
 impl VBDesc for ColoredVertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         use std::mem;